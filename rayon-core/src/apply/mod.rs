@@ -0,0 +1,68 @@
+use join::join_context;
+use registry;
+
+#[cfg(test)]
+mod test;
+
+/// Applies `f` to every element of `data` in parallel, passing each
+/// element's index within the slice alongside a mutable reference to
+/// it. The slice is recursively split with `split_at_mut()` and
+/// `join()` until pieces are at most `min_chunk` elements long; each
+/// such leaf is then processed serially, in index order. This covers
+/// the single most common parallel operation -- mutate every element
+/// of a slice -- without hand-rolling the recursion.
+///
+/// `min_chunk` bounds the splitting overhead: a larger value means
+/// fewer, coarser tasks. A value of zero means "use the pool-wide
+/// default" (see `Configuration::min_split_len()`). An empty slice
+/// is a no-op.
+///
+/// Splitting is also *adaptive*, exactly as in `reduce_range()`: a
+/// split budget starting at the pool's thread count is halved per
+/// level and renewed whenever a piece is stolen, so pieces stop
+/// splitting -- even above `min_chunk` -- once there is no evidence
+/// of an idle worker to serve them (see `FnContext::migrated()`).
+///
+/// # Panics
+///
+/// If `f` panics, that panic is propagated to the caller; per the
+/// `join()` contract, propagation still waits for the sibling pieces
+/// to finish first, so no piece is left running against the slice
+/// after `par_apply()` has returned.
+pub fn par_apply<T, F>(data: &mut [T], min_chunk: usize, f: F)
+    where T: Send,
+          F: Fn(usize, &mut T) + Sync
+{
+    let min_chunk = if min_chunk == 0 {
+        registry::current_min_split_len()
+    } else {
+        min_chunk
+    };
+    let splits = ::current_num_threads();
+    par_apply_helper(data, 0, min_chunk, splits, &f);
+}
+
+fn par_apply_helper<T, F>(data: &mut [T], offset: usize, min_chunk: usize, splits: usize, f: &F)
+    where T: Send,
+          F: Fn(usize, &mut T) + Sync
+{
+    if data.len() <= min_chunk || splits == 0 {
+        for (i, item) in data.iter_mut().enumerate() {
+            f(offset + i, item);
+        }
+    } else {
+        let mid = data.len() / 2;
+        let (left, right) = data.split_at_mut(mid);
+        join_context(|_| par_apply_helper(left, offset, min_chunk, splits / 2, f),
+                     |context| {
+                         // As in `reduce_range()`: a steal renews the
+                         // budget, a local run halves it.
+                         let splits = if context.migrated() {
+                             ::current_num_threads()
+                         } else {
+                             splits / 2
+                         };
+                         par_apply_helper(right, offset + mid, min_chunk, splits, f)
+                     });
+    }
+}
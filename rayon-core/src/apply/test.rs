@@ -0,0 +1,55 @@
+//! Tests for the parallel slice application.
+
+use apply::*;
+use unwind;
+
+#[test]
+fn squares_every_element() {
+    let mut v: Vec<usize> = (0..10 * 1024).collect();
+    par_apply(&mut v, 64, |_, x| *x *= *x);
+    for (i, &x) in v.iter().enumerate() {
+        assert_eq!(x, i * i);
+    }
+}
+
+#[test]
+fn indices_match_positions() {
+    // An odd, prime length, so the recursive splits never come out
+    // even: every leaf must still see the right base offset.
+    let mut v = vec![0; 1009];
+    par_apply(&mut v, 8, |i, x| *x = i);
+    for (i, &x) in v.iter().enumerate() {
+        assert_eq!(x, i);
+    }
+}
+
+#[test]
+fn empty_slice_is_a_no_op() {
+    let mut v: Vec<usize> = vec![];
+    par_apply(&mut v, 64, |_, x| *x += 1);
+    assert!(v.is_empty());
+}
+
+#[test]
+fn zero_min_chunk_uses_pool_default() {
+    // The global pool's default minimum split length is one, so only
+    // the adaptive split budget bounds the recursion here.
+    let mut v = vec![1; 100];
+    par_apply(&mut v, 0, |i, x| *x += i);
+    for (i, &x) in v.iter().enumerate() {
+        assert_eq!(x, i + 1);
+    }
+}
+
+#[test]
+fn panic_in_f_propagates() {
+    let mut v = vec![0; 1024];
+    let result = unwind::halt_unwinding(|| {
+        par_apply(&mut v, 16, |i, _| {
+            if i == 512 {
+                panic!("boom")
+            }
+        });
+    });
+    assert!(result.is_err(), "panic in f should propagate");
+}
@@ -0,0 +1,247 @@
+use ::{Configuration, ThreadPoolBuildError};
+use job::HeapJob;
+use latch::{Latch, LatchProbe, LockLatch, SpinLatch};
+use registry;
+use registry::{Registry, WorkerThread};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use unwind;
+
+#[cfg(test)]
+mod test;
+
+/// Executes `op`, which is expected to block (e.g. on file I/O, a
+/// syscall, or a channel), without holding a Rayon worker thread
+/// hostage for the duration.
+///
+/// When called on a worker thread, `op` is handed off to a temporary
+/// native thread, and the worker keeps executing (and stealing) pool
+/// jobs until `op` completes; the pool thus maintains its effective
+/// parallelism while the blocking operation is in flight. When called
+/// from outside the pool, `op` simply runs inline, since no worker is
+/// starved by blocking there.
+///
+/// Note that the temporary thread is spawned per call: this is cheap
+/// relative to blocking I/O, but makes `blocking()` a poor fit for
+/// short CPU-bound work -- just run that directly.
+///
+/// # Panics
+///
+/// If `op` panics, that panic is propagated to the caller.
+pub fn blocking<F, R>(op: F) -> R
+    where F: FnOnce() -> R + Send,
+          R: Send
+{
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        if worker_thread.is_null() {
+            return op();
+        }
+
+        let job = BlockingJob::new(op, (*worker_thread).registry().clone());
+
+        // We assert that the `BlockingJob` remains valid for as long
+        // as the temporary thread can touch it: the job lives on this
+        // stack frame, and we do not leave this frame before the
+        // latch (set *after* the last access to the job) is set.
+        // `thread::spawn` requires a `'static` closure, but `op` may
+        // borrow from the enclosing frame. Crossing over as a plain
+        // fn pointer plus an address erases those lifetimes; this is
+        // sound for the same reason as above.
+        let job_addr = &job as *const BlockingJob<F, R> as usize;
+        let run_fn: unsafe fn(usize) = BlockingJob::<F, R>::run;
+        let spawned = thread::Builder::new()
+            .name("rayon blocking helper".to_string())
+            .spawn(move || run_fn(job_addr));
+
+        match spawned {
+            Ok(_) => {
+                // Keep this worker useful while `op` blocks elsewhere.
+                (*worker_thread).wait_until(&job.latch);
+                job.into_result()
+            }
+            Err(_) => {
+                // Could not spawn a helper thread (resource
+                // exhaustion); fall back to blocking in place.
+                job.run_inline()
+            }
+        }
+    }
+}
+
+/// Initializes the dedicated thread pool used by `spawn_blocking()`.
+/// The interesting knob is `num_threads()`, which caps how many
+/// blocking tasks run concurrently; tasks beyond the cap queue up
+/// behind the running ones. This must be called before the first
+/// `spawn_blocking()`, which otherwise creates the pool with the
+/// default configuration; once the pool exists, this fails with
+/// `BlockingPoolAlreadyInitialized`.
+pub fn initialize_blocking_pool(config: Configuration) -> Result<(), ThreadPoolBuildError> {
+    registry::init_blocking_registry(config)
+}
+
+/// Hands `func` off to a dedicated pool of threads reserved for
+/// blocking operations and returns immediately with a handle to the
+/// result. This complements `blocking()`: where `blocking()` keeps
+/// the *caller's* position in the pool while spawning a temporary
+/// thread per call, `spawn_blocking()` reuses a fixed set of threads
+/// (see `initialize_blocking_pool()`) and does not require waiting in
+/// place -- the caller can fire off several blocking tasks and join
+/// them later, from this thread or another.
+///
+/// The blocking pool is separate from every compute pool, so blocking
+/// tasks can never starve CPU-bound Rayon work of its workers, and
+/// vice versa.
+///
+/// # Panics
+///
+/// If `func` panics, the panic is captured and rethrown by
+/// `BlockingHandle::join()`. If the handle is dropped without being
+/// joined, the panic is discarded along with the result.
+pub fn spawn_blocking<F, R>(func: F) -> BlockingHandle<R>
+    where F: FnOnce() -> R + Send + 'static,
+          R: Send + 'static
+{
+    let task = Arc::new(BlockingTask {
+        latch: LockLatch::new(),
+        result: Mutex::new(None),
+        waiter: Mutex::new(None),
+    });
+    unsafe {
+        let job = Box::new(HeapJob::new({
+            let task = task.clone();
+            move || {
+                *task.result.lock().unwrap() = Some(unwind::halt_unwinding(func));
+                task.latch.set();
+                // A handle holder waiting cooperatively in a
+                // *compute* pool may have fallen asleep there, and
+                // wakeups on the blocking pool do not reach it. The
+                // waiter registered its registry before waiting (see
+                // `join()`); tickle it now that the latch is set.
+                if let Some(waiter) = task.waiter.lock().unwrap().take() {
+                    waiter.tickle();
+                }
+            }
+        }));
+        // The code between allocating the job and enqueuing it must
+        // not panic, or the job would leak. The blocking registry is
+        // leaked and never terminated, so unlike `spawn_async()` no
+        // terminate-count bookkeeping is needed here.
+        let abort_guard = unwind::AbortIfPanic;
+        let job_ref = HeapJob::as_job_ref(job);
+        registry::blocking_registry().inject(&[job_ref]);
+        mem::forget(abort_guard);
+    }
+    BlockingHandle { task: task }
+}
+
+/// State shared between a blocking task and its handle.
+struct BlockingTask<R> {
+    latch: LockLatch,
+    result: Mutex<Option<thread::Result<R>>>,
+
+    /// Registry of a worker thread currently waiting in `join()`,
+    /// registered before it starts to wait. The mutex orders the
+    /// registration against the task's read of it, so either the task
+    /// sees the registry here and tickles it, or the waiter's first
+    /// latch probe already sees the latch set and it never sleeps.
+    waiter: Mutex<Option<Arc<Registry>>>,
+}
+
+/// Handle to a task started with `spawn_blocking()`.
+pub struct BlockingHandle<R> {
+    task: Arc<BlockingTask<R>>,
+}
+
+impl<R> BlockingHandle<R> {
+    /// Returns true if the task has finished, whether normally or by
+    /// panic.
+    pub fn is_complete(&self) -> bool {
+        self.task.latch.probe()
+    }
+
+    /// Blocks until the task has finished and returns its result.
+    /// When called on a worker thread of a compute pool, the worker
+    /// keeps executing (and stealing) that pool's jobs while it
+    /// waits, so joining from inside a pool cannot deadlock it.
+    pub fn join(self) -> R {
+        unsafe {
+            let worker_thread = WorkerThread::current();
+            if !worker_thread.is_null() {
+                *self.task.waiter.lock().unwrap() = Some((*worker_thread).registry().clone());
+                (*worker_thread).wait_until(&self.task.latch);
+            } else {
+                self.task.latch.wait();
+            }
+        }
+        let result = self.task
+            .result
+            .lock()
+            .unwrap()
+            .take()
+            .expect("latch set but no result recorded");
+        match result {
+            Ok(value) => value,
+            Err(err) => unwind::resume_unwinding(err),
+        }
+    }
+}
+
+/// A blocking operation in flight, allocated on the calling worker's
+/// stack. Compare `StackJob`; this is not a `Job` because it is never
+/// published to the pool -- only the one helper thread runs it.
+struct BlockingJob<F, R>
+    where F: FnOnce() -> R + Send,
+          R: Send
+{
+    func: Option<F>,
+    result: Option<thread::Result<R>>,
+    latch: SpinLatch,
+    registry: Arc<Registry>,
+}
+
+impl<F, R> BlockingJob<F, R>
+    where F: FnOnce() -> R + Send,
+          R: Send
+{
+    fn new(func: F, registry: Arc<Registry>) -> BlockingJob<F, R> {
+        BlockingJob {
+            func: Some(func),
+            result: None,
+            latch: SpinLatch::new(),
+            registry: registry,
+        }
+    }
+
+    /// Entry point of the helper thread. The caller guarantees that
+    /// `addr` points to a `BlockingJob` that remains valid until our
+    /// `latch` is set.
+    unsafe fn run(addr: usize) {
+        let this = addr as *mut BlockingJob<F, R>;
+        let func = (*this).func.take().unwrap();
+        let registry = (*this).registry.clone();
+        (*this).result = Some(unwind::halt_unwinding(func));
+        (*this).latch.set();
+        // The waiting worker may have given up spinning on the latch
+        // and fallen asleep; wake it. This must go through a handle
+        // cloned *before* the latch was set: setting the latch frees
+        // the caller's frame, and the job with it.
+        registry.tickle();
+    }
+
+    fn run_inline(mut self) -> R {
+        let func = self.func.take().unwrap();
+        func()
+    }
+
+    fn into_result(self) -> R {
+        // The latch was set after `result` was written, so it must be
+        // present by now.
+        match self.result {
+            Some(Ok(r)) => r,
+            Some(Err(err)) => unwind::resume_unwinding(err),
+            None => unreachable!("latch set but no result recorded"),
+        }
+    }
+}
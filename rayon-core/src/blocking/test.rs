@@ -0,0 +1,90 @@
+use Configuration;
+use ThreadPool;
+use ThreadPoolBuildErrorKind;
+use scope;
+use std::sync::mpsc::channel;
+use std::thread;
+use super::{blocking, initialize_blocking_pool, spawn_blocking};
+
+#[test]
+fn blocking_outside_worker_runs_inline() {
+    assert_eq!(blocking(|| 22), 22);
+}
+
+#[test]
+fn blocking_worker_keeps_scheduling() {
+    // One worker: if `blocking()` held the worker hostage, the
+    // spawned job below could never run and `rx.recv()` would
+    // deadlock. The worker must keep executing pool jobs while the
+    // helper thread is blocked on the channel.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let result = pool.install(|| {
+        let (tx, rx) = channel();
+        scope(|s| {
+            s.spawn(move |_| tx.send(22).unwrap());
+            blocking(move || rx.recv().unwrap())
+        })
+    });
+    assert_eq!(result, 22);
+}
+
+#[test]
+#[should_panic(expected = "Hello, world!")]
+fn blocking_panic_propagates() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    pool.install(|| blocking(|| panic!("Hello, world!")));
+}
+
+#[test]
+fn spawn_blocking_returns_result() {
+    let handle = spawn_blocking(|| 22);
+    assert_eq!(handle.join(), 22);
+}
+
+#[test]
+fn spawn_blocking_join_inside_pool_keeps_scheduling() {
+    // One worker: if `join()` held the worker hostage, the spawned
+    // job below could never run, the blocking task would never
+    // receive, and the join would deadlock. The worker must keep
+    // executing pool jobs while it waits on the handle.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let result = pool.install(|| {
+        let (tx, rx) = channel();
+        let handle = spawn_blocking(move || rx.recv().unwrap());
+        scope(|s| {
+            s.spawn(move |_| tx.send(22).unwrap());
+            handle.join()
+        })
+    });
+    assert_eq!(result, 22);
+}
+
+#[test]
+fn spawn_blocking_is_complete() {
+    let (tx, rx) = channel();
+    let handle = spawn_blocking(move || rx.recv().unwrap());
+    assert!(!handle.is_complete());
+    tx.send(22).unwrap();
+    while !handle.is_complete() {
+        thread::yield_now();
+    }
+    assert_eq!(handle.join(), 22);
+}
+
+#[test]
+#[should_panic(expected = "Hello, world!")]
+fn spawn_blocking_panic_rethrown_at_join() {
+    spawn_blocking(|| panic!("Hello, world!")).join();
+}
+
+#[test]
+fn initialize_blocking_pool_after_use_fails() {
+    // Make sure the blocking pool exists (other tests may already
+    // have created it; this makes the outcome order-independent).
+    spawn_blocking(|| ()).join();
+    let err = initialize_blocking_pool(Configuration::new()).unwrap_err();
+    match *err.kind() {
+        ThreadPoolBuildErrorKind::BlockingPoolAlreadyInitialized => {}
+        ref kind => panic!("unexpected error kind: {:?}", kind),
+    }
+}
@@ -0,0 +1,64 @@
+use registry::{self, Registry};
+
+#[cfg(test)]
+mod test;
+
+/// Executes `op` exactly once on every worker thread of the current
+/// thread-pool, passing each invocation the index of the worker it
+/// runs on, and returns the results indexed by worker. This is useful
+/// for initializing per-thread resources such as thread-local
+/// counters, RNGs, or connection handles.
+///
+/// Every invocation is guaranteed to run on a distinct worker thread.
+/// If this is executing within a Rayon thread-pool, the broadcast
+/// targets that pool; otherwise, it targets the global thread-pool.
+///
+/// # Blocking
+///
+/// `broadcast()` returns once every worker has executed `op`. Workers
+/// that are busy with long-running jobs only get to their broadcast
+/// job once they return to the scheduler, so this call may block for
+/// a correspondingly long time.
+///
+/// # Panic handling
+///
+/// If any invocation of `op` should panic, that panic will be
+/// propagated once all invocations have completed. If multiple
+/// invocations panic, the one for the lowest worker index is
+/// propagated.
+pub fn broadcast<OP, R>(op: OP) -> Vec<R>
+    where OP: Fn(usize) -> R + Sync,
+          R: Send
+{
+    let registry = Registry::current();
+    // A lazy pool must be at full strength first: every invocation is
+    // reserved for one specific worker, including ones that may not
+    // have been started yet.
+    registry::spawn_all_unspawned(&registry);
+    registry.broadcast(op)
+}
+
+/// Like `broadcast()`, but folds the per-worker results into a single
+/// value instead of returning them as a vector: `init` runs exactly
+/// once on every worker thread (receiving the worker's index), and
+/// once all workers have finished, the results are combined with
+/// `combine` in worker-index order on the calling thread. This is the
+/// natural shape for "sum the per-thread counters" style reductions
+/// over worker-local state.
+///
+/// `combine` is applied left-to-right starting from worker 0's
+/// result, so strictly it only needs to tolerate that one
+/// association; treating it as associative anyway keeps the door open
+/// for a tree-shaped reduction later.
+///
+/// The blocking and panic-handling behavior is that of `broadcast()`.
+pub fn broadcast_reduce<T, F, C>(init: F, combine: C) -> T
+    where F: Fn(usize) -> T + Sync,
+          C: Fn(T, T) -> T,
+          T: Send
+{
+    let mut results = broadcast(init).into_iter();
+    let first = results.next()
+        .expect("thread-pool has at least one worker");
+    results.fold(first, combine)
+}
@@ -0,0 +1,56 @@
+use {Configuration, ThreadPool};
+use std::sync::Mutex;
+use std::collections::HashSet;
+use std::thread;
+use super::{broadcast, broadcast_reduce};
+
+#[test]
+fn broadcast_global() {
+    let v = broadcast(|i| i);
+    assert_eq!(v, (0..v.len()).collect::<Vec<_>>());
+}
+
+#[test]
+fn broadcast_pool() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(7)).unwrap();
+    let v = pool.install(|| broadcast(|i| i + 1));
+    assert_eq!(v, (1..8).collect::<Vec<_>>());
+}
+
+#[test]
+fn broadcast_distinct_threads() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(7)).unwrap();
+    let thread_ids = Mutex::new(HashSet::new());
+    pool.install(|| {
+        broadcast(|_| {
+            thread_ids.lock().unwrap().insert(thread::current().id());
+        })
+    });
+    assert_eq!(thread_ids.into_inner().unwrap().len(), 7);
+}
+
+#[test]
+fn broadcast_reduce_sums_per_worker() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(7)).unwrap();
+    let sum = pool.install(|| broadcast_reduce(|i| i + 1, |a, b| a + b));
+    assert_eq!(sum, (1..8).sum());
+}
+
+#[test]
+fn broadcast_reduce_combines_in_index_order() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+    let order = pool.install(|| {
+        broadcast_reduce(|i| vec![i], |mut a, mut b| {
+            a.append(&mut b);
+            a
+        })
+    });
+    assert_eq!(order, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn broadcast_reduce_single_thread_never_combines() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let value = pool.install(|| broadcast_reduce(|i| i + 41, |_, _| panic!("combine called")));
+    assert_eq!(value, 41);
+}
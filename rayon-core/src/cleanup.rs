@@ -0,0 +1,55 @@
+//! A dedicated thread for running the drop glue of aborted jobs off
+//! the worker threads (see
+//! `Configuration::offload_aborted_drops()`). Destructors can be
+//! arbitrarily slow, or even call back into the pool; running them on
+//! a worker would stall it, and running them on a terminating thread
+//! could deadlock. The cleanup thread is process-wide, started
+//! lazily on first use, and -- like the global registry -- is never
+//! torn down.
+//!
+//! Callers are responsible for ordering: whatever memory the handed-
+//! off closure touches must stay valid until the closure has *run*,
+//! not merely until it was submitted. The scope abort path ensures
+//! this by signaling scope completion from inside the closure itself,
+//! after the drop.
+
+use std::mem;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Once, ONCE_INIT};
+use std::thread;
+use unwind;
+
+type CleanupJob = Box<FnOnce() + Send>;
+
+static mut THE_SENDER: Option<&'static Mutex<Sender<CleanupJob>>> = None;
+static THE_SENDER_SET: Once = ONCE_INIT;
+
+/// Starts the cleanup thread (if that has not already happened) and
+/// returns the sending half of its queue.
+fn sender() -> &'static Mutex<Sender<CleanupJob>> {
+    THE_SENDER_SET.call_once(|| unsafe {
+        let (tx, rx) = channel::<CleanupJob>();
+        thread::Builder::new()
+            .name("rayon cleanup".to_string())
+            .spawn(move || {
+                for job in rx {
+                    // A panicking destructor must not take the
+                    // cleanup thread down with it -- every later
+                    // hand-off would then be lost. Swallow the
+                    // panic; the job it came from was aborted
+                    // anyway.
+                    let _ = unwind::halt_unwinding(job);
+                }
+            })
+            .unwrap();
+        THE_SENDER = Some(mem::transmute(Box::new(Mutex::new(tx))));
+    });
+    unsafe { THE_SENDER.expect("The cleanup thread has not been started.") }
+}
+
+/// Runs `job` on the shared cleanup thread, in submission order.
+/// Returns as soon as the job is queued.
+pub fn run_off_thread(job: CleanupJob) {
+    sender().lock().unwrap().send(job).unwrap();
+}
@@ -0,0 +1,60 @@
+use scope::scope;
+
+#[cfg(test)]
+mod test;
+
+/// Applies `f` to every item of `iter` in parallel, by draining the
+/// iterator into `Vec`s of at most `chunk` items and spawning each
+/// such chunk as a scope task. The iterator itself is only ever
+/// advanced by one thread, so this is a pragmatic bridge for
+/// non-indexable sources -- channels, file readers, generators --
+/// that cannot be recursively split the way a slice can (compare
+/// `par_apply()`). Processing of earlier chunks overlaps the draining
+/// of later ones: the chunks are spawned as they fill up, and idle
+/// workers steal and run them while the draining thread keeps going.
+///
+/// `chunk` bounds the task overhead: a larger value means fewer,
+/// coarser tasks, but also less parallelism and more buffering. The
+/// final chunk may hold fewer than `chunk` items. Items are dropped
+/// as their chunk is processed; the function does not return until
+/// every spawned chunk has completed.
+///
+/// No order should be assumed: chunks run concurrently, so `f` must
+/// be prepared to see items out of sequence.
+///
+/// # Panics
+///
+/// Panics if `chunk` is zero. If `f` panics -- or the iterator
+/// panics while being drained -- that panic is propagated to the
+/// caller; per the `scope()` contract, chunks that were already
+/// spawned still run to completion first.
+pub fn par_for_each_chunked<I, F>(iter: I, chunk: usize, f: F)
+    where I: Iterator + Send,
+          I::Item: Send,
+          F: Fn(I::Item) + Sync
+{
+    assert!(chunk > 0, "chunk size must be positive");
+    scope(|s| {
+        let f = &f;
+        let mut iter = iter;
+        loop {
+            let mut buffer = Vec::with_capacity(chunk);
+            while buffer.len() < chunk {
+                match iter.next() {
+                    Some(item) => buffer.push(item),
+                    None => break,
+                }
+            }
+            if buffer.is_empty() {
+                break;
+            }
+            let drained = buffer.len() < chunk;
+            s.spawn(move |_| for item in buffer {
+                        f(item);
+                    });
+            if drained {
+                break;
+            }
+        }
+    });
+}
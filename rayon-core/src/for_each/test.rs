@@ -0,0 +1,76 @@
+//! Tests for the chunked iterator driver.
+
+use for_each::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use unwind;
+
+#[test]
+fn visits_every_item() {
+    let sum = AtomicUsize::new(0);
+    let count = AtomicUsize::new(0);
+    par_for_each_chunked(0..1000, 64, |i| {
+        sum.fetch_add(i, Ordering::SeqCst);
+        count.fetch_add(1, Ordering::SeqCst);
+    });
+    assert_eq!(count.load(Ordering::SeqCst), 1000);
+    assert_eq!(sum.load(Ordering::SeqCst), 1000 * 999 / 2);
+}
+
+#[test]
+fn final_partial_chunk_is_processed() {
+    // 10 items, chunk 7: one full chunk and one partial one.
+    let count = AtomicUsize::new(0);
+    par_for_each_chunked(0..10, 7, |_| {
+        count.fetch_add(1, Ordering::SeqCst);
+    });
+    assert_eq!(count.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn short_iterator_fits_one_partial_chunk() {
+    let count = AtomicUsize::new(0);
+    par_for_each_chunked(0..3, 64, |_| {
+        count.fetch_add(1, Ordering::SeqCst);
+    });
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn empty_iterator_is_a_no_op() {
+    let count = AtomicUsize::new(0);
+    par_for_each_chunked(0..0, 64, |_| {
+        count.fetch_add(1, Ordering::SeqCst);
+    });
+    assert_eq!(count.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+#[should_panic(expected = "chunk size must be positive")]
+fn zero_chunk_panics() {
+    par_for_each_chunked(0..10, 0, |_| ());
+}
+
+#[test]
+fn panic_in_f_propagates() {
+    let result = unwind::halt_unwinding(|| {
+        par_for_each_chunked(0..100, 8, |i| if i == 50 {
+            panic!("Hello, world!");
+        })
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn panic_while_draining_propagates() {
+    // The iterator itself panics partway through; items already
+    // spawned must still be processed before the panic propagates.
+    let count = AtomicUsize::new(0);
+    let result = unwind::halt_unwinding(|| {
+        let iter = (0..100).map(|i| if i == 20 { panic!("Hello, world!") } else { i });
+        par_for_each_chunked(iter, 5, |_| {
+            count.fetch_add(1, Ordering::SeqCst);
+        })
+    });
+    assert!(result.is_err());
+    assert_eq!(count.load(Ordering::SeqCst), 20);
+}
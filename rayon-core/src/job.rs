@@ -2,6 +2,8 @@ use latch::Latch;
 use std::any::Any;
 use std::cell::UnsafeCell;
 use std::mem;
+#[cfg(feature = "unstable")]
+use std::ptr;
 use unwind;
 
 pub enum JobResult<T> {
@@ -54,11 +56,62 @@ impl JobRef {
     pub unsafe fn execute(&self) {
         (self.execute_fn)(self.pointer)
     }
+
+    /// Creates a `JobRef` whose closure is stored *inside* the
+    /// `pointer` field itself rather than behind it -- a small-buffer
+    /// optimization for spawn-heavy code: a closure that fits (see
+    /// `fits_inline()`) costs no heap allocation at all. The bytes
+    /// travel through the deques by value, like any other `JobRef`.
+    ///
+    /// Unsafe for the usual `JobRef` reason -- it must be executed
+    /// exactly once -- with the leak on the dropped-without-executing
+    /// path being whatever the closure captured.
+    #[cfg(feature = "unstable")]
+    pub unsafe fn new_inline<BODY>(body: BODY) -> JobRef
+        where BODY: FnOnce() + Send
+    {
+        debug_assert!(fits_inline::<BODY>());
+        let mut pointer: *const () = ptr::null();
+        ptr::write(&mut pointer as *mut *const () as *mut BODY, body);
+        JobRef {
+            pointer: pointer,
+            execute_fn: execute_inline::<BODY>,
+        }
+    }
+}
+
+/// Returns true if a closure of type `T` is small enough to ride
+/// inside a `JobRef`'s pointer field (see `JobRef::new_inline()`).
+/// Both operands are compile-time constants, so a branch on this
+/// folds away entirely.
+#[cfg(feature = "unstable")]
+pub fn fits_inline<T>() -> bool {
+    mem::size_of::<T>() <= mem::size_of::<*const ()>() &&
+    mem::align_of::<T>() <= mem::align_of::<*const ()>()
+}
+
+/// The execute fn of an inline `JobRef`: reinterprets the pointer
+/// *value* it was handed as the closure's bytes and runs it.
+#[cfg(feature = "unstable")]
+unsafe fn execute_inline<BODY>(this: *const ())
+    where BODY: FnOnce()
+{
+    let body: BODY = ptr::read(&this as *const *const () as *const BODY);
+    body();
 }
 
 /// A job that will be owned by a stack slot. This means that when it
 /// executes it need not free any heap data, the cleanup occurs when
 /// the stack frame is later popped.
+///
+/// The job carries a typed result slot, which is what lets APIs like
+/// `join()` and `ThreadPool::install()` return an `R` rather than
+/// merely signal completion. The slot is protected by the latch
+/// alone: `execute()` writes the result (or the captured panic)
+/// *before* setting the latch, and the owner reads it only *after*
+/// the latch is set. Since a set latch synchronizes-with the probe
+/// that observes it (see `latch.rs`), the owner's read happens-after
+/// the write and no further synchronization on the slot is needed.
 pub struct StackJob<L: Latch, F, R> {
     pub latch: L,
     func: UnsafeCell<Option<F>>,
@@ -84,6 +137,9 @@ impl<L: Latch, F, R> StackJob<L, F, R>
         self.func.into_inner().unwrap()()
     }
 
+    /// Extracts the result written by `execute()`. Only valid once
+    /// the latch has been observed as set; see the ordering note on
+    /// the struct.
     pub unsafe fn into_result(self) -> R {
         self.result.into_inner().into_return_value()
     }
@@ -96,6 +152,10 @@ impl<L: Latch, F, R> Job for StackJob<L, F, R>
         let this = &*this;
         let abort = unwind::AbortIfPanic;
         let func = (*this.func.get()).take().unwrap();
+        // The result must be fully written before the latch is set:
+        // the owner may be blocked in `latch.wait()` (or probing from
+        // another worker) and will read the slot as soon as it
+        // observes the latch. See the ordering note on the struct.
         (*this.result.get()) = match unwind::halt_unwinding(|| func()) {
             Ok(x) => JobResult::Ok(x),
             Err(x) => JobResult::Panic(x),
@@ -4,6 +4,9 @@ use log::Event::*;
 use job::StackJob;
 use registry::{self, WorkerThread};
 use std::any::Any;
+use std::thread;
+#[cfg(feature = "unstable")]
+use std::time::{Duration, Instant};
 use unwind;
 
 #[cfg(test)]
@@ -46,6 +49,30 @@ pub fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
     registry::in_worker(|worker_thread| unsafe {
         log!(Join { worker: worker_thread.index() });
 
+        // With a single worker there is nobody to steal task B, so
+        // the deque push, latch, and pop-back below are pure
+        // overhead: run both closures serially right here.
+        if worker_thread.registry().num_threads() == 1 {
+            return join_serial(oper_a, oper_b);
+        }
+
+        #[cfg(feature = "unstable")]
+        {
+            // Past the configured join-depth cap, stop splitting: no
+            // deque push means nothing new to steal, so the stack
+            // stops accumulating scheduler frames (see
+            // `Configuration::max_recursion_depth()`). Joins nested
+            // inside the serial closures find the depth unchanged and
+            // stay serial too.
+            if let Some(max_depth) = worker_thread.registry().max_recursion_depth() {
+                if worker_thread.join_depth() >= max_depth {
+                    return join_serial(oper_a, oper_b);
+                }
+            }
+        }
+        #[cfg(feature = "unstable")]
+        let _depth = JoinDepthGuard::enter(worker_thread);
+
         // Create virtual wrapper for task b; this all has to be
         // done here so that the stack frame can keep it all live
         // long enough.
@@ -75,7 +102,17 @@ pub fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
                     return (result_a, result_b);
                 } else {
                     log!(PoppedJob { worker: worker_thread.index() });
-                    worker_thread.execute(job);
+                    // If this job should panic, we cannot simply
+                    // unwind: job B may have been stolen and be
+                    // executing concurrently, with references into
+                    // this stack frame. Recover the same way as a
+                    // panic in task A, so that the worker is back in
+                    // a consistent state once the panic is handled at
+                    // a higher frame.
+                    match unwind::halt_unwinding(|| worker_thread.execute(job)) {
+                        Ok(()) => {}
+                        Err(err) => join_recover_from_panic(worker_thread, &job_b.latch, err),
+                    }
                 }
             } else {
                 // Local deque is empty. Time to steal from other
@@ -91,6 +128,271 @@ pub fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
     })
 }
 
+/// Runs both sides of a `join()` serially, preserving its contract:
+/// both closures always execute, and if both panic, the first one's
+/// panic is the one propagated.
+fn join_serial<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    where A: FnOnce() -> RA,
+          B: FnOnce() -> RB
+{
+    let result_a = unwind::halt_unwinding(oper_a);
+    let result_b = unwind::halt_unwinding(oper_b);
+    match (result_a, result_b) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(err), _) | (_, Err(err)) => unwind::resume_unwinding(err),
+    }
+}
+
+/// Bumps the worker's live-join count for the duration of `join()`'s
+/// parallel path (see `Configuration::max_recursion_depth()`). An
+/// RAII guard, so the count is restored on every exit, unwinding
+/// included.
+#[cfg(feature = "unstable")]
+struct JoinDepthGuard<'w> {
+    worker: &'w WorkerThread,
+}
+
+#[cfg(feature = "unstable")]
+impl<'w> JoinDepthGuard<'w> {
+    fn enter(worker: &'w WorkerThread) -> JoinDepthGuard<'w> {
+        worker.set_join_depth(worker.join_depth() + 1);
+        JoinDepthGuard { worker: worker }
+    }
+}
+
+#[cfg(feature = "unstable")]
+impl<'w> Drop for JoinDepthGuard<'w> {
+    fn drop(&mut self) {
+        self.worker.set_join_depth(self.worker.join_depth() - 1);
+    }
+}
+
+/// Like `join()`, except that a panic in either closure is captured
+/// and returned as an `Err` instead of being propagated by
+/// unwinding. Both closures are always executed, and both results are
+/// always returned, so a panic in one closure never loses the result
+/// of the other.
+///
+/// Note that a panicking closure still unwinds normally up to the
+/// point where the panic is captured: any resources it owns are
+/// dropped per the usual drop semantics.
+pub fn try_join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (thread::Result<RA>, thread::Result<RB>)
+    where A: FnOnce() -> RA + Send,
+          B: FnOnce() -> RB + Send,
+          RA: Send,
+          RB: Send
+{
+    // The closures we execute never themselves panic, since any panic
+    // in `oper_a`/`oper_b` is halted and becomes part of the result.
+    join(move || unwind::halt_unwinding(oper_a),
+         move || unwind::halt_unwinding(oper_b))
+}
+
+/// Context passed to the closures of `join_context()`. It reports
+/// whether the closure was *migrated*: stolen and run on a different
+/// worker than the one that called `join_context()`. The first
+/// closure always runs on the calling worker, so its context never
+/// reports migration.
+#[cfg(feature = "unstable")]
+pub struct FnContext {
+    migrated: bool,
+}
+
+#[cfg(feature = "unstable")]
+impl FnContext {
+    #[inline]
+    fn new(migrated: bool) -> FnContext {
+        FnContext { migrated: migrated }
+    }
+
+    /// Returns true if this closure was stolen and ran on a worker
+    /// other than the one that called `join_context()`. A migrated
+    /// second closure proves another worker was idle enough to take
+    /// it; a non-migrated one is weak evidence that further splitting
+    /// below it will not buy any parallelism. Adaptive helpers like
+    /// `reduce_range()` use exactly this signal to decide when to
+    /// stop splitting.
+    #[inline]
+    pub fn migrated(&self) -> bool {
+        self.migrated
+    }
+}
+
+/// Crate-internal constructor for `FnContext`, so that other entry
+/// points (`Scope::spawn_context()`) can build contexts without
+/// `FnContext::new()` becoming public API. Not re-exported.
+#[cfg(feature = "unstable")]
+pub fn fn_context(migrated: bool) -> FnContext {
+    FnContext::new(migrated)
+}
+
+/// Like `join()`, except that each closure receives an `FnContext`
+/// reporting whether it was stolen, which callers can use to adapt
+/// their splitting granularity (see `FnContext::migrated()`). The
+/// scheduling is identical to `join()`: the context only observes
+/// where the closures ended up running.
+#[cfg(feature = "unstable")]
+pub fn join_context<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    where A: FnOnce(FnContext) -> RA + Send,
+          B: FnOnce(FnContext) -> RB + Send,
+          RA: Send,
+          RB: Send
+{
+    registry::in_worker(|worker_thread| {
+        // Task A always runs on the calling worker; task B migrated
+        // iff, at the moment it starts, it finds itself on some other
+        // worker. (As in `join_timed()`, the worker's address is the
+        // identity we compare.)
+        let caller = worker_thread as *const WorkerThread as usize;
+        join(move || oper_a(FnContext::new(false)),
+             move || {
+                 let migrated = WorkerThread::current() as usize != caller;
+                 oper_b(FnContext::new(migrated))
+             })
+    })
+}
+
+/// Like `join()`, but additionally reports, for profiling purposes,
+/// how long each closure took and whether the second closure was
+/// stolen (i.e. actually ran in parallel on another worker). Returns
+/// `((result_a, duration_a), (result_b, duration_b, b_was_stolen))`.
+///
+/// This helps answer two common tuning questions: which half of a
+/// split dominates, and whether parallelism is kicking in at all --
+/// if `b_was_stolen` is almost always false, the splits are probably
+/// too fine-grained (or the pool is saturated higher up). The timing
+/// uses two `Instant` samples per closure and is only paid in this
+/// opt-in variant; `join()` itself is unaffected.
+#[cfg(feature = "unstable")]
+pub fn join_timed<A, B, RA, RB>(oper_a: A,
+                                oper_b: B)
+                                -> ((RA, Duration), (RB, Duration, bool))
+    where A: FnOnce() -> RA + Send,
+          B: FnOnce() -> RB + Send,
+          RA: Send,
+          RB: Send
+{
+    let (a, b) = join(move || {
+                          let start = Instant::now();
+                          let result = oper_a();
+                          (result, start.elapsed(), WorkerThread::current() as usize)
+                      },
+                      move || {
+                          let start = Instant::now();
+                          let result = oper_b();
+                          (result, start.elapsed(), WorkerThread::current() as usize)
+                      });
+
+    // Task A always runs on the worker that called `join`, so task B
+    // was stolen iff it ran on some other worker.
+    let (result_a, duration_a, worker_a) = a;
+    let (result_b, duration_b, worker_b) = b;
+    ((result_a, duration_a), (result_b, duration_b, worker_a != worker_b))
+}
+
+/// Runs `join(oper_a, oper_b)` and combines the pair of results with
+/// `combine`. This is thin sugar, but it is exactly the shape of the
+/// recursive step in a map-reduce tree (see e.g. `reduce_range()`):
+/// `join_reduce(|| left(), || right(), merge)` reads as the intent
+/// without naming the intermediate tuple.
+///
+/// `combine` always runs on the calling thread, after both closures
+/// have completed; only `oper_b` is ever stolen. Panics propagate as
+/// with `join()`, in which case `combine` never runs.
+#[cfg(feature = "unstable")]
+pub fn join_reduce<A, B, RA, RB, C, F>(oper_a: A, oper_b: B, combine: F) -> C
+    where A: FnOnce() -> RA + Send,
+          B: FnOnce() -> RB + Send,
+          RA: Send,
+          RB: Send,
+          F: FnOnce(RA, RB) -> C
+{
+    let (result_a, result_b) = join(oper_a, oper_b);
+    combine(result_a, result_b)
+}
+
+/// A sequential stand-in for `join()`: runs `oper_a` to completion
+/// and only then runs `oper_b`, with the same signature and return
+/// type as `join()`. Nothing is ever published to the deque, so the
+/// two closures cannot interleave and `oper_b` is guaranteed to
+/// observe all of `oper_a`'s side effects.
+///
+/// `join()` deliberately makes no such guarantee -- the second
+/// closure may be stolen and run concurrently with the first. This
+/// variant lets code that is generic over the "join shape" flip a
+/// particular split to sequential where ordering matters, without
+/// restructuring it.
+///
+/// ### Panics
+///
+/// Sequential semantics apply here too: if `oper_a` panics, that
+/// panic propagates immediately and `oper_b` is never executed
+/// (whereas `join()` always runs both closures).
+#[cfg(feature = "unstable")]
+pub fn join_seq<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    where A: FnOnce() -> RA + Send,
+          B: FnOnce() -> RB + Send,
+          RA: Send,
+          RB: Send
+{
+    let result_a = oper_a();
+    let result_b = oper_b();
+    (result_a, result_b)
+}
+
+/// Takes an array of `N` same-typed closures and *potentially* runs
+/// them in parallel, returning their results as an array. This is the
+/// homogeneous cousin of `join()`: splitting into `N` equal subtasks
+/// of the same return type with nested tuples is awkward, and an
+/// array is the natural shape for such a fan-out.
+///
+/// The closures are executed by recursively `join()`-splitting the
+/// array, so the usual `join()` properties carry over: the calling
+/// thread starts on the first half while the second half may be
+/// stolen, nothing is heap-allocated, and with `N <= 1` no
+/// parallelism machinery is touched at all.
+///
+/// ### Panics
+///
+/// As with `join()`, if any closure panics, that panic is propagated
+/// once all closures that already started have completed; results of
+/// the other closures are dropped. If several closures panic, the
+/// propagated one is the first in array order.
+#[cfg(feature = "unstable")]
+pub fn join_array<F, R, const N: usize>(fs: [F; N]) -> [R; N]
+    where F: FnOnce() -> R + Send,
+          R: Send
+{
+    let mut fs = fs.map(Some);
+    let mut results: [Option<R>; N] = [(); N].map(|_| None);
+    join_array_helper(&mut fs[..], &mut results[..]);
+    results.map(|result| result.unwrap())
+}
+
+/// Runs the closures of one subrange of a `join_array()` call,
+/// writing each result into the corresponding slot. The two slices
+/// always have equal length.
+#[cfg(feature = "unstable")]
+fn join_array_helper<F, R>(fs: &mut [Option<F>], results: &mut [Option<R>])
+    where F: FnOnce() -> R + Send,
+          R: Send
+{
+    match fs.len() {
+        0 => {}
+        1 => {
+            let f = fs[0].take().unwrap();
+            results[0] = Some(f());
+        }
+        n => {
+            let mid = n / 2;
+            let (fs_a, fs_b) = fs.split_at_mut(mid);
+            let (results_a, results_b) = results.split_at_mut(mid);
+            join(|| join_array_helper(fs_a, results_a),
+                 || join_array_helper(fs_b, results_b));
+        }
+    }
+}
+
 /// If job A panics, we still cannot return until we are sure that job
 /// B is complete. This is because it may contain references into the
 /// enclosing stack frame(s).
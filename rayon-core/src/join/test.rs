@@ -77,3 +77,235 @@ fn panic_b_still_executes() {
         Err(_) => assert!(x, "closure b failed to execute"),
     }
 }
+
+#[test]
+fn try_join_both_ok() {
+    let (a, b) = try_join(|| 22, || 44);
+    assert_eq!(a.unwrap(), 22);
+    assert_eq!(b.unwrap(), 44);
+}
+
+#[test]
+fn try_join_captures_panic_a() {
+    let (a, b) = try_join(|| -> () { panic!("Hello, world!") }, || 22);
+    let err = a.unwrap_err();
+    assert_eq!(*err.downcast_ref::<&str>().unwrap(), "Hello, world!");
+    assert_eq!(b.unwrap(), 22);
+}
+
+#[test]
+fn try_join_captures_both_panics() {
+    let (a, b) = try_join(|| -> () { panic!("Hello, world!") },
+                          || -> () { panic!("Goodbye, world!") });
+    assert!(a.is_err());
+    assert!(b.is_err());
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_timed_results_and_durations() {
+    use std::thread;
+    use std::time::Duration;
+
+    let ((a, da), (b, db, _stolen)) = join_timed(|| {
+                                                     thread::sleep(Duration::from_millis(10));
+                                                     22
+                                                 },
+                                                 || 44);
+    assert_eq!(a, 22);
+    assert_eq!(b, 44);
+    assert!(da >= Duration::from_millis(10));
+    assert!(db <= da);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_timed_not_stolen_on_one_thread() {
+    // with a single worker, task B can never be stolen
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    pool.install(|| {
+        let (_, (_, _, stolen)) = join_timed(|| 22, || 44);
+        assert!(!stolen, "task B cannot have been stolen on one worker");
+    });
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_seq_runs_a_before_b() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    pool.install(|| {
+        let order = AtomicUsize::new(0);
+        let (a, b) = join_seq(|| order.compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+                                      .is_ok(),
+                              || order.compare_exchange(1, 2, Ordering::SeqCst, Ordering::SeqCst)
+                                      .is_ok());
+        assert!(a && b, "closures of join_seq ran out of order");
+    });
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+#[should_panic(expected = "Hello, world!")]
+fn join_seq_panic_in_a_skips_b() {
+    join_seq(|| panic!("Hello, world!"),
+             || unreachable!("b must not run once a panicked"));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_array_computes_all_results() {
+    let squares = join_array([|| 0 * 0, || 1 * 1, || 2 * 2, || 3 * 3, || 4 * 4]);
+    assert_eq!(squares, [0, 1, 4, 9, 16]);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_array_trivial_sizes() {
+    let empty: [i32; 0] = join_array::<fn() -> i32, i32, 0>([]);
+    assert_eq!(empty, []);
+
+    let one = join_array([|| 22]);
+    assert_eq!(one, [22]);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_array_panic_propagates() {
+    let result = unwind::halt_unwinding(|| {
+        join_array([|| 1, || panic!("nope"), || 3])
+    });
+    assert!(result.is_err(), "panic in one closure should propagate");
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_context_results_and_a_never_migrated() {
+    let (a, b) = join_context(|context| {
+                                  assert!(!context.migrated(),
+                                          "task A runs on the calling worker");
+                                  22
+                              },
+                              |_| 44);
+    assert_eq!(a, 22);
+    assert_eq!(b, 44);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_context_not_migrated_on_one_thread() {
+    // with a single worker, task B can never be stolen
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    pool.install(|| {
+        let (_, migrated) = join_context(|_| 22, |context| context.migrated());
+        assert!(!migrated, "task B cannot have migrated on one worker");
+    });
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_reduce_combines_in_order() {
+    fn sum(range: ::std::ops::Range<usize>) -> usize {
+        if range.len() <= 16 {
+            return range.sum();
+        }
+        let mid = range.start + range.len() / 2;
+        join_reduce(|| sum(range.start..mid), || sum(mid..range.end), |a, b| a + b)
+    }
+
+    let n = 10 * 1024;
+    assert_eq!(sum(0..n), n * (n - 1) / 2);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_reduce_combine_runs_on_the_caller() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    pool.install(|| {
+        let caller = pool.current_thread_index();
+        let combined_on = join_reduce(|| 1, || 2, |a, b| {
+            assert_eq!(a + b, 3);
+            pool.current_thread_index()
+        });
+        assert_eq!(combined_on, caller);
+    });
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_reduce_panic_skips_combine() {
+    let result = unwind::halt_unwinding(|| {
+        join_reduce(|| 1, || -> i32 { panic!("nope") }, |_, _| {
+            unreachable!("combine must not run after a panic")
+        })
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn join_single_thread_same_results_as_parallel() {
+    // The single-worker fast path must be observationally identical
+    // to the work-stealing path.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let mut rng = XorShiftRng::from_seed([0, 1, 2, 3]);
+    let mut serial: Vec<u32> = rng.gen_iter().take(6 * 1024).collect();
+    let mut parallel = serial.clone();
+    pool.install(|| quick_sort(&mut serial));
+    quick_sort(&mut parallel);
+    assert_eq!(serial, parallel);
+}
+
+#[test]
+fn join_single_thread_still_executes_both_on_panic() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let ran_b = AtomicBool::new(false);
+    let result = pool.install(|| {
+        unwind::halt_unwinding(|| {
+            join(|| panic!("Hello, world!"),
+                 || ran_b.store(true, Ordering::SeqCst))
+        })
+    });
+    assert!(result.is_err(), "panic in task A was not propagated");
+    assert!(ran_b.load(Ordering::SeqCst), "task B must run even though A panicked");
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn max_recursion_depth_caps_splitting() {
+    use registry::WorkerThread;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Splits one element off per join: recursion depth is the range
+    // length, the pathological shape the cap exists for.
+    fn sum(range: ::std::ops::Range<usize>, max_seen: &AtomicUsize) -> usize {
+        let depth = unsafe { (*WorkerThread::current()).join_depth() };
+        max_seen.fetch_max(depth, Ordering::SeqCst);
+        if range.end - range.start <= 1 {
+            return range.start;
+        }
+        let rest = range.start + 1..range.end;
+        let (a, b) = join(|| range.start, || sum(rest, max_seen));
+        a + b
+    }
+
+    const CAP: usize = 8;
+    // Deep enough that uncapped splitting would stack hundreds of
+    // scheduler frames, while the algorithm's own serial recursion
+    // (which the cap deliberately leaves alone) still fits the
+    // enlarged worker stacks.
+    const LEN: usize = 4 * 1024;
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .stack_size(32 * 1024 * 1024)
+            .max_recursion_depth(CAP))
+        .unwrap();
+    let max_seen = AtomicUsize::new(0);
+    let total = pool.install(|| sum(0..LEN, &max_seen));
+    assert_eq!(total, LEN * (LEN - 1) / 2);
+    // Joins past the cap ran serially, so the scheduler never stacked
+    // more than CAP splitting frames on a worker.
+    assert_eq!(max_seen.load(Ordering::SeqCst), CAP);
+}
@@ -81,7 +81,10 @@ impl LockLatch {
         }
     }
 
-    /// Block until latch is set.
+    /// Block until latch is set. May be called any number of times,
+    /// from any number of threads: once the latch has been set, every
+    /// call (including repeat calls from the same thread) returns
+    /// immediately after one uncontended lock acquisition.
     pub fn wait(&self) {
         let mut guard = self.m.lock().unwrap();
         while !*guard {
@@ -108,6 +111,47 @@ impl Latch for LockLatch {
     }
 }
 
+/// A composite probe over a set of latches that is "set" once every
+/// member latch is set. This is only a probe, not a latch: setting
+/// still happens through the individual members. Used by
+/// `wait_all()` to feed a multi-latch condition into the single-latch
+/// waiting machinery.
+pub struct AllLatches<'l> {
+    latches: &'l [&'l LockLatch],
+}
+
+impl<'l> AllLatches<'l> {
+    #[inline]
+    pub fn new(latches: &'l [&'l LockLatch]) -> AllLatches<'l> {
+        AllLatches { latches: latches }
+    }
+}
+
+impl<'l> LatchProbe for AllLatches<'l> {
+    fn probe(&self) -> bool {
+        self.latches.iter().all(|l| l.probe())
+    }
+}
+
+/// The dual of `AllLatches`: "set" once at least one member latch is
+/// set. Used by `wait_any()`.
+pub struct AnyLatches<'l> {
+    latches: &'l [&'l LockLatch],
+}
+
+impl<'l> AnyLatches<'l> {
+    #[inline]
+    pub fn new(latches: &'l [&'l LockLatch]) -> AnyLatches<'l> {
+        AnyLatches { latches: latches }
+    }
+}
+
+impl<'l> LatchProbe for AnyLatches<'l> {
+    fn probe(&self) -> bool {
+        self.latches.iter().any(|l| l.probe())
+    }
+}
+
 /// Counting latches are used to implement scopes. They track a
 /// counter. Unlike other latches, calling `set()` does not
 /// necessarily make the latch be considered `set()`; instead, it just
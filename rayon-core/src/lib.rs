@@ -30,9 +30,11 @@
 #[allow(unused_imports)]
 use log::Event::*;
 use std::any::Any;
+use std::cmp;
 use std::env;
-use std::error::Error;
+use std::io;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::fmt;
 
 extern crate deque;
@@ -47,12 +49,29 @@ extern crate rand;
 #[macro_use]
 mod log;
 
+#[cfg(feature = "unstable")]
+mod apply;
+#[cfg(feature = "unstable")]
+mod blocking;
+#[cfg(feature = "unstable")]
+mod broadcast;
+mod cleanup;
+#[cfg(feature = "unstable")]
+mod for_each;
 mod latch;
 mod join;
 mod job;
 mod registry;
 #[cfg(feature = "unstable")]
+mod reduce;
+#[cfg(feature = "unstable")]
 mod future;
+#[cfg(feature = "unstable")]
+mod pool_barrier;
+#[cfg(feature = "unstable")]
+mod pool_local;
+#[cfg(feature = "unstable")]
+mod pool_semaphore;
 mod scope;
 mod sleep;
 #[cfg(feature = "unstable")]
@@ -63,14 +82,102 @@ mod unwind;
 mod util;
 
 pub use thread_pool::ThreadPool;
-pub use join::join;
+#[cfg(feature = "unstable")]
+pub use thread_pool::{current_pool, ThreadPoolRef};
+#[cfg(feature = "unstable")]
+pub use thread_pool::join_on;
+pub use registry::{ThreadPoolBuildError, ThreadPoolBuildErrorKind};
+#[cfg(feature = "unstable")]
+pub use registry::ThreadBuilder;
+#[cfg(feature = "unstable")]
+pub use log::Event;
+#[cfg(feature = "unstable")]
+pub use registry::current_min_split_len;
+#[cfg(feature = "unstable")]
+pub use registry::with_min_split_len;
+#[cfg(feature = "unstable")]
+pub use registry::resize_global_pool;
+#[cfg(feature = "unstable")]
+pub use registry::{wait_all, wait_any};
+#[cfg(feature = "unstable")]
+pub use registry::forbid_global_pool;
+#[cfg(feature = "unstable")]
+pub use registry::{SchedulerTrace, StealRecord};
+#[cfg(feature = "unstable")]
+pub use registry::JobTiming;
+#[cfg(feature = "unstable")]
+pub use registry::with_worker_rng;
+#[cfg(feature = "unstable")]
+pub use registry::inject_or_run_inline;
+#[cfg(feature = "unstable")]
+pub use registry::{oversubscription_factor, total_busy_workers};
+#[cfg(feature = "unstable")]
+pub use registry::drain_local_spawned;
+#[cfg(feature = "unstable")]
+pub use registry::{RegistrySnapshot, WorkerSnapshot};
+// Re-exported so that callers of `with_worker_rng()` can name the
+// generator's type and bring its `Rng` methods into scope without
+// depending on (a matching version of) the `rand` crate themselves.
+#[cfg(feature = "unstable")]
+pub use rand::{Rng, XorShiftRng};
+#[cfg(feature = "unstable")]
+pub use latch::{Latch, LatchProbe, LockLatch};
+#[cfg(feature = "unstable")]
+pub use apply::par_apply;
+#[cfg(feature = "unstable")]
+pub use blocking::{blocking, initialize_blocking_pool, spawn_blocking, BlockingHandle};
+#[cfg(feature = "unstable")]
+pub use broadcast::{broadcast, broadcast_reduce};
+#[cfg(feature = "unstable")]
+pub use for_each::par_for_each_chunked;
+pub use join::{join, try_join};
+#[cfg(feature = "unstable")]
+pub use join::join_array;
+#[cfg(feature = "unstable")]
+pub use join::{join_context, FnContext};
+#[cfg(feature = "unstable")]
+pub use join::join_reduce;
+#[cfg(feature = "unstable")]
+pub use join::join_seq;
+#[cfg(feature = "unstable")]
+pub use join::join_timed;
+#[cfg(feature = "unstable")]
+pub use reduce::reduce_range;
 pub use scope::{scope, Scope};
 #[cfg(feature = "unstable")]
+pub use scope::scope_collect;
+#[cfg(feature = "unstable")]
+pub use scope::scope_abort_on_panic;
+#[cfg(feature = "unstable")]
+pub use scope::scope_with_deadline;
+#[cfg(feature = "unstable")]
+pub use scope::{local_scope, LocalScope};
+#[cfg(feature = "unstable")]
 pub use spawn_async::spawn_async;
 #[cfg(feature = "unstable")]
 pub use spawn_async::spawn_future_async;
 #[cfg(feature = "unstable")]
+pub use spawn_async::spawn_future;
+#[cfg(feature = "unstable")]
+pub use spawn_async::spawn_priority;
+#[cfg(feature = "unstable")]
+pub use spawn_async::spawn_prioritized;
+#[cfg(feature = "unstable")]
+pub use spawn_async::{spawn_boxed, FnBox};
+#[cfg(feature = "unstable")]
+pub use spawn_async::spawn_sticky;
+#[cfg(feature = "unstable")]
+pub use spawn_async::spawn_on;
+#[cfg(feature = "unstable")]
+pub use spawn_async::{spawn_tracked, TaskHandle};
+#[cfg(feature = "unstable")]
 pub use future::RayonFuture;
+#[cfg(feature = "unstable")]
+pub use pool_barrier::{BarrierWaitResult, PoolBarrier};
+#[cfg(feature = "unstable")]
+pub use pool_local::PoolLocal;
+#[cfg(feature = "unstable")]
+pub use pool_semaphore::{PoolSemaphore, SemaphorePermit};
 
 /// Returns the number of threads in the current registry. If this
 /// code is executing within the Rayon thread-pool, then this will be
@@ -94,7 +201,12 @@ pub fn current_num_threads() -> usize {
 }
 
 /// Contains the rayon thread pool configuration.
-#[derive(Default)]
+///
+/// Configurations can be cloned cheaply: the closures (thread name,
+/// panic handler, etc) are shared between the clones rather than
+/// duplicated. This makes it easy to build a "base" configuration and
+/// tweak copies of it for several pools.
+#[derive(Clone, Default)]
 pub struct Configuration {
     /// The number of threads in the rayon thread pool.
     /// If zero will use the RAYON_RS_NUM_CPUS environment variable.
@@ -103,19 +215,175 @@ pub struct Configuration {
 
     /// Custom closure, if any, to handle a panic that we cannot propagate
     /// anywhere else.
-    panic_handler: Option<Box<PanicHandler>>,
+    panic_handler: Option<Arc<PanicHandler>>,
 
     /// Closure to compute the name of a thread.
-    get_thread_name: Option<Box<FnMut(usize) -> String>>,
+    get_thread_name: Option<Arc<Fn(usize) -> String>>,
 
     /// The stack size for the created worker threads
     stack_size: Option<usize>,
 
     /// Closure invoked on worker thread start.
-    start_handler: Option<Box<StartHandler>>,
+    start_handler: Option<Arc<StartHandler>>,
 
     /// Closure invoked on worker thread exit.
-    exit_handler: Option<Box<ExitHandler>>,
+    exit_handler: Option<Arc<ExitHandler>>,
+
+    /// Closure invoked with a `JobTiming` after each executed job.
+    job_profiler: Option<Arc<JobProfiler>>,
+
+    /// If true, a watchdog thread periodically checks for apparent
+    /// deadlocks and logs a warning to stderr.
+    deadlock_detection: bool,
+
+    /// If true, workers track how long they spend idle, so that
+    /// `utilization()` can report a busy-fraction per worker.
+    utilization_tracking: bool,
+
+    /// Maximum number of injected jobs that may be queued waiting
+    /// for a worker; `None` means unbounded.
+    max_injected_queue: Option<usize>,
+
+    /// Number of failed steal rounds after which an idle worker goes
+    /// to sleep; `None` means the built-in default.
+    steal_retries: Option<usize>,
+
+    /// Maximum number of jobs a worker keeps in its local deque
+    /// before further spawns spill to the shared injected queue;
+    /// `None` means unbounded.
+    local_queue_cap: Option<usize>,
+
+    /// Process exit code to use when Rayon aborts after an internal
+    /// failure; `None` keeps the default `libc::abort()`.
+    abort_exit_code: Option<i32>,
+
+    /// If true, panics in user code abort the process at the panic
+    /// site instead of being captured and propagated.
+    panic_abort: bool,
+
+    /// If true, only one worker thread is started eagerly; the rest
+    /// are spawned on demand, up to the configured number.
+    lazy_threads: bool,
+
+    /// If true, no worker threads are spawned at all; the pool's jobs
+    /// run on whichever thread drives the pool. Only takes effect
+    /// with the `unstable` feature.
+    caller_driven: bool,
+
+    /// If true, a thread blocked in `ThreadPool::install()` helps
+    /// drain the injected queue instead of sitting idle.
+    cooperative_install: bool,
+
+    /// Minimum piece length below which the fork-join helpers run
+    /// serially; `None` means the built-in default.
+    min_split_len: Option<usize>,
+
+    /// Join nesting depth on one worker's stack beyond which `join()`
+    /// runs serially; `None` means unbounded. Only takes effect with
+    /// the `unstable` feature.
+    max_recursion_depth: Option<usize>,
+
+    /// Number of consecutive panicking jobs on one worker after which
+    /// the pool aborts the process; `None` means never.
+    max_consecutive_panics: Option<usize>,
+
+    /// In what order an idle worker consults the injected queue
+    /// relative to local and stealable work.
+    inject_priority: InjectPriority,
+
+    /// If true, the drop glue of aborted jobs runs on a shared
+    /// cleanup thread instead of inline on a worker.
+    offload_aborted_drops: bool,
+
+    /// If true, a successful steal takes a batch of jobs from the
+    /// victim (up to half its apparent deque) rather than one.
+    steal_batching: bool,
+
+    /// If true, jobs injected from outside the pool are numbered at
+    /// submission and executed in strictly that order.
+    strict_inject_order: bool,
+
+    /// Upper bound on how many sleeping workers a single injected
+    /// batch may wake, or `None` for no bound.
+    wake_batch_limit: Option<usize>,
+
+    /// If true, idle workers release the memory of their emptied side
+    /// queues (sticky jobs, targeted mailbox) instead of holding the
+    /// high-water-mark capacity for the pool's lifetime.
+    shrink_idle_deques: bool,
+
+    /// Seed for the scheduling fuzzer, if any: workers insert
+    /// seed-driven yields on the scheduling paths to shake out
+    /// timing-dependent bugs. Only takes effect with the `unstable`
+    /// feature.
+    scheduler_fuzz: Option<u64>,
+
+    /// If true, the pool records each successful steal -- thief and
+    /// victim worker index -- into a trace retrievable through
+    /// `ThreadPool::scheduler_trace()`. Only takes effect with the
+    /// `unstable` feature.
+    record_steal_trace: bool,
+
+    /// A previously recorded trace whose steal decisions the pool's
+    /// workers replay, if any. Only takes effect with the `unstable`
+    /// feature.
+    replay_steal_trace: Option<registry::SchedulerTrace>,
+
+    /// Closure giving each worker its preferred order of steal
+    /// victims, if any. Only takes effect with the `unstable`
+    /// feature.
+    steal_order: Option<Arc<StealOrderFn>>,
+
+    /// Number of logical CPUs to leave unused when the number of
+    /// threads is computed automatically; ignored if an explicit
+    /// thread count is given.
+    leave_cores_free: Option<usize>,
+
+    /// Closure, if any, that receives every logged scheduling event.
+    event_sink: Option<Arc<log::EventSink>>,
+
+    /// Closure, if any, responsible for actually starting each worker
+    /// thread (see `spawn_handler()`); `None` uses `thread::spawn`.
+    /// The `Mutex` serializes calls, since the closure is `FnMut`.
+    spawn_handler: Option<Arc<Mutex<Box<SpawnHandler>>>>,
+
+    /// Closure invoked when the pool transitions from busy to fully
+    /// idle. Only takes effect with the `unstable` feature.
+    on_idle: Option<Arc<TransitionHandler>>,
+
+    /// Closure invoked when the pool transitions from fully idle back
+    /// to busy. Only takes effect with the `unstable` feature.
+    on_busy: Option<Arc<TransitionHandler>>,
+}
+
+/// In what order an idle worker looks for its next job: does the
+/// queue of jobs injected from outside the pool come before or after
+/// the work already inside it (the worker's own deque, and jobs
+/// stealable from siblings)? See
+/// `Configuration::inject_priority()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InjectPriority {
+    /// Check the injected queue first. Minimizes the latency of
+    /// freshly submitted requests, at the price of letting work
+    /// already underway sit longer -- a steady stream of injected
+    /// jobs can starve in-pool work.
+    PreferInjected,
+
+    /// Finish what the pool has already started -- local deque, then
+    /// stealing -- before accepting new injected work. Maximizes
+    /// throughput of in-flight computations; the default, and the
+    /// historical behavior.
+    PreferLocal,
+
+    /// Alternate between the two orders on successive rounds, so
+    /// that neither source can starve the other.
+    RoundRobin,
+}
+
+impl Default for InjectPriority {
+    fn default() -> InjectPriority {
+        InjectPriority::PreferLocal
+    }
 }
 
 /// The type for a panic handling closure. Note that this same closure
@@ -132,6 +400,29 @@ type StartHandler = Fn(usize) + Send + Sync;
 /// Note that this same closure may be invoked multiple times in parallel.
 type ExitHandler = Fn(usize) + Send + Sync;
 
+/// The type for a per-job profiling closure (see
+/// `Configuration::job_profiler()`). Note that this same closure may
+/// be invoked multiple times in parallel, from every worker thread.
+type JobProfiler = Fn(registry::JobTiming) + Send + Sync;
+
+/// The type for a steal-order closure (see
+/// `Configuration::steal_order()`). Given a worker index, it returns
+/// that worker's preferred order of steal victims. It is invoked once
+/// per worker while the pool is being built.
+type StealOrderFn = Fn(usize) -> Vec<usize> + Send + Sync;
+
+/// The type for a closure that is responsible for starting each
+/// worker thread (see `Configuration::spawn_handler()`). It receives
+/// the parameters for one worker and must arrange for
+/// `ThreadBuilder::run()` to be called on the new thread.
+type SpawnHandler = FnMut(registry::ThreadBuilder) -> io::Result<()> + Send;
+
+/// The type for the pool idle/busy transition callbacks (see
+/// `Configuration::on_idle()` and `Configuration::on_busy()`). Note
+/// that the same closure may be invoked from different threads over
+/// time, whichever thread happens to drive the transition.
+type TransitionHandler = Fn() + Send + Sync;
+
 impl Configuration {
     /// Creates and return a valid rayon thread pool configuration, but does not initialize it.
     pub fn new() -> Configuration {
@@ -146,21 +437,32 @@ impl Configuration {
         } else {
             match env::var("RAYON_RS_NUM_CPUS").ok().and_then(|s| usize::from_str(&s).ok()) {
                 Some(x) if x > 0 => x,
-                _ => num_cpus::get(),
+                _ => {
+                    match self.leave_cores_free {
+                        // never go below one worker, however many
+                        // cores we were asked to reserve
+                        Some(n) => cmp::max(1, num_cpus::get().saturating_sub(n)),
+                        // `num_cpus` can report 0 in constrained
+                        // (e.g. containerized) environments; a
+                        // zero-worker pool would hang the first
+                        // caller forever, so clamp here too
+                        None => cmp::max(1, num_cpus::get()),
+                    }
+                }
             }
         }
     }
 
     /// Get the thread name for the thread with the given index.
-    fn get_thread_name(&mut self, index: usize) -> Option<String> {
-        self.get_thread_name.as_mut().map(|c| c(index))
+    fn get_thread_name(&self, index: usize) -> Option<String> {
+        self.get_thread_name.as_ref().map(|c| c(index))
     }
 
     /// Set a closure which takes a thread index and returns
     /// the thread's name.
     pub fn thread_name<F>(mut self, closure: F) -> Self
-    where F: FnMut(usize) -> String + 'static {
-        self.get_thread_name = Some(Box::new(closure));
+    where F: Fn(usize) -> String + 'static {
+        self.get_thread_name = Some(Arc::new(closure));
         self
     }
 
@@ -184,13 +486,59 @@ impl Configuration {
     /// may wish to use the [`num_cpus`
     /// crate](https://crates.io/crates/num_cpus) to query the number
     /// of CPUs dynamically.
+    ///
+    /// **Deterministic execution:** a pool with exactly one thread
+    /// executes all work serially and in a deterministic order: the
+    /// second closure of a `join()` always runs after the first, and
+    /// spawned tasks run in a fixed (last-in, first-out) order when
+    /// the worker next returns to the scheduler. Since there are no
+    /// other workers, no stealing ever occurs. This is the canonical
+    /// way to get reproducible runs when testing parallel code.
     pub fn num_threads(mut self, num_threads: usize) -> Configuration {
         self.num_threads = num_threads;
         self
     }
 
+    /// Installs a closure that receives every scheduling event the
+    /// runtime logs (work stolen, jobs injected, workers going to
+    /// sleep, ...), turning Rayon's internal tracing into a stream
+    /// that can be routed into an external tracing system.
+    ///
+    /// The sink is installed process-wide when the pool is built, and
+    /// uninstalled again when that pool terminates; building a second
+    /// pool with a sink replaces the first. When no sink is
+    /// installed, the cost at each event site is a single atomic
+    /// load. If the sink panics, the panic is swallowed -- the
+    /// scheduler cannot propagate it anywhere sensible.
+    #[cfg(feature = "unstable")]
+    pub fn event_sink<F>(mut self, sink: F) -> Configuration
+        where F: Fn(&Event) + Send + Sync + 'static
+    {
+        self.event_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Returns the event sink, if any.
+    fn take_event_sink(&mut self) -> Option<Arc<log::EventSink>> {
+        self.event_sink.take()
+    }
+
+    /// Rather than an absolute thread count, ask for "all cores but
+    /// `n`": when the number of threads is computed automatically,
+    /// use the number of logical CPUs minus `n`, clamped to a minimum
+    /// of one worker. This is convenient for co-located services that
+    /// should not monopolize a shared machine.
+    ///
+    /// An explicit thread count -- whether set via `num_threads()`
+    /// or the `RAYON_RS_NUM_CPUS` environment variable -- always
+    /// takes precedence over this setting.
+    pub fn leave_cores_free(mut self, n: usize) -> Configuration {
+        self.leave_cores_free = Some(n);
+        self
+    }
+
     /// Returns a copy of the current panic handler.
-    fn take_panic_handler(&mut self) -> Option<Box<PanicHandler>> {
+    fn take_panic_handler(&mut self) -> Option<Arc<PanicHandler>> {
         self.panic_handler.take()
     }
 
@@ -205,13 +553,19 @@ impl Configuration {
     /// process, under the principle that panics should not go
     /// unobserved.
     ///
+    /// The handler is also invoked when a panic from within Rayon
+    /// itself is about to abort a worker thread, so that applications
+    /// get one last chance to record a diagnostic before the process
+    /// dies. Note that the abort still occurs after the handler
+    /// returns in that case.
+    ///
     /// If the panic handler itself panics, this will abort the
     /// process. To prevent this, wrap the body of your panic handler
     /// in a call to `std::panic::catch_unwind()`.
     pub fn panic_handler<H>(mut self, panic_handler: H) -> Configuration
         where H: Fn(Box<Any + Send>) + Send + Sync + 'static
     {
-        self.panic_handler = Some(Box::new(panic_handler));
+        self.panic_handler = Some(Arc::new(panic_handler));
         self
     }
 
@@ -226,8 +580,575 @@ impl Configuration {
         self
     }
 
+    /// Returns true if deadlock detection was requested.
+    fn get_deadlock_detection(&self) -> bool {
+        self.deadlock_detection
+    }
+
+    /// Enable a watchdog thread that periodically checks whether the
+    /// thread-pool appears to be deadlocked -- that is, whether every
+    /// worker thread has been idle for several seconds while some
+    /// caller is still blocked waiting on the pool. When this
+    /// situation is detected, a warning is logged to stderr.
+    ///
+    /// This is a debugging aid: the heuristic is deliberately coarse,
+    /// and a warning does not prove a deadlock (nor does its absence
+    /// prove there is none). The watchdog costs one extra (mostly
+    /// sleeping) thread per pool, so it is disabled by default.
+    pub fn deadlock_detection(mut self, enabled: bool) -> Configuration {
+        self.deadlock_detection = enabled;
+        self
+    }
+
+    /// Returns true if lazy thread spawning was requested.
+    fn get_lazy_threads(&self) -> bool {
+        self.lazy_threads
+    }
+
+    /// Normally, all worker threads are spawned eagerly when the pool
+    /// is created. With lazy threads enabled, only one worker is
+    /// started up front, and further workers (up to the number
+    /// configured via `num_threads()`) are spawned on demand, when
+    /// jobs are injected while every running worker is already
+    /// busy. This reduces startup cost and idle footprint for
+    /// programs that only occasionally lean on the pool, at the price
+    /// of a short ramp-up the first time it is saturated.
+    pub fn lazy_threads(mut self, enabled: bool) -> Configuration {
+        self.lazy_threads = enabled;
+        self
+    }
+
+    /// Returns true if a caller-driven pool was requested.
+    fn get_caller_driven(&self) -> bool {
+        self.caller_driven
+    }
+
+    /// Builds a pool that spawns no OS threads at all: every job runs
+    /// on whichever thread drives the pool. A host event loop calls
+    /// `ThreadPool::run_pending()` whenever it is ready to donate
+    /// time, executing injected and spawned work inline, and a thread
+    /// blocked in `install()` drains the queue itself (cooperative
+    /// installs are implied). This turns the pool into a
+    /// single-threaded cooperative executor for environments that
+    /// forbid spawning threads (some WASM targets) or that want Rayon
+    /// work interleaved with an existing event loop.
+    ///
+    /// Two caveats. APIs that reserve work for a particular worker
+    /// (`spawn_on()`, `broadcast()`) must not be used on a
+    /// caller-driven pool: they would wait on workers that are never
+    /// going to start. And a job being run by the driving thread runs
+    /// on it as an ordinary external thread, so nested `join()` or
+    /// `scope()` calls inside such a job fall back to the global
+    /// pool rather than this one.
+    ///
+    /// Disabled by default. A no-op without the `unstable` feature.
+    #[cfg(feature = "unstable")]
+    pub fn caller_driven(mut self, enabled: bool) -> Configuration {
+        self.caller_driven = enabled;
+        self
+    }
+
+    /// Returns true if cooperative install was requested.
+    fn get_cooperative_install(&self) -> bool {
+        self.cooperative_install
+    }
+
+    /// Normally, an off-pool thread that calls
+    /// `ThreadPool::install()` blocks until a worker has executed the
+    /// closure, contributing nothing itself. With cooperative install
+    /// enabled, the blocked thread instead helps out: while it waits,
+    /// it takes jobs from the pool's injected queue and runs them in
+    /// place, so install-heavy programs get up to one extra thread's
+    /// worth of throughput. Only the injected queue is drained --
+    /// jobs on worker deques stay where they are, since the caller
+    /// has no deque of its own to steal into.
+    ///
+    /// One behavioral consequence: the installed closure itself may
+    /// end up running on the calling thread rather than a worker
+    /// (just as it already does when `install()` is called from
+    /// inside the pool). Code that relies on
+    /// `current_thread_index()` being `Some` inside `install()`
+    /// should leave this disabled.
+    pub fn cooperative_install(mut self, enabled: bool) -> Configuration {
+        self.cooperative_install = enabled;
+        self
+    }
+
+    /// Get the maximum injected queue length, if any.
+    fn get_max_injected_queue(&self) -> Option<usize> {
+        self.max_injected_queue
+    }
+
+    /// Bound the queue of jobs injected into the pool from the
+    /// outside (e.g. by `install()` or `spawn_async()`). When the
+    /// queue is full, injecting threads block until workers have
+    /// drained some of the backlog, providing backpressure for
+    /// streaming workloads where an unbounded producer could
+    /// otherwise grow the queue without limit.
+    ///
+    /// A batch larger than the bound is still accepted, but only
+    /// once the queue is completely empty. By default the queue is
+    /// unbounded.
+    pub fn max_injected_queue(mut self, max: usize) -> Configuration {
+        self.max_injected_queue = Some(max);
+        self
+    }
+
+    /// Returns the configured abort exit code, if any.
+    fn get_abort_exit_code(&self) -> Option<i32> {
+        self.abort_exit_code
+    }
+
+    /// Set the process exit code used when Rayon aborts after an
+    /// internal failure (a panic in scheduler code, or a worker
+    /// leaving the main loop unexpectedly). By default the process is
+    /// terminated with `libc::abort()`, i.e. `SIGABRT`; configuring a
+    /// code switches those paths to `process::exit(code)`, letting
+    /// you pick a value your supervisor recognizes as "Rayon internal
+    /// failure" without colliding with your application's own exit
+    /// codes.
+    ///
+    /// The setting is process-wide and takes effect when a pool is
+    /// built from this configuration; if several pools configure it,
+    /// the last one built wins.
+    pub fn abort_exit_code(mut self, code: i32) -> Configuration {
+        self.abort_exit_code = Some(code);
+        self
+    }
+
+    /// Returns whether user panics should abort the process.
+    fn get_panic_abort(&self) -> bool {
+        self.panic_abort
+    }
+
+    /// If `enabled` is true, a panic in user code running on this
+    /// pool aborts the process at the panic site instead of being
+    /// captured and rethrown from `join()`, `install()`, or the panic
+    /// handler. This mirrors building with `panic = "abort"`, but
+    /// scoped to the pool's worker threads, and is mainly a debugging
+    /// aid: the abort fires while the panicking frames are still on
+    /// the stack, so core dumps and `RUST_BACKTRACE` point at the
+    /// actual panic site rather than the join point that rethrew it.
+    ///
+    /// Defaults to false, i.e. the usual capture-and-propagate
+    /// behavior.
+    pub fn panic_abort(mut self, enabled: bool) -> Configuration {
+        self.panic_abort = enabled;
+        self
+    }
+
+    /// Returns the configured steal retry limit, if any.
+    fn get_steal_retries(&self) -> Option<usize> {
+        self.steal_retries
+    }
+
+    /// Set the number of failed steal rounds after which an idle
+    /// worker thread goes to sleep. Between rounds the worker yields
+    /// its time slice, so this knob trades wakeup latency for CPU
+    /// usage: a small value makes idle workers release their CPUs
+    /// almost immediately (good for pools sharing a machine with
+    /// other processes), while a large value keeps them spinning and
+    /// ready to pick up new work with minimal delay. Halfway through
+    /// the limit the worker announces itself "sleepy", which is when
+    /// it starts becoming more expensive to post new work. Values
+    /// below one are treated as one. The default is 64 rounds.
+    pub fn steal_retries(mut self, retries: usize) -> Configuration {
+        self.steal_retries = Some(retries);
+        self
+    }
+
+    /// Returns the configured local queue cap, if any.
+    fn get_local_queue_cap(&self) -> Option<usize> {
+        self.local_queue_cap
+    }
+
+    /// Bound the length of each worker's local deque: once a worker
+    /// already has `cap` jobs queued locally, further spawns are
+    /// handed to the shared injected queue instead, where every
+    /// worker sees them immediately. This caps the memory a single
+    /// very unbalanced spawner can tie up in its private deque, and
+    /// improves load balancing for such workloads: the excess work
+    /// does not have to wait to be discovered by random stealing.
+    ///
+    /// The spill keeps the job accounting intact -- a spilled job is
+    /// counted as pending exactly once, and the per-worker length
+    /// hints that drive the steal heuristics only ever count jobs
+    /// that really sit in the local deque. With a bounded injected
+    /// queue (`max_injected_queue()`), a worker never blocks waiting
+    /// for injector room: if the injected queue is full, the job
+    /// simply stays local, temporarily exceeding the cap.
+    ///
+    /// By default the local deques are unbounded. A cap of zero
+    /// spills every spawn, which serializes all work through the
+    /// shared queue and is useful only for debugging.
+    pub fn local_queue_cap(mut self, cap: usize) -> Configuration {
+        self.local_queue_cap = Some(cap);
+        self
+    }
+
+    /// Returns the scheduling-fuzzer seed, if any.
+    #[cfg(feature = "unstable")]
+    fn get_scheduler_fuzz(&self) -> Option<u64> {
+        self.scheduler_fuzz
+    }
+
+    /// Enable scheduling fuzz: every worker of the pool inserts
+    /// pseudo-random yields on the scheduling paths (pushing,
+    /// popping, and hunting for work), driven by a per-worker stream
+    /// derived from `seed`. This perturbs the interleaving of workers
+    /// so that tests exercise orderings that rarely occur naturally;
+    /// rerunning with the same seed replays the same per-worker
+    /// decision streams, though the operating system still has the
+    /// last word on actual thread timing. This is test infrastructure
+    /// -- it deliberately makes the pool slower -- and is a no-op
+    /// without the `unstable` feature.
+    #[cfg(feature = "unstable")]
+    pub fn scheduler_fuzz(mut self, seed: u64) -> Configuration {
+        self.scheduler_fuzz = Some(seed);
+        self
+    }
+
+    /// Returns true if trace recording was requested.
+    #[cfg(feature = "unstable")]
+    fn get_record_steal_trace(&self) -> bool {
+        self.record_steal_trace
+    }
+
+    /// Record the pool's scheduling decisions: every successful steal
+    /// appends its thief and victim index to a trace that can be
+    /// retrieved at any time through `ThreadPool::scheduler_trace()`
+    /// and replayed through `replay_steal_trace()`. This is a tool
+    /// for reproducing scheduler-dependent bugs -- capture the trace
+    /// of a failing run, then replay it until the bug gives in --
+    /// and recording serializes the steal path through a mutex, so
+    /// leave it disabled in production. Disabled by default, and a
+    /// no-op without the `unstable` feature.
+    #[cfg(feature = "unstable")]
+    pub fn record_steal_trace(mut self, enabled: bool) -> Configuration {
+        self.record_steal_trace = enabled;
+        self
+    }
+
+    /// Takes the replay trace out of the configuration, if any.
+    #[cfg(feature = "unstable")]
+    fn take_replay_steal_trace(&mut self) -> Option<SchedulerTrace> {
+        self.replay_steal_trace.take()
+    }
+
+    /// Replay a trace previously captured with
+    /// `record_steal_trace()`: instead of picking steal victims at
+    /// random, each worker works through the victims the trace
+    /// recorded for it, in order, waiting for the scripted victim to
+    /// have work rather than stealing elsewhere. Replay is
+    /// best-effort -- it steers the schedule, it cannot pin down the
+    /// operating system's thread timing -- and a worker abandons a
+    /// scripted entry (and, once its script is exhausted, falls back
+    /// to normal stealing) if the run visibly diverges from the
+    /// recorded one. Replaying a trace against a different workload
+    /// or pool size is pointless but harmless. A no-op without the
+    /// `unstable` feature.
+    #[cfg(feature = "unstable")]
+    pub fn replay_steal_trace(mut self, trace: SchedulerTrace) -> Configuration {
+        self.replay_steal_trace = Some(trace);
+        self
+    }
+
+    /// Takes the steal-order closure out of the configuration, if any.
+    #[cfg(feature = "unstable")]
+    fn take_steal_order(&mut self) -> Option<Arc<StealOrderFn>> {
+        self.steal_order.take()
+    }
+
+    /// Provide, for each worker index, the order in which that worker
+    /// should probe the others when it steals. Normally victims are
+    /// tried in a random rotation; on big (e.g. NUMA) machines,
+    /// stealing from a near neighbor is much cheaper than from a
+    /// distant one, and a caller that knows the topology can rank
+    /// near victims first here. The closure is invoked once per
+    /// worker while the pool is built. Entries that are out of range,
+    /// duplicated, or name the worker itself are dropped, and any
+    /// victims the order omits are appended at the end, so every
+    /// worker always stays reachable. Workers still use a random
+    /// rotation for roughly one idle sweep in eight, so a fixed
+    /// order cannot starve a distant-but-busy victim or lock workers
+    /// into probing each other in lockstep. By default victims are
+    /// picked purely at random. A no-op without the `unstable`
+    /// feature.
+    #[cfg(feature = "unstable")]
+    pub fn steal_order<F>(mut self, order: F) -> Configuration
+        where F: Fn(usize) -> Vec<usize> + Send + Sync + 'static
+    {
+        self.steal_order = Some(Arc::new(order));
+        self
+    }
+
+    /// Returns true if batch stealing was requested.
+    fn get_steal_batching(&self) -> bool {
+        self.steal_batching
+    }
+
+    /// Normally a worker that raids a victim's deque takes a single
+    /// job and runs it. With batch stealing enabled, a successful
+    /// raid also moves up to half of the victim's remaining (regular)
+    /// deque into the thief's own, so the two end up with comparable
+    /// backlogs after one round-trip instead of many. This is a
+    /// throughput optimization for workloads with bursty spawning,
+    /// where one worker can otherwise accumulate a deep deque that
+    /// its siblings drain one steal at a time; the extra jobs a thief
+    /// takes remain stealable from it in turn. Disabled by default.
+    pub fn steal_batching(mut self, enabled: bool) -> Configuration {
+        self.steal_batching = enabled;
+        self
+    }
+
+    /// Returns true if strict injection ordering was requested.
+    fn get_strict_inject_order(&self) -> bool {
+        self.strict_inject_order
+    }
+
+    /// Make the order of jobs injected from outside the pool strict:
+    /// every submission claims a monotonic sequence number on entry,
+    /// and workers execute injected jobs in exactly that order --
+    /// a worker finding only later-numbered jobs idles until the
+    /// next-in-sequence one is available, rather than running ahead.
+    /// By default the order is only approximate: concurrent
+    /// submitters are serialized by a lock, and a submitter preempted
+    /// at the wrong moment can land behind one that arrived after it.
+    ///
+    /// This is an interop feature for sequenced event processing; it
+    /// costs throughput, since pulling injected jobs serializes on
+    /// the pool's state lock (rather than the lock-free injector
+    /// deque) and workers may idle while the queue is non-empty.
+    /// Only the order in which injected jobs *start* is pinned down:
+    /// jobs handed to a pool of more than one worker still run
+    /// concurrently, and nothing is promised about the order in which
+    /// they finish. Disabled by default.
+    pub fn strict_inject_order(mut self, enabled: bool) -> Configuration {
+        self.strict_inject_order = enabled;
+        self
+    }
+
+    /// Returns the wake batch limit, if one was set.
+    fn get_wake_batch_limit(&self) -> Option<usize> {
+        self.wake_batch_limit
+    }
+
+    /// When a batch of K jobs is injected from outside the pool, the
+    /// pool wakes `min(K, sleeping workers)` of its sleepers; with a
+    /// limit of N it wakes at most `min(K, N)` instead. Jobs beyond
+    /// that bound are not lost -- they are picked up as the woken
+    /// workers (and any already-awake ones) drain the injected queue
+    /// -- but they may wait longer. This trades latency under bursty
+    /// injection for fewer wakeups, which can matter on large pools
+    /// where a burst otherwise causes a notification storm. A limit
+    /// of zero is treated as one, since an injected job must always
+    /// be able to wake somebody.
+    pub fn wake_batch_limit(mut self, limit: usize) -> Configuration {
+        self.wake_batch_limit = Some(limit);
+        self
+    }
+
+    /// Returns true if idle workers should shrink their side queues.
+    fn get_shrink_idle_deques(&self) -> bool {
+        self.shrink_idle_deques
+    }
+
+    /// A worker's queues grow to fit the deepest backlog they ever
+    /// held and normally keep that capacity for the pool's lifetime,
+    /// which for long-lived pools with bursty spawning amounts to a
+    /// slow memory leak. With this enabled, a worker that goes idle
+    /// releases the buffers of its emptied side queues (the sticky
+    /// queue and the targeted mailbox), so steady-state memory drops
+    /// back down after a burst; the next burst simply reallocates.
+    /// Disabled by default.
+    ///
+    /// The main work-stealing deque itself is owned by the `deque`
+    /// crate, whose buffers can only be freed whole; its memory is
+    /// reclaimed when the pool is dropped (or the worker retires, see
+    /// `resize()`), not by this knob.
+    pub fn shrink_idle_deques(mut self, enabled: bool) -> Configuration {
+        self.shrink_idle_deques = enabled;
+        self
+    }
+
+    /// Returns true if aborted jobs' drop glue should run off-worker.
+    fn get_offload_aborted_drops(&self) -> bool {
+        self.offload_aborted_drops
+    }
+
+    /// When a job is aborted without running -- today that means a
+    /// pending job dropped by `scope_abort_on_panic()` after a
+    /// sibling panicked -- its closure's destructors normally run
+    /// inline on whichever worker picked the job up. If those
+    /// destructors are slow, or themselves lean on the pool, that
+    /// worker is stalled for the duration. With this enabled, the
+    /// aborted closure is instead handed to a process-wide cleanup
+    /// thread (started lazily, shared by all pools) and the worker
+    /// moves straight on to the next job.
+    ///
+    /// The scope still does not complete until the destructors have
+    /// actually run, so borrowed data remains valid for them; only
+    /// *where* they run changes. A panic in an off-loaded destructor
+    /// is swallowed rather than reported through the panic handler.
+    /// Disabled by default.
+    pub fn offload_aborted_drops(mut self, enabled: bool) -> Configuration {
+        self.offload_aborted_drops = enabled;
+        self
+    }
+
+    /// Returns the configured inject priority.
+    fn get_inject_priority(&self) -> InjectPriority {
+        self.inject_priority
+    }
+
+    /// Set in what order an idle worker looks for its next job: the
+    /// queue of jobs injected from the outside versus the work
+    /// already inside the pool. The default, `PreferLocal`, finishes
+    /// what the pool has started before accepting new submissions;
+    /// `PreferInjected` trades some of that throughput for lower
+    /// latency on fresh submissions, and `RoundRobin` alternates so
+    /// that neither source can starve the other. See
+    /// `InjectPriority` for details. Whatever the policy, jobs
+    /// reserved for a specific worker (`spawn_on()`) are checked
+    /// last.
+    pub fn inject_priority(mut self, priority: InjectPriority) -> Configuration {
+        self.inject_priority = priority;
+        self
+    }
+
+    /// Returns the configured consecutive-panic limit, if any.
+    fn get_max_consecutive_panics(&self) -> Option<usize> {
+        self.max_consecutive_panics
+    }
+
+    /// Set a safety valve for streaming workloads: after `max`
+    /// consecutive jobs on one worker thread have panicked (with the
+    /// panics captured by a `panic_handler()`), the pool logs to
+    /// stderr and aborts the process, on the theory that something is
+    /// fundamentally broken and failing fast beats spinning on a
+    /// poison pill. Any job that completes normally resets that
+    /// worker's count.
+    ///
+    /// Values below one are treated as one. Disabled by default:
+    /// captured panics never abort, however many there are. Without a
+    /// panic handler the limit is moot, since the first uncaught
+    /// panic already aborts.
+    pub fn max_consecutive_panics(mut self, max: usize) -> Configuration {
+        self.max_consecutive_panics = Some(max);
+        self
+    }
+
+    /// Returns the configured minimum split length, if any.
+    fn get_min_split_len(&self) -> Option<usize> {
+        self.min_split_len
+    }
+
+    /// Set the pool-wide minimum split length: the piece size below
+    /// which the recursive fork-join helpers (`reduce_range()`,
+    /// `par_apply()`, and friends) stop splitting and run serially,
+    /// when the caller passes them a `min_chunk` of zero. Centralizing
+    /// the threshold here lets a program tune the
+    /// scheduling-overhead/parallelism trade-off in one place: raise
+    /// it when per-element work is cheap, lower it when each element
+    /// is expensive. Values below one are treated as one. The default
+    /// is 1, i.e. helpers split all the way down unless told
+    /// otherwise.
+    pub fn min_split_len(mut self, len: usize) -> Configuration {
+        self.min_split_len = Some(len);
+        self
+    }
+
+    /// Returns the configured join-depth cap, if any.
+    #[cfg(feature = "unstable")]
+    fn get_max_recursion_depth(&self) -> Option<usize> {
+        self.max_recursion_depth
+    }
+
+    /// Set a cap on how deeply `join()` calls may nest on one
+    /// worker's stack before they stop splitting: a `join()` that
+    /// finds `max_depth` enclosing joins already live on its worker
+    /// runs both closures serially instead, and so does everything
+    /// nested inside them. Divide-and-conquer code whose recursion
+    /// depth is driven by the input (an unbalanced tree, a
+    /// pathological quicksort pivot) can use this as a stack-overflow
+    /// safety valve: splitting stops at a predictable depth instead
+    /// of at whatever depth exhausts the worker's stack, which may
+    /// come sooner than expected since stolen jobs run -- and nest --
+    /// on the stealing worker's stack.
+    ///
+    /// The count is per worker, not per call chain: a join executed
+    /// by a stolen job counts against the joins it happens to be
+    /// stacked on, since they share the same physical stack. Serial
+    /// execution past the cap still recurses like the equivalent
+    /// sequential algorithm, so the cap bounds the scheduler's
+    /// contribution to stack growth, not the algorithm's own.
+    ///
+    /// Unbounded by default. A no-op without the `unstable` feature.
+    #[cfg(feature = "unstable")]
+    pub fn max_recursion_depth(mut self, max_depth: usize) -> Configuration {
+        self.max_recursion_depth = Some(max_depth);
+        self
+    }
+
+    /// Returns true if utilization tracking was requested.
+    fn get_utilization_tracking(&self) -> bool {
+        self.utilization_tracking
+    }
+
+    /// Enable tracking of per-worker idle time. When enabled, each
+    /// worker records how long it spends asleep waiting for work,
+    /// and the pool can report a busy-fraction per worker (see
+    /// `ThreadPool::utilization()`). The measurement uses coarse
+    /// `Instant` deltas taken only when a worker actually goes to
+    /// sleep, so the overhead is negligible; it is still disabled by
+    /// default so that pools which never ask for utilization pay
+    /// nothing at all.
+    pub fn utilization_tracking(mut self, enabled: bool) -> Configuration {
+        self.utilization_tracking = enabled;
+        self
+    }
+
+    /// Takes the current spawn handler, leaving `None`.
+    fn take_spawn_handler(&mut self) -> Option<Arc<Mutex<Box<SpawnHandler>>>> {
+        self.spawn_handler.take()
+    }
+
+    /// Set a closure responsible for actually starting each worker
+    /// thread, in place of `thread::spawn`. This is the hook for
+    /// embedding Rayon in environments with custom thread creation --
+    /// sandboxes, custom runtimes, or threads the host already
+    /// manages. The closure receives a `ThreadBuilder` describing one
+    /// worker (its index, and the configured name and stack size) and
+    /// must arrange for `ThreadBuilder::run()` to be called on the
+    /// new thread; `run()` does not return until the worker shuts
+    /// down. Returning an `Err` makes pool creation fail.
+    ///
+    /// The default behavior, used when no handler is set, is
+    /// equivalent to:
+    ///
+    /// ```rust,ignore
+    /// config.spawn_handler(|thread| {
+    ///     let mut b = std::thread::Builder::new();
+    ///     if let Some(name) = thread.name() {
+    ///         b = b.name(name.to_string());
+    ///     }
+    ///     if let Some(stack_size) = thread.stack_size() {
+    ///         b = b.stack_size(stack_size);
+    ///     }
+    ///     b.spawn(move || thread.run())?;
+    ///     Ok(())
+    /// })
+    /// ```
+    #[cfg(feature = "unstable")]
+    pub fn spawn_handler<F>(mut self, spawn_handler: F) -> Configuration
+        where F: FnMut(ThreadBuilder) -> io::Result<()> + Send + 'static
+    {
+        self.spawn_handler = Some(Arc::new(Mutex::new(Box::new(spawn_handler))));
+        self
+    }
+
     /// Takes the current thread start callback, leaving `None`.
-    fn take_start_handler(&mut self) -> Option<Box<StartHandler>> {
+    fn take_start_handler(&mut self) -> Option<Arc<StartHandler>> {
         self.start_handler.take()
     }
 
@@ -238,12 +1159,12 @@ impl Configuration {
     pub fn start_handler<H>(mut self, start_handler: H) -> Configuration
         where H: Fn(usize) + Send + Sync + 'static
     {
-        self.start_handler = Some(Box::new(start_handler));
+        self.start_handler = Some(Arc::new(start_handler));
         self
     }
 
     /// Returns a current thread exit callback, leaving `None`.
-    fn take_exit_handler(&mut self) -> Option<Box<ExitHandler>> {
+    fn take_exit_handler(&mut self) -> Option<Arc<ExitHandler>> {
         self.exit_handler.take()
     }
 
@@ -254,7 +1175,84 @@ impl Configuration {
     pub fn exit_handler<H>(mut self, exit_handler: H) -> Configuration
         where H: Fn(usize) + Send + Sync + 'static
     {
-        self.exit_handler = Some(Box::new(exit_handler));
+        self.exit_handler = Some(Arc::new(exit_handler));
+        self
+    }
+
+    /// Takes the current idle-transition callback, leaving `None`.
+    #[cfg(feature = "unstable")]
+    fn take_on_idle(&mut self) -> Option<Arc<TransitionHandler>> {
+        self.on_idle.take()
+    }
+
+    /// Set a callback to be invoked when the pool goes fully idle:
+    /// the moment the last awake worker gives up looking for work and
+    /// goes to sleep. Paired with `on_busy()`, this is the hook for
+    /// power management and autoscaling -- parking cores, shrinking a
+    /// fleet, or just recording duty cycles -- without polling
+    /// `ThreadPool::snapshot()`.
+    ///
+    /// The two callbacks are edge-triggered and strictly alternate:
+    /// after `on_idle` fires, no amount of further settling repeats
+    /// it, and a burst of wakeups fires `on_busy` exactly once, so
+    /// rapid flips cannot multiply invocations of the same edge. They
+    /// are invoked without any scheduler lock held, so it is safe for
+    /// them to call back into the pool -- though note that injecting
+    /// work from `on_idle` will promptly trigger `on_busy`.
+    ///
+    /// "Idle" here means every configured worker thread is asleep;
+    /// with lazy or caller-driven spawning, the pool does not reach
+    /// the idle state until all of its workers have been started. A
+    /// no-op without the `unstable` feature.
+    #[cfg(feature = "unstable")]
+    pub fn on_idle<H>(mut self, on_idle: H) -> Configuration
+        where H: Fn() + Send + Sync + 'static
+    {
+        self.on_idle = Some(Arc::new(on_idle));
+        self
+    }
+
+    /// Takes the current busy-transition callback, leaving `None`.
+    #[cfg(feature = "unstable")]
+    fn take_on_busy(&mut self) -> Option<Arc<TransitionHandler>> {
+        self.on_busy.take()
+    }
+
+    /// Set a callback to be invoked when the pool leaves the fully
+    /// idle state: the first wakeup -- typically an injected job or a
+    /// `spawn` from outside -- after `on_idle` fired. See `on_idle()`
+    /// for the transition semantics; the same locking and debouncing
+    /// guarantees apply. A no-op without the `unstable` feature.
+    #[cfg(feature = "unstable")]
+    pub fn on_busy<H>(mut self, on_busy: H) -> Configuration
+        where H: Fn() + Send + Sync + 'static
+    {
+        self.on_busy = Some(Arc::new(on_busy));
+        self
+    }
+
+    /// Returns the job profiler, if any.
+    fn take_job_profiler(&mut self) -> Option<Arc<JobProfiler>> {
+        self.job_profiler.take()
+    }
+
+    /// Set a closure invoked after every job a worker of this pool
+    /// executes, receiving a `JobTiming` with the worker's index and
+    /// the wall-clock duration of the job's body. This is enough to
+    /// build flame-graph-like views of where pool time goes without
+    /// attaching an external profiler.
+    ///
+    /// The closure runs on the worker that executed the job, for
+    /// every job, so it should be cheap and must be thread-safe;
+    /// pools without a profiler pay only an untaken branch per job.
+    /// Jobs that are cancelled before they start -- for example
+    /// pending jobs dropped by `scope_abort_on_panic()` -- only run
+    /// their drop glue, and that is what gets timed for them.
+    #[cfg(feature = "unstable")]
+    pub fn job_profiler<H>(mut self, profiler: H) -> Configuration
+        where H: Fn(registry::JobTiming) + Send + Sync + 'static
+    {
+        self.job_profiler = Some(Arc::new(profiler));
         self
     }
 }
@@ -272,11 +1270,14 @@ impl Configuration {
 ///   this cost is minimal.
 ///
 /// Initialization of the global thread pool happens exactly
-/// once. Once started, the configuration cannot be
-/// changed. Therefore, if you call `initialize` a second time, it
-/// will return an error. An `Ok` result indicates that this
-/// is the first initialization of the thread pool.
-pub fn initialize(config: Configuration) -> Result<(), Box<Error>> {
+/// once. Once started, the configuration cannot be changed.
+/// Therefore, if you call `initialize` a second time -- or after the
+/// pool was already started lazily by some parallel call -- it will
+/// return an error whose `kind()` is `GlobalPoolAlreadyInitialized`,
+/// and the given configuration is dropped rather than silently
+/// ignored. An `Ok` result indicates that this is the first
+/// initialization of the thread pool.
+pub fn initialize(config: Configuration) -> Result<(), ThreadPoolBuildError> {
     let registry = try!(registry::init_global_registry(config));
     registry.wait_until_primed();
     Ok(())
@@ -292,7 +1293,27 @@ pub fn dump_stats() {
 impl fmt::Debug for Configuration {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let Configuration { ref num_threads, ref get_thread_name, ref panic_handler, ref stack_size,
-                            ref start_handler, ref exit_handler } = *self;
+                            ref start_handler, ref exit_handler, ref job_profiler,
+                            ref deadlock_detection,
+                            ref utilization_tracking, ref max_injected_queue, ref steal_retries,
+                            ref local_queue_cap,
+                            ref abort_exit_code, ref panic_abort,
+                            ref lazy_threads, ref caller_driven,
+                            ref cooperative_install, ref min_split_len,
+                            ref max_recursion_depth,
+                            ref max_consecutive_panics, ref inject_priority,
+                            ref offload_aborted_drops, ref steal_batching,
+                            ref strict_inject_order, ref wake_batch_limit,
+                            ref shrink_idle_deques, ref scheduler_fuzz,
+                            ref record_steal_trace, ref replay_steal_trace,
+                            ref steal_order,
+                            ref leave_cores_free, ref event_sink, ref spawn_handler,
+                            ref on_idle, ref on_busy } = *self;
+        let event_sink = event_sink.as_ref().map(|_| "<closure>");
+        let spawn_handler = spawn_handler.as_ref().map(|_| "<closure>");
+        let steal_order = steal_order.as_ref().map(|_| "<closure>");
+        let on_idle = on_idle.as_ref().map(|_| "<closure>");
+        let on_busy = on_busy.as_ref().map(|_| "<closure>");
 
         // Just print `Some("<closure>")` or `None` to the debug
         // output.
@@ -303,6 +1324,7 @@ impl fmt::Debug for Configuration {
         let panic_handler = panic_handler.as_ref().map(|_| "<closure>");
         let start_handler = start_handler.as_ref().map(|_| "<closure>");
         let exit_handler = exit_handler.as_ref().map(|_| "<closure>");
+        let job_profiler = job_profiler.as_ref().map(|_| "<closure>");
 
         f.debug_struct("Configuration")
          .field("num_threads", num_threads)
@@ -311,6 +1333,35 @@ impl fmt::Debug for Configuration {
          .field("stack_size", &stack_size)
          .field("start_handler", &start_handler)
          .field("exit_handler", &exit_handler)
+         .field("job_profiler", &job_profiler)
+         .field("deadlock_detection", deadlock_detection)
+         .field("utilization_tracking", utilization_tracking)
+         .field("max_injected_queue", max_injected_queue)
+         .field("steal_retries", steal_retries)
+         .field("local_queue_cap", local_queue_cap)
+         .field("abort_exit_code", abort_exit_code)
+         .field("panic_abort", panic_abort)
+         .field("lazy_threads", lazy_threads)
+         .field("caller_driven", caller_driven)
+         .field("cooperative_install", cooperative_install)
+         .field("min_split_len", min_split_len)
+         .field("max_recursion_depth", max_recursion_depth)
+         .field("max_consecutive_panics", max_consecutive_panics)
+         .field("inject_priority", inject_priority)
+         .field("offload_aborted_drops", offload_aborted_drops)
+         .field("steal_batching", steal_batching)
+         .field("strict_inject_order", strict_inject_order)
+         .field("wake_batch_limit", wake_batch_limit)
+         .field("shrink_idle_deques", shrink_idle_deques)
+         .field("scheduler_fuzz", scheduler_fuzz)
+         .field("record_steal_trace", record_steal_trace)
+         .field("replay_steal_trace", replay_steal_trace)
+         .field("steal_order", &steal_order)
+         .field("leave_cores_free", leave_cores_free)
+         .field("event_sink", &event_sink)
+         .field("spawn_handler", &spawn_handler)
+         .field("on_idle", &on_idle)
+         .field("on_busy", &on_busy)
          .finish()
     }
 }
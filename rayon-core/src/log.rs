@@ -5,7 +5,9 @@
 //! `DUMP_LOGS` to be `true`.
 
 use std::env;
-use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT};
+use unwind;
 
 #[derive(Debug)]
 pub enum Event {
@@ -19,6 +21,7 @@ pub enum Event {
     DidNotFindWork { worker: usize, yields: usize },
     StoleWork { worker: usize, victim: usize },
     UninjectedWork { worker: usize },
+    TargetedWork { worker: usize },
     WaitUntil { worker: usize },
     LatchSet { worker: usize },
     InjectJobs { count: usize },
@@ -47,9 +50,57 @@ lazy_static! {
     pub static ref LOG_ENV: bool = env::var("RAYON_RS_LOG").is_ok();
 }
 
+/// The type for a user-supplied event sink: a closure that receives
+/// every logged scheduling event. Note that it will be invoked from
+/// many threads in parallel.
+pub type EventSink = Fn(&Event) + Send + Sync;
+
+/// Whether a sink is installed; checked before each event is even
+/// constructed, so that the cost when no sink is installed is a
+/// single atomic load.
+static SINK_ACTIVE: AtomicBool = ATOMIC_BOOL_INIT;
+
+lazy_static! {
+    static ref EVENT_SINK: Mutex<Option<Arc<EventSink>>> = Mutex::new(None);
+}
+
+/// Installs `sink` as the process-wide event sink; see
+/// `Configuration::event_sink()`.
+pub fn set_event_sink(sink: Arc<EventSink>) {
+    *EVENT_SINK.lock().unwrap() = Some(sink);
+    SINK_ACTIVE.store(true, Ordering::SeqCst);
+}
+
+/// Uninstalls the event sink. Called when the pool that installed it
+/// terminates, so that the sink does not outlive the scheduler state
+/// it was observing.
+pub fn clear_event_sink() {
+    SINK_ACTIVE.store(false, Ordering::SeqCst);
+    *EVENT_SINK.lock().unwrap() = None;
+}
+
+#[inline]
+pub fn sink_active() -> bool {
+    SINK_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Forwards `event` to the installed sink, if any. A panic in the
+/// sink is halted here: the scheduler is in no position to propagate
+/// it, and must not be corrupted by it.
+#[cold]
+pub fn forward(event: &Event) {
+    let sink = EVENT_SINK.lock().unwrap().clone();
+    if let Some(sink) = sink {
+        let _ = unwind::halt_unwinding(|| sink(event));
+    }
+}
+
 macro_rules! log {
     ($event:expr) => {
-        if ::log::DUMP_LOGS { if *::log::LOG_ENV { println!("{:?}", $event); } }
+        {
+            if ::log::DUMP_LOGS { if *::log::LOG_ENV { println!("{:?}", $event); } }
+            if ::log::sink_active() { ::log::forward(&$event); }
+        }
     }
 }
 
@@ -0,0 +1,139 @@
+use latch::LatchProbe;
+use registry::{Registry, WorkerThread};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[cfg(test)]
+mod test;
+
+/// A barrier synchronizing a fixed number of pool tasks, for
+/// BSP-style algorithms (iterative solvers, cellular automata, ...)
+/// where every participant must finish one superstep before any may
+/// start the next. Each of the `n` participating tasks calls
+/// `wait()`; all of them proceed once the last has arrived, and the
+/// barrier then resets itself for the next superstep, like
+/// `std::sync::Barrier`.
+///
+/// Unlike `std::sync::Barrier`, a worker thread that arrives at the
+/// barrier does not block: it cooperatively executes other pool work
+/// until the remaining participants show up. This is what makes the
+/// barrier usable from inside the pool at all -- with as many
+/// participants as workers, every worker may be standing at the
+/// barrier, and if the participating tasks have not all been picked
+/// up yet, the waiting workers themselves must run them.
+///
+/// The flip side is the usual caveat of cooperative waits: arbitrary
+/// pool work may run on a participant's stack *inside* `wait()`, so
+/// a superstep must not rely on exclusive access to anything a
+/// stolen job might touch.
+///
+/// # One participant per worker
+///
+/// A waiting worker may steal *another participant* and run it on
+/// its own stack. For a single rendezvous that is exactly what makes
+/// the barrier safe -- see below -- but if participants loop over
+/// several supersteps, the stolen participant buries the waiting one:
+/// the worker cannot return to the buried task until the stolen one
+/// finishes, while the stolen task's next `wait()` needs the buried
+/// one to arrive. Iterative algorithms should therefore give each
+/// participant its own worker, e.g. by spawning one participant per
+/// worker index with `spawn_on()`; targeted jobs are never stolen,
+/// so no participant can end up beneath another.
+pub struct PoolBarrier {
+    registry: Arc<Registry>,
+
+    /// How many tasks participate in each superstep.
+    participants: usize,
+
+    /// How many participants have arrived in the current generation.
+    arrived: AtomicUsize,
+
+    /// Bumped by the last arrival of each generation; waiters probe
+    /// it to learn that their generation's barrier has opened.
+    generation: AtomicUsize,
+}
+
+/// Returned by `PoolBarrier::wait()`; exactly one participant per
+/// generation is the "leader", which BSP code typically uses to run
+/// a sequential step (convergence check, reduction) exactly once.
+pub struct BarrierWaitResult {
+    is_leader: bool,
+}
+
+impl PoolBarrier {
+    /// Creates a barrier for `participants` tasks, tied to the
+    /// current pool (the pool this is called from, or the global pool
+    /// otherwise). Only workers of that pool wait cooperatively;
+    /// other threads calling `wait()` simply wait.
+    pub fn new(participants: usize) -> PoolBarrier {
+        assert!(participants > 0,
+                "a PoolBarrier needs at least one participant");
+        PoolBarrier {
+            registry: Registry::current(),
+            participants: participants,
+            arrived: AtomicUsize::new(0),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
+    /// Arrives at the barrier, proceeding once all participants of
+    /// the current generation have. The last participant to arrive
+    /// releases the rest and is reported as the leader; the barrier
+    /// is then ready for the next superstep.
+    pub fn wait(&self) -> BarrierWaitResult {
+        let generation = self.generation.load(Ordering::SeqCst);
+        let arrived = self.arrived.fetch_add(1, Ordering::SeqCst) + 1;
+        if arrived == self.participants {
+            // Last one in: reset the count *before* opening the
+            // barrier, so that a released participant re-arriving
+            // for the next superstep finds a fresh count.
+            self.arrived.store(0, Ordering::SeqCst);
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            // Cooperative waiters that ran out of other work may
+            // have fallen asleep; wake the pool so they re-probe.
+            self.registry.tickle();
+            return BarrierWaitResult { is_leader: true };
+        }
+
+        unsafe {
+            let worker_thread = WorkerThread::current();
+            if !worker_thread.is_null() &&
+               (*worker_thread).registry().id() == self.registry.id() {
+                let probe = GenerationProbe {
+                    barrier: self,
+                    generation: generation,
+                };
+                (*worker_thread).wait_until(&probe);
+            } else {
+                while self.generation.load(Ordering::SeqCst) == generation {
+                    thread::yield_now();
+                }
+            }
+        }
+        BarrierWaitResult { is_leader: false }
+    }
+}
+
+impl BarrierWaitResult {
+    /// Returns true for exactly one participant per generation: the
+    /// one whose arrival opened the barrier.
+    pub fn is_leader(&self) -> bool {
+        self.is_leader
+    }
+}
+
+/// The probe a cooperatively waiting participant parks on: "set"
+/// once the generation it arrived in has been opened. Generations
+/// only ever advance, so once this reports true it keeps doing so,
+/// as the latch contract requires.
+struct GenerationProbe<'b> {
+    barrier: &'b PoolBarrier,
+    generation: usize,
+}
+
+impl<'b> LatchProbe for GenerationProbe<'b> {
+    fn probe(&self) -> bool {
+        self.barrier.generation.load(Ordering::SeqCst) != self.generation
+    }
+}
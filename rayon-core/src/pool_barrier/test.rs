@@ -0,0 +1,99 @@
+//! Tests for the cooperative pool barrier.
+
+use Configuration;
+use pool_barrier::PoolBarrier;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use thread_pool::ThreadPool;
+
+#[test]
+fn supersteps_stay_in_lockstep() {
+    const STEPS: usize = 3;
+    const TASKS: usize = 4;
+
+    // One participant per worker, pinned with `spawn_on()` as the
+    // module docs prescribe for iterative use.
+    let pool = Arc::new(ThreadPool::new(Configuration::new().num_threads(TASKS)).unwrap());
+    let barrier = Arc::new(pool.install(|| PoolBarrier::new(TASKS)));
+    let arrivals: Arc<Vec<_>> = Arc::new((0..STEPS).map(|_| AtomicUsize::new(0)).collect());
+    let leaders = Arc::new(AtomicUsize::new(0));
+    let violations = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+
+    for index in 0..TASKS {
+        let barrier = barrier.clone();
+        let arrivals = arrivals.clone();
+        let leaders = leaders.clone();
+        let violations = violations.clone();
+        let done = done.clone();
+        pool.spawn_on(index, move || {
+            for step in 0..STEPS {
+                arrivals[step].fetch_add(1, Ordering::SeqCst);
+                if barrier.wait().is_leader() {
+                    leaders.fetch_add(1, Ordering::SeqCst);
+                }
+                // The barrier has opened, so every participant must
+                // have finished this superstep by now.
+                if arrivals[step].load(Ordering::SeqCst) != TASKS {
+                    violations.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            done.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+
+    while done.load(Ordering::SeqCst) < TASKS {
+        thread::yield_now();
+    }
+    assert_eq!(violations.load(Ordering::SeqCst), 0);
+    // Exactly one leader per superstep.
+    assert_eq!(leaders.load(Ordering::SeqCst), STEPS);
+}
+
+#[test]
+fn waiting_worker_runs_the_missing_participant() {
+    // A single worker and two participants: the first task to arrive
+    // can only be released if its cooperative wait executes the
+    // second task, which is still sitting in the queue. A blocking
+    // barrier would deadlock here.
+    let pool = Arc::new(ThreadPool::new(Configuration::new().num_threads(1)).unwrap());
+    let barrier = Arc::new(pool.install(|| PoolBarrier::new(2)));
+    let done = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..2 {
+        let barrier = barrier.clone();
+        let done = done.clone();
+        pool.spawn_async(move || {
+            barrier.wait();
+            done.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+
+    while done.load(Ordering::SeqCst) < 2 {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn outside_thread_may_participate() {
+    // One participant is not a worker of the pool: it waits plainly
+    // while the worker-side participant waits cooperatively.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let barrier = Arc::new(pool.install(|| PoolBarrier::new(2)));
+    let done = Arc::new(AtomicUsize::new(0));
+
+    {
+        let barrier = barrier.clone();
+        let done = done.clone();
+        pool.spawn_async(move || {
+            barrier.wait();
+            done.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+
+    barrier.wait();
+    while done.load(Ordering::SeqCst) < 1 {
+        thread::yield_now();
+    }
+}
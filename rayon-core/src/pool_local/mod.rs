@@ -0,0 +1,62 @@
+use registry::{Registry, WorkerThread};
+use std::sync::Arc;
+
+#[cfg(test)]
+mod test;
+
+/// Per-worker scratch storage for a thread pool: one `T` per worker
+/// thread, indexed by the current worker's index. This is the "a `T`
+/// per thread" pattern (caches, scratch buffers, statistics) without
+/// `thread_local!` plumbing, and with a lifetime that is tied to the
+/// value rather than to the threads.
+///
+/// Each worker only ever sees its own slot, so the slots need not be
+/// `Sync`; wrap the `T` in a `Cell` or `RefCell` if the worker needs
+/// to mutate it.
+pub struct PoolLocal<T> {
+    registry: Arc<Registry>,
+    slots: Vec<T>,
+}
+
+impl<T: Send> PoolLocal<T> {
+    /// Creates one slot per worker thread of the current pool (the
+    /// pool this is called from, or the global pool otherwise),
+    /// initializing the slot for worker `i` with `init(i)`.
+    pub fn new<F>(mut init: F) -> PoolLocal<T>
+        where F: FnMut(usize) -> T
+    {
+        let registry = Registry::current();
+        PoolLocal {
+            slots: (0..registry.num_threads()).map(|i| init(i)).collect(),
+            registry: registry,
+        }
+    }
+
+    /// Returns the slot belonging to the current worker thread, or
+    /// `None` if the current thread is not a worker of the pool this
+    /// `PoolLocal` was created for.
+    pub fn get(&self) -> Option<&T> {
+        unsafe {
+            let worker_thread = WorkerThread::current();
+            if worker_thread.is_null() {
+                None
+            } else if (*worker_thread).registry().id() != self.registry.id() {
+                None
+            } else {
+                Some(&self.slots[(*worker_thread).index()])
+            }
+        }
+    }
+
+    /// Consumes the `PoolLocal`, returning the per-worker values
+    /// indexed by worker. Useful to combine per-worker partial
+    /// results once the parallel phase is over.
+    pub fn into_inner(self) -> Vec<T> {
+        self.slots
+    }
+}
+
+/// Safe because a slot is only ever handed out to the worker thread
+/// with the matching index (see `get()`), so no `&T` is shared
+/// between threads even though the `PoolLocal` itself is.
+unsafe impl<T: Send> Sync for PoolLocal<T> {}
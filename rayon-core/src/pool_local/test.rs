@@ -0,0 +1,38 @@
+use Configuration;
+use ThreadPool;
+use broadcast::broadcast;
+use std::cell::Cell;
+use std::sync::Arc;
+use super::PoolLocal;
+
+#[test]
+fn pool_local_per_worker() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+    pool.install(|| {
+        let local = PoolLocal::new(|i| Cell::new(i));
+
+        // every worker must see the slot initialized with its own index
+        broadcast(|i| {
+            let slot = local.get().unwrap();
+            assert_eq!(slot.get(), i);
+            slot.set(i * 10);
+        });
+
+        let values: Vec<usize> = local.into_inner().into_iter().map(|c| c.get()).collect();
+        assert_eq!(values, vec![0, 10, 20, 30]);
+    });
+}
+
+#[test]
+fn pool_local_off_pool_is_none() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let local = Arc::new(pool.install(|| PoolLocal::new(|i| i)));
+
+    // this thread is not a worker of the pool, so there is no slot
+    assert!(local.get().is_none());
+
+    // nor is a worker of some *other* pool
+    let other = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let local2 = local.clone();
+    assert!(other.install(move || local2.get().is_none()));
+}
@@ -0,0 +1,128 @@
+use latch::LatchProbe;
+use registry::{Registry, WorkerThread};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[cfg(test)]
+mod test;
+
+/// A counting semaphore for capping how many resource-heavy tasks of
+/// a pool run concurrently (large allocations, GPU transfers, ...)
+/// without shrinking the pool itself. `acquire()` returns an RAII
+/// permit; while all permits are taken, a worker thread that calls
+/// `acquire()` does not block -- it cooperatively executes other pool
+/// work until a permit frees up, so the pool keeps making progress on
+/// everything that is not subject to the cap.
+///
+/// Note the flip side of the cooperative wait: other jobs may run on
+/// the worker's stack *inside* the `acquire()` call. A job that
+/// already holds a permit must therefore not depend, directly or
+/// indirectly, on jobs that are themselves waiting to acquire, or the
+/// permits can deadlock just like any other semaphore cycle.
+pub struct PoolSemaphore {
+    registry: Arc<Registry>,
+    permits: AtomicUsize,
+}
+
+/// An acquired permit (see `PoolSemaphore::acquire()`). The permit is
+/// returned to the semaphore when this guard is dropped.
+pub struct SemaphorePermit<'s> {
+    semaphore: &'s PoolSemaphore,
+}
+
+impl PoolSemaphore {
+    /// Creates a semaphore with `permits` permits, tied to the
+    /// current pool (the pool this is called from, or the global pool
+    /// otherwise). Only workers of that pool wait cooperatively;
+    /// other threads calling `acquire()` simply wait.
+    pub fn new(permits: usize) -> PoolSemaphore {
+        assert!(permits > 0, "a PoolSemaphore needs at least one permit");
+        PoolSemaphore {
+            registry: Registry::current(),
+            permits: AtomicUsize::new(permits),
+        }
+    }
+
+    /// Takes a permit without waiting; returns `None` if all permits
+    /// are currently taken.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit> {
+        if self.try_take_permit() {
+            Some(SemaphorePermit { semaphore: self })
+        } else {
+            None
+        }
+    }
+
+    /// Takes a permit, waiting for one to free up if need be. On a
+    /// worker thread of the semaphore's pool the wait is cooperative:
+    /// the worker runs other pool work until a permit is available.
+    pub fn acquire(&self) -> SemaphorePermit {
+        unsafe {
+            let worker_thread = WorkerThread::current();
+            if !worker_thread.is_null() &&
+               (*worker_thread).registry().id() == self.registry.id() {
+                let probe = AcquireProbe {
+                    semaphore: self,
+                    acquired: Cell::new(false),
+                };
+                (*worker_thread).wait_until(&probe);
+                debug_assert!(probe.acquired.get());
+            } else {
+                while !self.try_take_permit() {
+                    thread::yield_now();
+                }
+            }
+        }
+        SemaphorePermit { semaphore: self }
+    }
+
+    fn try_take_permit(&self) -> bool {
+        let mut permits = self.permits.load(Ordering::SeqCst);
+        while permits > 0 {
+            match self.permits.compare_exchange(permits,
+                                                permits - 1,
+                                                Ordering::SeqCst,
+                                                Ordering::SeqCst) {
+                Ok(_) => return true,
+                Err(p) => permits = p,
+            }
+        }
+        false
+    }
+
+    fn release_permit(&self) {
+        self.permits.fetch_add(1, Ordering::SeqCst);
+        // A cooperative waiter that ran out of other work may have
+        // fallen asleep; wake the pool so it re-probes the permit
+        // count.
+        self.registry.tickle();
+    }
+}
+
+impl<'s> Drop for SemaphorePermit<'s> {
+    fn drop(&mut self) {
+        self.semaphore.release_permit();
+    }
+}
+
+/// The probe a cooperatively waiting worker parks on: "set" once it
+/// has managed to take a permit. Unlike a latch this probe has a side
+/// effect -- the successful attempt *is* the acquisition -- but it
+/// upholds the latch contract that matters: once it reports true, it
+/// keeps doing so, and the taken permit is remembered rather than
+/// leaked.
+struct AcquireProbe<'s> {
+    semaphore: &'s PoolSemaphore,
+    acquired: Cell<bool>,
+}
+
+impl<'s> LatchProbe for AcquireProbe<'s> {
+    fn probe(&self) -> bool {
+        if !self.acquired.get() && self.semaphore.try_take_permit() {
+            self.acquired.set(true);
+        }
+        self.acquired.get()
+    }
+}
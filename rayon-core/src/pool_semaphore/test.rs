@@ -0,0 +1,88 @@
+//! Tests for the cooperative pool semaphore.
+
+use Configuration;
+use pool_semaphore::PoolSemaphore;
+use scope::scope;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use thread_pool::ThreadPool;
+
+#[test]
+fn permit_cap_is_never_exceeded() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+    pool.install(|| {
+        let semaphore = PoolSemaphore::new(2);
+        let active = AtomicUsize::new(0);
+        let max_active = AtomicUsize::new(0);
+        scope(|s| {
+            for _ in 0..20 {
+                s.spawn(|_| {
+                    let _permit = semaphore.acquire();
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(1));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        let max_active = max_active.load(Ordering::SeqCst);
+        assert!(max_active <= 2,
+                "{} permit holders ran concurrently",
+                max_active);
+        assert_eq!(active.load(Ordering::SeqCst), 0);
+    });
+}
+
+#[test]
+fn try_acquire_respects_the_cap() {
+    let semaphore = PoolSemaphore::new(1);
+    let permit = semaphore.try_acquire();
+    assert!(permit.is_some());
+    assert!(semaphore.try_acquire().is_none());
+    drop(permit);
+    assert!(semaphore.try_acquire().is_some());
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn exhausted_worker_runs_other_work() {
+    // A single worker, and the only permit is held outside the pool:
+    // job A's `acquire()` can make progress only by cooperatively
+    // executing job B while it waits, and it gets its permit once the
+    // outside holder lets go.
+    let pool = Arc::new(ThreadPool::new(Configuration::new().num_threads(1)).unwrap());
+    let semaphore = Arc::new(pool.install(|| PoolSemaphore::new(1)));
+    let b_ran = Arc::new(AtomicUsize::new(0));
+    let a_done = Arc::new(AtomicUsize::new(0));
+
+    let held = semaphore.acquire();
+
+    {
+        let semaphore = semaphore.clone();
+        let a_done = a_done.clone();
+        pool.spawn_async(move || {
+            let _permit = semaphore.acquire();
+            a_done.store(1, Ordering::SeqCst);
+        });
+    }
+    {
+        let b_ran = b_ran.clone();
+        pool.spawn_async(move || {
+            b_ran.store(1, Ordering::SeqCst);
+        });
+    }
+
+    // B finishing while A occupies the only worker proves the
+    // cooperative wait.
+    while b_ran.load(Ordering::SeqCst) == 0 {
+        thread::yield_now();
+    }
+    assert_eq!(a_done.load(Ordering::SeqCst), 0);
+
+    drop(held);
+    while a_done.load(Ordering::SeqCst) == 0 {
+        thread::yield_now();
+    }
+}
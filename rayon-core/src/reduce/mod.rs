@@ -0,0 +1,97 @@
+use join::join_context;
+use registry;
+use std::ops::Range;
+
+#[cfg(test)]
+mod test;
+
+/// Reduces the indices of `range` in parallel: conceptually computes
+/// `map(i)` for every `i` in the range and combines the results with
+/// `reduce`, starting from `identity()`. The range is recursively
+/// split with `join()` until pieces are at most `min_chunk` indices
+/// long; each such leaf is then folded serially. This gives a focused
+/// map-reduce over an index range without hand-rolling the recursion.
+///
+/// `min_chunk` bounds the splitting overhead: a larger value means
+/// fewer, coarser tasks. A value of zero means "use the pool-wide
+/// default" (see `Configuration::min_split_len()`). An empty range
+/// returns `identity()`.
+///
+/// Splitting is also *adaptive*: each call starts with a split budget
+/// of the pool's thread count, halved at every level, and a subrange
+/// whose budget runs out is folded serially even if it is larger than
+/// `min_chunk`. Whenever a subrange is stolen -- proof that an idle
+/// worker exists -- its budget is renewed (see
+/// `FnContext::migrated()`). So on a busy or small pool the number of
+/// tasks stays proportional to the number of workers rather than to
+/// `range.len() / min_chunk`, while steals still open up as much
+/// splitting as there are threads to serve it.
+///
+/// # Associativity
+///
+/// How the range is partitioned depends on `min_chunk` and on which
+/// jobs get stolen, so `reduce` must be **associative** and
+/// `identity()` must be a left and right identity for it -- otherwise
+/// the result is nondeterministic. `reduce` need not be commutative:
+/// results are always combined in range order.
+///
+/// # Panics
+///
+/// If any of the closures panics, that panic is propagated to the
+/// caller (via `join()`); results from other pieces are dropped.
+pub fn reduce_range<T, ID, MAP, REDUCE>(range: Range<usize>,
+                                        min_chunk: usize,
+                                        identity: ID,
+                                        map: MAP,
+                                        reduce: REDUCE)
+                                        -> T
+    where T: Send,
+          ID: Fn() -> T + Sync,
+          MAP: Fn(usize) -> T + Sync,
+          REDUCE: Fn(T, T) -> T + Sync
+{
+    let min_chunk = if min_chunk == 0 {
+        registry::current_min_split_len()
+    } else {
+        min_chunk
+    };
+    let splits = ::current_num_threads();
+    reduce_range_helper(range, min_chunk, splits, &identity, &map, &reduce)
+}
+
+fn reduce_range_helper<T, ID, MAP, REDUCE>(range: Range<usize>,
+                                           min_chunk: usize,
+                                           splits: usize,
+                                           identity: &ID,
+                                           map: &MAP,
+                                           reduce: &REDUCE)
+                                           -> T
+    where T: Send,
+          ID: Fn() -> T + Sync,
+          MAP: Fn(usize) -> T + Sync,
+          REDUCE: Fn(T, T) -> T + Sync
+{
+    if range.len() <= min_chunk || splits == 0 {
+        range.fold(identity(), |acc, i| reduce(acc, map(i)))
+    } else {
+        let mid = range.start + range.len() / 2;
+        let (left, right) =
+            join_context(|_| {
+                             reduce_range_helper(range.start..mid, min_chunk, splits / 2,
+                                                 identity, map, reduce)
+                         },
+                         |context| {
+                             // A migrated right half proves an idle worker
+                             // took it: renew its split budget so it can
+                             // fan out over the takers in turn.
+                             let splits = if context.migrated() {
+                                 ::current_num_threads()
+                             } else {
+                                 splits / 2
+                             };
+                             reduce_range_helper(mid..range.end, min_chunk, splits,
+                                                 identity, map, reduce)
+                         });
+        reduce(left, right)
+    }
+}
@@ -0,0 +1,77 @@
+//! Tests for the parallel range reduction.
+
+use reduce::*;
+use unwind;
+
+#[test]
+fn sum_of_range() {
+    let n = 10 * 1024;
+    let sum = reduce_range(0..n, 64, || 0, |i| i, |a, b| a + b);
+    assert_eq!(sum, n * (n - 1) / 2);
+}
+
+#[test]
+fn empty_range_yields_identity() {
+    let sum = reduce_range(10..10, 64, || 42, |i| i, |a, b| a + b);
+    assert_eq!(sum, 42);
+}
+
+#[test]
+fn zero_min_chunk_uses_pool_default() {
+    // The global pool's default minimum split length is one, so only
+    // the adaptive split budget bounds the recursion here.
+    let sum = reduce_range(0..100, 0, || 0, |i| i, |a, b| a + b);
+    assert_eq!(sum, 100 * 99 / 2);
+}
+
+#[test]
+fn adaptive_splitting_bounds_task_count() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use {Configuration, ThreadPool};
+
+    // `identity()` runs once per leaf task. On a single-threaded pool
+    // nothing is ever migrated, so the split budget (one, here) is
+    // never renewed and the recursion collapses to a couple of leaves
+    // instead of one per index.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let leaves = AtomicUsize::new(0);
+    let n = 10 * 1024;
+    let sum = pool.install(|| {
+        reduce_range(0..n,
+                     1,
+                     || {
+                         leaves.fetch_add(1, Ordering::SeqCst);
+                         0
+                     },
+                     |i| i,
+                     |a, b| a + b)
+    });
+    assert_eq!(sum, n * (n - 1) / 2);
+    assert!(leaves.load(Ordering::SeqCst) <= 4,
+            "expected a handful of leaves, got {}",
+            leaves.load(Ordering::SeqCst));
+}
+
+#[test]
+fn associative_but_not_commutative() {
+    // String concatenation is associative but not commutative: the
+    // result must come out in range order regardless of how the range
+    // was split and stolen.
+    let cat = reduce_range(0..100,
+                           3,
+                           String::new,
+                           |i| i.to_string(),
+                           |a, b| a + &b);
+    let expected: String = (0..100).map(|i| i.to_string()).collect();
+    assert_eq!(cat, expected);
+}
+
+#[test]
+fn panic_in_map_propagates() {
+    let result = unwind::halt_unwinding(|| {
+        reduce_range(0..1024, 16, || 0, |i| if i == 512 { panic!("boom") } else { i }, |a, b| {
+            a + b
+        })
+    });
+    assert!(result.is_err(), "panic in map should propagate");
+}
@@ -1,17 +1,29 @@
-use ::{Configuration, ExitHandler, PanicHandler, StartHandler};
+use ::{Configuration, ExitHandler, InjectPriority, JobProfiler, PanicHandler, SpawnHandler,
+       StartHandler};
 use deque;
 use deque::{Worker, Stealer, Stolen};
 use job::{JobRef, StackJob};
-use latch::{LatchProbe, Latch, CountLatch, LockLatch};
+use latch::{AllLatches, AnyLatches, LatchProbe, Latch, CountLatch, LockLatch};
 #[allow(unused_imports)]
 use log::Event::*;
 use rand::{self, Rng};
-use sleep::Sleep;
+#[cfg(feature = "unstable")]
+use rand::SeedableRng;
+use sleep::{self, Sleep};
 use std::any::Any;
 use std::error::Error;
+use std::io;
 use std::cell::{Cell, UnsafeCell};
-use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::cmp;
+use std::collections::BinaryHeap;
+use std::io::prelude::*;
+use std::io::stderr;
+use std::sync::{Arc, Condvar, Mutex, Once, ONCE_INIT};
+#[cfg(feature = "unstable")]
+use std::sync::Weak;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_BOOL_INIT};
 use std::thread;
+use std::time::{Duration, Instant};
 use std::mem;
 use std::fmt;
 use std::u32;
@@ -19,20 +31,209 @@ use std::usize;
 use unwind;
 use util::leak;
 
-/// Error if the gloal thread pool is initialized multiple times.
-#[derive(Debug,PartialEq)]
-struct GlobalPoolAlreadyInitialized;
+/// Error describing why building a thread pool failed. Inspect
+/// `kind()` to distinguish the causes programmatically, e.g. to retry
+/// with a smaller pool after an `IOError`, or to ignore a
+/// `GlobalPoolAlreadyInitialized` from a racing initialization.
+#[derive(Debug)]
+pub struct ThreadPoolBuildError {
+    kind: ThreadPoolBuildErrorKind,
+}
+
+/// The underlying cause of a `ThreadPoolBuildError`.
+#[derive(Debug)]
+pub enum ThreadPoolBuildErrorKind {
+    /// The OS (or the configured spawn handler, see
+    /// `Configuration::spawn_handler()`) failed to start a thread.
+    IOError(io::Error),
+
+    /// The global thread pool was already initialized, so the given
+    /// configuration cannot take effect.
+    GlobalPoolAlreadyInitialized,
+
+    /// Use of the global thread pool has been forbidden (see
+    /// `forbid_global_pool()`); create an explicit `ThreadPool`
+    /// instead.
+    GlobalPoolForbidden,
+
+    /// The resolved number of worker threads is not usable.
+    InvalidNumThreads(usize),
+
+    /// The dedicated blocking pool (see `initialize_blocking_pool()`)
+    /// was already initialized, so the given configuration cannot
+    /// take effect.
+    BlockingPoolAlreadyInitialized,
+}
+
+impl ThreadPoolBuildError {
+    fn new(kind: ThreadPoolBuildErrorKind) -> ThreadPoolBuildError {
+        ThreadPoolBuildError { kind: kind }
+    }
+
+    /// Returns the underlying cause of this error.
+    pub fn kind(&self) -> &ThreadPoolBuildErrorKind {
+        &self.kind
+    }
+}
+
+impl From<io::Error> for ThreadPoolBuildError {
+    fn from(err: io::Error) -> ThreadPoolBuildError {
+        ThreadPoolBuildError::new(ThreadPoolBuildErrorKind::IOError(err))
+    }
+}
 
-impl fmt::Display for GlobalPoolAlreadyInitialized {
+impl fmt::Display for ThreadPoolBuildError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.description())
+        match self.kind {
+            ThreadPoolBuildErrorKind::IOError(ref err) => err.fmt(f),
+            ThreadPoolBuildErrorKind::GlobalPoolAlreadyInitialized |
+            ThreadPoolBuildErrorKind::GlobalPoolForbidden |
+            ThreadPoolBuildErrorKind::InvalidNumThreads(_) |
+            ThreadPoolBuildErrorKind::BlockingPoolAlreadyInitialized => {
+                f.write_str(self.description())
+            }
+        }
     }
 }
 
-impl Error for GlobalPoolAlreadyInitialized {
+impl Error for ThreadPoolBuildError {
     fn description(&self) -> &str {
-        "The global thread pool has already been initialized."
+        match self.kind {
+            ThreadPoolBuildErrorKind::IOError(_) => "The thread pool could not spawn a thread.",
+            ThreadPoolBuildErrorKind::GlobalPoolAlreadyInitialized => {
+                "The global thread pool has already been initialized."
+            }
+            ThreadPoolBuildErrorKind::GlobalPoolForbidden => {
+                "Use of the global thread pool has been forbidden; \
+                 create an explicit ThreadPool instead."
+            }
+            ThreadPoolBuildErrorKind::InvalidNumThreads(_) => {
+                "The number of worker threads is invalid."
+            }
+            ThreadPoolBuildErrorKind::BlockingPoolAlreadyInitialized => {
+                "The blocking thread pool has already been initialized."
+            }
+        }
     }
+
+    fn cause(&self) -> Option<&Error> {
+        match self.kind {
+            ThreadPoolBuildErrorKind::IOError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Default minimum split length for the fork-join helpers: split all
+/// the way down to single elements unless the pool (or the call site)
+/// says otherwise.
+const DEFAULT_MIN_SPLIT_LEN: usize = 1;
+
+/// Timing record handed to the per-job profiler (see
+/// `Configuration::job_profiler()`): which worker executed a job and
+/// how long it ran.
+#[derive(Clone, Copy, Debug)]
+pub struct JobTiming {
+    /// Index of the worker that executed the job.
+    pub worker: usize,
+    /// Wall-clock duration of the job's execution.
+    pub duration: Duration,
+}
+
+/// One recorded scheduling decision: worker `thief` took a job from
+/// worker `victim`'s deque. See
+/// `Configuration::record_steal_trace()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StealRecord {
+    /// Index of the worker that took the job.
+    pub thief: usize,
+    /// Index of the worker the job was taken from.
+    pub victim: usize,
+}
+
+/// A trace of the scheduling decisions a pool made during some
+/// execution, recorded under `Configuration::record_steal_trace()`
+/// and fed back via `Configuration::replay_steal_trace()` to steer a
+/// later run toward the same schedule. The trace currently covers
+/// steal decisions only -- which worker stole from which victim, in
+/// what order -- which is the main source of run-to-run
+/// nondeterminism in the scheduler.
+#[derive(Clone, Debug, Default)]
+pub struct SchedulerTrace {
+    records: Vec<StealRecord>,
+}
+
+#[cfg(feature = "unstable")]
+impl SchedulerTrace {
+    /// The recorded steals, in the order they were committed.
+    pub fn records(&self) -> &[StealRecord] {
+        &self.records
+    }
+}
+
+/// A point-in-time view of one pool's scheduler state, produced by
+/// `ThreadPool::snapshot()`. This is the dump behind a "/debug/pool"
+/// endpoint or a log-on-signal handler: each field is read atomically
+/// without stopping the pool, so the values are individually accurate
+/// at the instant they were read but mutually racy -- coherent enough
+/// for diagnosing a stuck pool, not an exact transcript.
+#[cfg(feature = "unstable")]
+#[derive(Clone, Debug)]
+pub struct RegistrySnapshot {
+    /// Worker slots the pool was created with.
+    pub num_threads: usize,
+
+    /// Workers actually started so far; less than `num_threads` for a
+    /// lazy or resized pool.
+    pub num_spawned: usize,
+
+    /// Started workers currently in the deep condvar sleep.
+    pub num_sleeping: usize,
+
+    /// Started workers not currently asleep. As in `is_saturated()`,
+    /// an awake worker is *presumed* busy -- one spinning in search
+    /// of work counts too.
+    pub threads_at_work: usize,
+
+    /// Jobs sitting in the shared injected queue, not yet picked up
+    /// by any worker.
+    pub injected_jobs: usize,
+
+    /// Advisory count of jobs anywhere in the pool (injected or in
+    /// worker deques) that have not finished executing.
+    pub pending_jobs: usize,
+
+    /// True once the pool has begun terminating.
+    pub terminating: bool,
+
+    /// Per-worker queue statistics, indexed by worker.
+    pub workers: Vec<WorkerSnapshot>,
+}
+
+/// One worker's slice of a `RegistrySnapshot`.
+#[cfg(feature = "unstable")]
+#[derive(Clone, Debug)]
+pub struct WorkerSnapshot {
+    /// Index of this worker.
+    pub index: usize,
+
+    /// Approximate number of jobs in the worker's deque -- the same
+    /// hint the steal heuristics use, so it may drift slightly from
+    /// the true length.
+    pub queued: usize,
+
+    /// High-water mark of `queued` since the pool started (or since
+    /// `ThreadPool::reset_max_deque_depths()`).
+    pub max_queued: usize,
+
+    /// Consecutive panicking jobs this worker has just executed (see
+    /// `Configuration::max_consecutive_panics()`); reset by any job
+    /// that completes cleanly.
+    pub consecutive_panics: usize,
+
+    /// Whether the worker's thread has started up and entered its
+    /// main loop.
+    pub started: bool,
 }
 
 pub struct Registry {
@@ -40,9 +241,193 @@ pub struct Registry {
     state: Mutex<RegistryState>,
     sleep: Sleep,
     job_uninjector: Stealer<JobRef>,
-    panic_handler: Option<Box<PanicHandler>>,
-    start_handler: Option<Box<StartHandler>>,
-    exit_handler: Option<Box<ExitHandler>>,
+    panic_handler: Option<Arc<PanicHandler>>,
+    start_handler: Option<Arc<StartHandler>>,
+    exit_handler: Option<Arc<ExitHandler>>,
+
+    /// Closure invoked with a `JobTiming` after each executed job
+    /// (see `Configuration::job_profiler()`).
+    job_profiler: Option<Arc<JobProfiler>>,
+
+    /// Number of threads currently blocked waiting for work they
+    /// injected into this registry to complete. Only used as a
+    /// diagnostic by the deadlock watchdog (see
+    /// `Configuration::deadlock_detection()`).
+    blocked_waiters: AtomicUsize,
+
+    /// When `utilization()` was last called (or the registry
+    /// created); busy-fractions are reported relative to this point.
+    utilization_reset: Mutex<Instant>,
+
+    /// Number of jobs that have been injected but not yet picked up
+    /// by a worker; used by `wait_until_idle()` and to enforce
+    /// `max_injected`. Kept outside the `state` mutex so that the
+    /// per-job decrement on the uninject path does not contend on
+    /// that lock; see `pop_injected_job()` for how the backpressure
+    /// condvar avoids lost wakeups regardless.
+    injected_jobs: AtomicUsize,
+
+    /// Bound on `injected_jobs`, if any. When the bound is reached,
+    /// `inject()` blocks on `inject_space` until workers have
+    /// drained part of the backlog.
+    max_injected: Option<usize>,
+
+    /// Signaled (under the `state` lock) whenever the injected queue
+    /// shrinks; only used when `max_injected` is set.
+    inject_space: Condvar,
+
+    /// If true, injected jobs carry a sequence number assigned at
+    /// submission and workers pull them in strictly that order (see
+    /// `Configuration::strict_inject_order()`).
+    strict_inject_order: bool,
+
+    /// Next sequence number to hand out to an injected job; only
+    /// used when `strict_inject_order` is set. Assigned *before* the
+    /// `state` lock is taken, so that a submitter preempted between
+    /// assignment and insertion still holds its place in line.
+    inject_seq: AtomicUsize,
+
+    /// Number of jobs sitting in the prioritized injected heap (see
+    /// `inject_prioritized()`). Kept outside the `state` mutex so
+    /// that workers can skip the lock entirely on the common path
+    /// where no prioritized work exists.
+    #[cfg(feature = "unstable")]
+    prioritized_len: AtomicUsize,
+
+    /// Workers that have not been spawned yet (see
+    /// `Configuration::lazy_threads()`), in reverse index order so
+    /// that `pop()` yields the next index to start. Empty for eager
+    /// pools.
+    unspawned: Mutex<Vec<UnspawnedWorker>>,
+
+    /// Number of worker threads actually started so far. Equal to
+    /// `num_threads()` unless the pool is lazy or has been resized
+    /// (see `resize_pool()`).
+    num_spawned: AtomicUsize,
+
+    /// True if building this registry installed the process-wide
+    /// event sink (see `Configuration::event_sink()`); the sink is
+    /// uninstalled again when the registry terminates.
+    owns_event_sink: bool,
+
+    /// Stack size for worker threads, remembered so that a worker
+    /// respawned after retirement (see `resize_pool()`) gets the same
+    /// stack as one spawned at pool creation.
+    stack_size: Option<usize>,
+
+    /// If true, a thread blocked in `ThreadPool::install()` helps
+    /// drain the injected queue instead of sitting idle (see
+    /// `Configuration::cooperative_install()`).
+    cooperative_install: bool,
+
+    /// If true, no worker threads are ever spawned; the pool's jobs
+    /// run on whichever thread drives the pool (see
+    /// `Configuration::caller_driven()` and
+    /// `ThreadPool::run_pending()`).
+    caller_driven: bool,
+
+    /// Piece length below which the fork-join helpers run serially
+    /// when not told otherwise (see
+    /// `Configuration::min_split_len()`). Always at least one.
+    min_split_len: usize,
+
+    /// Join nesting depth on one worker's stack beyond which `join()`
+    /// stops splitting and runs serially (see
+    /// `Configuration::max_recursion_depth()`); `None` means
+    /// unbounded.
+    #[cfg(feature = "unstable")]
+    max_recursion_depth: Option<usize>,
+
+    /// Number of consecutive captured panics on one worker after
+    /// which the pool aborts the process (see
+    /// `Configuration::max_consecutive_panics()`); `None` means
+    /// never.
+    max_consecutive_panics: Option<usize>,
+
+    /// Maximum number of jobs a worker keeps in its local deque
+    /// before spawns spill to the injected queue (see
+    /// `Configuration::local_queue_cap()`); `usize::MAX` when
+    /// unbounded.
+    local_queue_cap: usize,
+
+    /// If true, this pool's workers abort the process on a user panic
+    /// instead of capturing it (see `Configuration::panic_abort()`).
+    panic_abort: bool,
+
+    /// In what order an idle worker consults the injected queue
+    /// relative to the work already inside the pool (see
+    /// `Configuration::inject_priority()`).
+    inject_priority: InjectPriority,
+
+    /// If true, the drop glue of aborted jobs runs on the shared
+    /// cleanup thread instead of inline on a worker (see
+    /// `Configuration::offload_aborted_drops()`).
+    offload_aborted_drops: bool,
+
+    /// If true, a successful steal also moves up to half of the
+    /// victim's remaining deque into the thief's (see
+    /// `Configuration::steal_batching()`).
+    steal_batching: bool,
+
+    /// Upper bound on how many sleepers a single injected batch may
+    /// wake (see `Configuration::wake_batch_limit()`); `usize::MAX`
+    /// when unbounded.
+    wake_batch_limit: usize,
+
+    /// If true, idle workers release the buffers of their emptied
+    /// side queues (see `Configuration::shrink_idle_deques()`).
+    shrink_idle_deques: bool,
+
+    /// Seed for the scheduling fuzzer, if any (see
+    /// `Configuration::scheduler_fuzz()`).
+    #[cfg(feature = "unstable")]
+    fuzz_seed: Option<u64>,
+
+    /// Sink for the scheduling decisions recorded so far, if
+    /// recording is enabled (see
+    /// `Configuration::record_steal_trace()`). The mutex is only
+    /// ever touched when recording.
+    #[cfg(feature = "unstable")]
+    steal_trace: Option<Mutex<Vec<StealRecord>>>,
+
+    /// A previously recorded trace whose steal decisions the workers
+    /// replay, if any (see `Configuration::replay_steal_trace()`).
+    #[cfg(feature = "unstable")]
+    replay_trace: Option<SchedulerTrace>,
+
+    /// Per-worker victim preference orders, computed once at pool
+    /// construction from `Configuration::steal_order()` and already
+    /// sanitized (see `sanitize_steal_order()`). `None` keeps the
+    /// default random victim rotation.
+    #[cfg(feature = "unstable")]
+    steal_orders: Option<Vec<Vec<usize>>>,
+
+    /// Number of tracked detached tasks (see `spawn_tracked()`) that
+    /// have been spawned but have not yet finished.
+    #[cfg(feature = "unstable")]
+    detached_tasks: AtomicUsize,
+
+    /// Number of stealable jobs believed to be queued somewhere in
+    /// the pool: incremented when a job is pushed onto a worker deque
+    /// or injected, decremented when one is taken back out. Sticky
+    /// and targeted jobs are excluded, since only one specific worker
+    /// could ever take those. This is an advisory counter that lets
+    /// an idle worker decide, without taking any lock, whether to
+    /// keep spinning instead of going to sleep; the sleep protocol
+    /// itself never depends on it (see `has_pending_jobs()`).
+    pending_jobs: AtomicUsize,
+
+    /// Exact job accounting used to validate the approximate counters
+    /// above in debug builds; absent from release builds, which pay
+    /// nothing for it. See `debug_note_jobs_recorded()`.
+    #[cfg(debug_assertions)]
+    job_accounting: JobAccounting,
+
+    /// Custom thread creation closure, if any (see
+    /// `Configuration::spawn_handler()`); kept in the registry so
+    /// that workers started later -- lazy pools, `resize_pool()` --
+    /// go through the same hook as those started at pool creation.
+    spawn_handler: Option<Arc<Mutex<Box<SpawnHandler>>>>,
 
     // When this latch reaches 0, it means that all work on this
     // registry must be complete. This is ensured in the following ways:
@@ -60,8 +445,207 @@ pub struct Registry {
     terminate_latch: CountLatch,
 }
 
+/// Exact counterpart of the approximate `pending_jobs` and
+/// `approx_len` counters, maintained only in debug builds. Unlike
+/// those, the totals here never saturate, and `recorded` is always
+/// bumped *before* the job in question becomes visible to other
+/// threads; "jobs taken never outnumber jobs recorded" is therefore a
+/// hard invariant, and violating it means a job was double-executed
+/// or never counted. Like `pending_jobs`, sticky and targeted jobs
+/// are excluded.
+#[cfg(debug_assertions)]
+struct JobAccounting {
+    recorded: AtomicUsize,
+    taken: AtomicUsize,
+}
+
+/// Everything needed to start one not-yet-spawned worker of a lazy
+/// pool: its deque halves plus the thread parameters that would
+/// otherwise have been consumed from the `Configuration` at pool
+/// creation.
+struct UnspawnedWorker {
+    index: usize,
+    worker: Worker<JobRef>,
+    priority_worker: Worker<JobRef>,
+    name: Option<String>,
+    stack_size: Option<usize>,
+}
+
+/// Everything needed to start one worker thread, handed to a custom
+/// spawn handler (see `Configuration::spawn_handler()`). The handler
+/// inspects the thread parameters, arranges for a thread to exist --
+/// however the host environment creates threads -- and has that
+/// thread call `run()`.
+pub struct ThreadBuilder {
+    name: Option<String>,
+    stack_size: Option<usize>,
+    index: usize,
+    worker: Worker<JobRef>,
+    priority_worker: Worker<JobRef>,
+    registry: Arc<Registry>,
+}
+
+impl ThreadBuilder {
+    /// Returns the index of this worker within its pool.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the name requested for this worker thread, if any (see
+    /// `Configuration::thread_name()`).
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|s| &s[..])
+    }
+
+    /// Returns the stack size requested for this worker thread, if
+    /// any (see `Configuration::stack_size()`).
+    pub fn stack_size(&self) -> Option<usize> {
+        self.stack_size
+    }
+
+    /// Executes the worker's main loop on the calling thread. Does
+    /// not return until the pool terminates (or, under
+    /// `resize_pool()`, this worker is retired).
+    pub fn run(self) {
+        unsafe { main_loop(self.worker, self.priority_worker, self.registry, self.index) }
+    }
+}
+
+impl fmt::Debug for ThreadBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThreadBuilder")
+         .field("index", &self.index)
+         .field("name", &self.name)
+         .field("stack_size", &self.stack_size)
+         .finish()
+    }
+}
+
+/// Clears the dormant flags set by one `with_max_threads()` call,
+/// even if `op` unwinds, and wakes any sleepers so that the restored
+/// workers resume stealing promptly.
+struct DormantGuard<'a> {
+    registry: &'a Registry,
+    marked: Vec<usize>,
+}
+
+impl<'a> Drop for DormantGuard<'a> {
+    fn drop(&mut self) {
+        for &index in &self.marked {
+            self.registry.thread_infos[index].dormant.store(false, Ordering::SeqCst);
+        }
+        self.registry.sleep.tickle(usize::MAX);
+    }
+}
+
+/// Probe used by `Registry::wait_detached()`: "set" while no tracked
+/// detached task is pending. Like the pool semaphore's probe, this is
+/// not a true latch -- the condition can become false again if new
+/// tasks are tracked-spawned -- but `wait_until()` only requires that
+/// a `true` observation is a safe moment to stop waiting, which it
+/// is: the caller was momentarily drained.
+#[cfg(feature = "unstable")]
+struct DetachedDrained<'r> {
+    registry: &'r Registry,
+}
+
+#[cfg(feature = "unstable")]
+impl<'r> LatchProbe for DetachedDrained<'r> {
+    fn probe(&self) -> bool {
+        self.registry.pending_detached() == 0
+    }
+}
+
 struct RegistryState {
     job_injector: Worker<JobRef>,
+
+    /// Injected jobs of a strict-FIFO pool, held out of
+    /// `job_injector` and ordered by sequence number instead; unused
+    /// otherwise (see `Configuration::strict_inject_order()`).
+    sequenced_jobs: BinaryHeap<SequencedJob>,
+
+    /// Sequence number of the next job workers may take from
+    /// `sequenced_jobs`.
+    next_inject_run: usize,
+
+    /// Injected jobs carrying a user-assigned priority (see
+    /// `inject_prioritized()`), held apart from the FIFO
+    /// `job_injector` and drained highest-priority first.
+    #[cfg(feature = "unstable")]
+    prioritized_jobs: BinaryHeap<PrioritizedJob>,
+
+    /// Submission counter for `prioritized_jobs`, used to break
+    /// priority ties in favor of the oldest submission.
+    #[cfg(feature = "unstable")]
+    next_prioritized_seq: usize,
+}
+
+/// An injected job tagged with the sequence number assigned at its
+/// submission, for strict-FIFO pools (see
+/// `Configuration::strict_inject_order()`). The ordering is inverted
+/// so that a `BinaryHeap` yields the lowest-numbered -- oldest --
+/// job first.
+struct SequencedJob {
+    seq: usize,
+    job: JobRef,
+}
+
+impl PartialEq for SequencedJob {
+    fn eq(&self, other: &SequencedJob) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl Eq for SequencedJob {}
+
+impl PartialOrd for SequencedJob {
+    fn partial_cmp(&self, other: &SequencedJob) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SequencedJob {
+    fn cmp(&self, other: &SequencedJob) -> cmp::Ordering {
+        other.seq.cmp(&self.seq)
+    }
+}
+
+/// An injected job carrying the priority its submitter assigned (see
+/// `inject_prioritized()`). Higher priorities come out of the
+/// `BinaryHeap` first; among equal priorities the sequence number
+/// breaks the tie in favor of the oldest submission, so that equal
+/// priorities degrade to FIFO rather than to an arbitrary order.
+#[cfg(feature = "unstable")]
+struct PrioritizedJob {
+    priority: i32,
+    seq: usize,
+    job: JobRef,
+}
+
+#[cfg(feature = "unstable")]
+impl PartialEq for PrioritizedJob {
+    fn eq(&self, other: &PrioritizedJob) -> bool {
+        self.priority == other.priority && self.seq == other.seq
+    }
+}
+
+#[cfg(feature = "unstable")]
+impl Eq for PrioritizedJob {}
+
+#[cfg(feature = "unstable")]
+impl PartialOrd for PrioritizedJob {
+    fn partial_cmp(&self, other: &PrioritizedJob) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "unstable")]
+impl Ord for PrioritizedJob {
+    fn cmp(&self, other: &PrioritizedJob) -> cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then(other.seq.cmp(&self.seq))
+    }
 }
 
 /// ////////////////////////////////////////////////////////////////////////
@@ -69,18 +653,98 @@ struct RegistryState {
 
 static mut THE_REGISTRY: Option<&'static Arc<Registry>> = None;
 static THE_REGISTRY_SET: Once = ONCE_INIT;
+static THE_REGISTRY_FORBIDDEN: AtomicBool = ATOMIC_BOOL_INIT;
+
+/// Every live registry in the process, for cross-pool diagnostics
+/// (see `oversubscription_factor()`). The references are weak so
+/// that the list never keeps a dropped pool's registry alive; dead
+/// entries are pruned whenever a registry is added.
+#[cfg(feature = "unstable")]
+static LIVE_REGISTRIES: Mutex<Vec<Weak<Registry>>> = Mutex::new(Vec::new());
+
+/// Records a newly created registry in `LIVE_REGISTRIES`.
+#[cfg(feature = "unstable")]
+fn note_registry_created(registry: &Arc<Registry>) {
+    let mut live = LIVE_REGISTRIES.lock().unwrap();
+    live.retain(|weak| weak.upgrade().is_some());
+    live.push(Arc::downgrade(registry));
+}
+
+#[cfg(not(feature = "unstable"))]
+#[inline]
+fn note_registry_created(_registry: &Arc<Registry>) {}
+
+/// Total number of workers across every live pool in the process
+/// that are currently awake. As in `Registry::is_saturated()`, an
+/// awake worker is *presumed* busy -- one spinning in search of work
+/// counts too -- so this is an upper bound on useful parallelism.
+#[cfg(feature = "unstable")]
+pub fn total_busy_workers() -> usize {
+    LIVE_REGISTRIES.lock()
+                   .unwrap()
+                   .iter()
+                   .filter_map(|weak| weak.upgrade())
+                   .map(|registry| {
+                       registry.num_spawned_threads()
+                           .saturating_sub(registry.sleep.num_sleeping())
+                   })
+                   .sum()
+}
+
+/// Ratio of awake workers across every live pool to the number of
+/// CPUs. A value persistently above 1.0 means the process is
+/// oversubscribed -- typically because a job on one pool called
+/// `install()` or `join()` on another, stacking both pools' workers
+/// onto the same cores -- and monitoring code sampling this figure
+/// can use it to decide to serialize instead of nest. Like the busy
+/// count it is built on, this is a snapshot hint, not a guarantee.
+#[cfg(feature = "unstable")]
+pub fn oversubscription_factor() -> f64 {
+    total_busy_workers() as f64 / ::num_cpus::get() as f64
+}
+
+/// Forbids use of the implicit global thread pool: from now on, any
+/// Rayon call that would lazily create it panics with a message
+/// directing the caller to an explicit `ThreadPool`, and
+/// `initialize()` fails with `GlobalPoolForbidden`. Embedders whose
+/// environment restricts thread creation (e.g. running inside another
+/// runtime) can call this at startup to catch unintended parallelism.
+///
+/// This must run before the first use of the global pool: if the pool
+/// already exists, forbidding it is too late and this call has no
+/// effect on the threads already running.
+pub fn forbid_global_pool() {
+    THE_REGISTRY_FORBIDDEN.store(true, Ordering::SeqCst);
+}
+
+/// Reverts `forbid_global_pool()`, so that tests touching the flag do
+/// not poison the rest of the suite.
+#[cfg(test)]
+pub fn allow_global_pool() {
+    THE_REGISTRY_FORBIDDEN.store(false, Ordering::SeqCst);
+}
 
 /// Starts the worker threads (if that has not already happened). If
 /// initialization has not already occurred, use the default
 /// configuration.
 fn global_registry() -> &'static Arc<Registry> {
+    if THE_REGISTRY_FORBIDDEN.load(Ordering::SeqCst) && !THE_REGISTRY_SET.is_completed() {
+        panic!("Use of the global thread pool has been forbidden \
+                (see `forbid_global_pool()`); create an explicit \
+                `ThreadPool` and run the parallel call inside its \
+                `install()` instead.");
+    }
     THE_REGISTRY_SET.call_once(|| unsafe { init_registry(Configuration::new()).unwrap() });
     unsafe { THE_REGISTRY.expect("The global thread pool has not been initialized.") }
 }
 
 /// Starts the worker threads (if that has not already happened) with
 /// the given configuration.
-pub fn init_global_registry(config: Configuration) -> Result<&'static Registry, Box<Error>> {
+pub fn init_global_registry(config: Configuration)
+                            -> Result<&'static Registry, ThreadPoolBuildError> {
+    if THE_REGISTRY_FORBIDDEN.load(Ordering::SeqCst) {
+        return Err(ThreadPoolBuildError::new(ThreadPoolBuildErrorKind::GlobalPoolForbidden));
+    }
     let mut called = false;
     let mut init_result = Ok(());;
     THE_REGISTRY_SET.call_once(|| unsafe {
@@ -90,7 +754,7 @@ pub fn init_global_registry(config: Configuration) -> Result<&'static Registry,
     if called {
         init_result.map(|()| &**global_registry())
     } else {
-        Err(Box::new(GlobalPoolAlreadyInitialized))
+        Err(ThreadPoolBuildError::new(ThreadPoolBuildErrorKind::GlobalPoolAlreadyInitialized))
     }
 }
 
@@ -98,10 +762,80 @@ pub fn init_global_registry(config: Configuration) -> Result<&'static Registry,
 /// Meant to be called from within the `THE_REGISTRY_SET` once
 /// function. Declared `unsafe` because it writes to `THE_REGISTRY` in
 /// an unsynchronized fashion.
-unsafe fn init_registry(config: Configuration) -> Result<(), Box<Error>> {
+unsafe fn init_registry(config: Configuration) -> Result<(), ThreadPoolBuildError> {
     Registry::new(config).map(|registry| THE_REGISTRY = Some(leak(registry)))
 }
 
+/// The dedicated pool for `spawn_blocking()` tasks. It is created
+/// lazily, leaked, and never terminated, so jobs injected into it
+/// need no terminate-count bookkeeping.
+#[cfg(feature = "unstable")]
+static mut THE_BLOCKING_REGISTRY: Option<&'static Arc<Registry>> = None;
+#[cfg(feature = "unstable")]
+static THE_BLOCKING_REGISTRY_SET: Once = ONCE_INIT;
+
+/// Returns the blocking pool's registry, creating it with the
+/// default configuration if `init_blocking_registry()` has not run.
+#[cfg(feature = "unstable")]
+pub fn blocking_registry() -> &'static Arc<Registry> {
+    THE_BLOCKING_REGISTRY_SET
+        .call_once(|| unsafe { init_blocking_registry_once(Configuration::new()).unwrap() });
+    unsafe { THE_BLOCKING_REGISTRY.expect("The blocking thread pool has not been initialized.") }
+}
+
+/// Initializes the blocking pool with the given configuration --
+/// notably `num_threads()`, which caps how many blocking tasks can be
+/// in flight at once. Fails with `BlockingPoolAlreadyInitialized` if
+/// the pool already exists, whether from an earlier call or from a
+/// `spawn_blocking()` that created it with the defaults.
+#[cfg(feature = "unstable")]
+pub fn init_blocking_registry(config: Configuration) -> Result<(), ThreadPoolBuildError> {
+    let mut called = false;
+    let mut init_result = Ok(());
+    THE_BLOCKING_REGISTRY_SET.call_once(|| unsafe {
+        init_result = init_blocking_registry_once(config);
+        called = true;
+    });
+    if called {
+        init_result
+    } else {
+        Err(ThreadPoolBuildError::new(ThreadPoolBuildErrorKind::BlockingPoolAlreadyInitialized))
+    }
+}
+
+/// Meant to be called from within the `THE_BLOCKING_REGISTRY_SET`
+/// once function; compare `init_registry()`.
+#[cfg(feature = "unstable")]
+unsafe fn init_blocking_registry_once(config: Configuration) -> Result<(), ThreadPoolBuildError> {
+    Registry::new(config).map(|registry| THE_BLOCKING_REGISTRY = Some(leak(registry)))
+}
+
+/// Cleans up one worker's victim order as returned by the
+/// `Configuration::steal_order()` callback: entries that are out of
+/// range, duplicated, or name the worker itself are dropped, and any
+/// victims the order omits are appended (in index order) so that
+/// every other worker stays reachable. A steal order that could never
+/// probe some victim would strand that victim's jobs whenever it is
+/// the only one with work.
+#[cfg(feature = "unstable")]
+fn sanitize_steal_order(order: Vec<usize>, index: usize, n_threads: usize) -> Vec<usize> {
+    let mut seen = vec![false; n_threads];
+    seen[index] = true;
+    let mut sanitized = Vec::with_capacity(n_threads - 1);
+    for victim in order {
+        if victim < n_threads && !seen[victim] {
+            seen[victim] = true;
+            sanitized.push(victim);
+        }
+    }
+    for victim in 0..n_threads {
+        if !seen[victim] {
+            sanitized.push(victim);
+        }
+    }
+    sanitized
+}
+
 struct Terminator<'a>(&'a Arc<Registry>);
 
 impl<'a> Drop for Terminator<'a> {
@@ -111,38 +845,161 @@ impl<'a> Drop for Terminator<'a> {
 }
 
 impl Registry {
-    pub fn new(mut configuration: Configuration) -> Result<Arc<Registry>, Box<Error>> {
+    pub fn new(mut configuration: Configuration) -> Result<Arc<Registry>, ThreadPoolBuildError> {
         let n_threads = configuration.get_num_threads();
+        if n_threads == 0 {
+            // Cannot happen with the current `Configuration` (zero
+            // means "choose automatically"), but guards any future
+            // fallible resolution of the thread count.
+            return Err(ThreadPoolBuildError::new(
+                ThreadPoolBuildErrorKind::InvalidNumThreads(n_threads)));
+        }
+
+        if let Some(code) = configuration.get_abort_exit_code() {
+            unwind::set_abort_exit_code(code);
+        }
+
+        let caller_driven = configuration.get_caller_driven();
+
+        let owns_event_sink = match configuration.take_event_sink() {
+            Some(sink) => {
+                ::log::set_event_sink(sink);
+                true
+            }
+            None => false,
+        };
+
+        let sleep = Sleep::new(n_threads,
+                               configuration.get_utilization_tracking(),
+                               configuration.get_steal_retries());
+        #[cfg(feature = "unstable")]
+        let sleep = sleep.with_transition_handlers(configuration.take_on_idle(),
+                                                   configuration.take_on_busy());
 
         let (inj_worker, inj_stealer) = deque::new();
         let (workers, stealers): (Vec<_>, Vec<_>) = (0..n_threads).map(|_| deque::new()).unzip();
+        let (priority_workers, priority_stealers): (Vec<_>, Vec<_>) =
+            (0..n_threads).map(|_| deque::new()).unzip();
 
         let registry = Arc::new(Registry {
             thread_infos: stealers.into_iter()
-                .map(|s| ThreadInfo::new(s))
+                .zip(priority_stealers)
+                .map(|(s, ps)| ThreadInfo::new(s, ps))
                 .collect(),
             state: Mutex::new(RegistryState::new(inj_worker)),
-            sleep: Sleep::new(),
+            sleep: sleep,
             job_uninjector: inj_stealer,
             terminate_latch: CountLatch::new(),
             panic_handler: configuration.take_panic_handler(),
             start_handler: configuration.take_start_handler(),
             exit_handler: configuration.take_exit_handler(),
+            job_profiler: configuration.take_job_profiler(),
+            blocked_waiters: AtomicUsize::new(0),
+            utilization_reset: Mutex::new(Instant::now()),
+            injected_jobs: AtomicUsize::new(0),
+            max_injected: configuration.get_max_injected_queue(),
+            strict_inject_order: configuration.get_strict_inject_order(),
+            inject_seq: AtomicUsize::new(0),
+            #[cfg(feature = "unstable")]
+            prioritized_len: AtomicUsize::new(0),
+            inject_space: Condvar::new(),
+            unspawned: Mutex::new(Vec::new()),
+            num_spawned: AtomicUsize::new(0),
+            owns_event_sink: owns_event_sink,
+            pending_jobs: AtomicUsize::new(0),
+            #[cfg(debug_assertions)]
+            job_accounting: JobAccounting {
+                recorded: AtomicUsize::new(0),
+                taken: AtomicUsize::new(0),
+            },
+            stack_size: configuration.get_stack_size(),
+            // A caller-driven pool has no workers of its own, so a
+            // thread blocked in `install()` *must* drain the queue
+            // itself; the flag is implied there.
+            cooperative_install: configuration.get_cooperative_install() || caller_driven,
+            caller_driven: caller_driven,
+            min_split_len: cmp::max(configuration.get_min_split_len()
+                                        .unwrap_or(DEFAULT_MIN_SPLIT_LEN),
+                                    1),
+            #[cfg(feature = "unstable")]
+            max_recursion_depth: configuration.get_max_recursion_depth(),
+            max_consecutive_panics: configuration.get_max_consecutive_panics()
+                .map(|max| cmp::max(max, 1)),
+            local_queue_cap: configuration.get_local_queue_cap().unwrap_or(usize::MAX),
+            panic_abort: configuration.get_panic_abort(),
+            inject_priority: configuration.get_inject_priority(),
+            offload_aborted_drops: configuration.get_offload_aborted_drops(),
+            steal_batching: configuration.get_steal_batching(),
+            wake_batch_limit: configuration.get_wake_batch_limit()
+                .map(|limit| cmp::max(limit, 1))
+                .unwrap_or(usize::MAX),
+            shrink_idle_deques: configuration.get_shrink_idle_deques(),
+            #[cfg(feature = "unstable")]
+            fuzz_seed: configuration.get_scheduler_fuzz(),
+            #[cfg(feature = "unstable")]
+            steal_trace: if configuration.get_record_steal_trace() {
+                Some(Mutex::new(Vec::new()))
+            } else {
+                None
+            },
+            #[cfg(feature = "unstable")]
+            replay_trace: configuration.take_replay_steal_trace(),
+            #[cfg(feature = "unstable")]
+            steal_orders: configuration.take_steal_order().map(|order| {
+                (0..n_threads)
+                    .map(|index| sanitize_steal_order(order(index), index, n_threads))
+                    .collect()
+            }),
+            #[cfg(feature = "unstable")]
+            detached_tasks: AtomicUsize::new(0),
+            spawn_handler: configuration.take_spawn_handler(),
         });
 
         // If we return early or panic, make sure to terminate existing threads.
         let t1000 = Terminator(&registry);
 
-        for (index, worker) in workers.into_iter().enumerate() {
-            let registry = registry.clone();
-            let mut b = thread::Builder::new();
-            if let Some(name) = configuration.get_thread_name(index) {
-                b = b.name(name);
-            }
-            if let Some(stack_size) = configuration.get_stack_size() {
-                b = b.stack_size(stack_size);
+        note_registry_created(&registry);
+
+        // For a lazy pool, spawn only worker 0 now; stash the rest to
+        // be spawned on demand (see `spawn_unspawned_worker()`). A
+        // caller-driven pool never spawns any: the driving thread
+        // runs everything (see `ThreadPool::run_pending()`).
+        let eager_threads = if caller_driven {
+            0
+        } else if configuration.get_lazy_threads() {
+            1
+        } else {
+            n_threads
+        };
+
+        for (index, (worker, priority_worker)) in
+            workers.into_iter().zip(priority_workers).enumerate() {
+            if index >= eager_threads {
+                registry.unspawned.lock().unwrap().insert(0, UnspawnedWorker {
+                    index: index,
+                    worker: worker,
+                    priority_worker: priority_worker,
+                    name: configuration.get_thread_name(index),
+                    stack_size: configuration.get_stack_size(),
+                });
+                continue;
             }
-            try!(b.spawn(move || unsafe { main_loop(worker, registry, index) }));
+            try!(registry.spawn_thread(ThreadBuilder {
+                name: configuration.get_thread_name(index),
+                stack_size: configuration.get_stack_size(),
+                index: index,
+                worker: worker,
+                priority_worker: priority_worker,
+                registry: registry.clone(),
+            }));
+        }
+        registry.num_spawned.store(eager_threads, Ordering::SeqCst);
+
+        if configuration.get_deadlock_detection() {
+            let registry = registry.clone();
+            try!(thread::Builder::new()
+                .name("rayon deadlock watchdog".to_string())
+                .spawn(move || deadlock_watchdog(registry)));
         }
 
         // Returning normally now, without termination.
@@ -151,6 +1008,29 @@ impl Registry {
         Ok(registry.clone())
     }
 
+    /// Starts the worker thread described by `thread`, via the
+    /// configured spawn handler if there is one, or `thread::spawn`
+    /// otherwise.
+    fn spawn_thread(&self, thread: ThreadBuilder) -> io::Result<()> {
+        match self.spawn_handler {
+            Some(ref handler) => {
+                let mut handler = handler.lock().unwrap();
+                (&mut **handler)(thread)
+            }
+            None => {
+                let mut b = thread::Builder::new();
+                if let Some(name) = thread.name() {
+                    b = b.name(name.to_string());
+                }
+                if let Some(stack_size) = thread.stack_size() {
+                    b = b.stack_size(stack_size);
+                }
+                try!(b.spawn(move || thread.run()));
+                Ok(())
+            }
+        }
+    }
+
     pub fn current() -> Arc<Registry> {
         unsafe {
             let worker_thread = WorkerThread::current();
@@ -188,6 +1068,14 @@ impl Registry {
         self.thread_infos.len()
     }
 
+    /// Returns the number of worker threads actually started. Only
+    /// differs from `num_threads()` for a lazy pool that has not yet
+    /// been saturated.
+    pub fn num_spawned_threads(&self) -> usize {
+        self.num_spawned.load(Ordering::SeqCst)
+    }
+
+
     pub fn handle_panic(&self, err: Box<Any + Send>) {
         match self.panic_handler {
             Some(ref handler) => {
@@ -196,6 +1084,7 @@ impl Registry {
                 let abort_guard = unwind::AbortIfPanic;
                 handler(err);
                 mem::forget(abort_guard);
+                self.note_captured_panic();
             }
             None => {
                 // Default panic handler aborts.
@@ -204,16 +1093,367 @@ impl Registry {
         }
     }
 
+    /// Called after the panic handler has captured a panic. If a
+    /// consecutive-panic limit is configured and this worker has now
+    /// hit it, the pool gives up on the process (see
+    /// `Configuration::max_consecutive_panics()`). The counter is
+    /// reset in `WorkerThread::execute()` by any job that finishes
+    /// without panicking.
+    fn note_captured_panic(&self) {
+        let max = match self.max_consecutive_panics {
+            Some(max) => max,
+            None => return,
+        };
+        unsafe {
+            let worker_thread = WorkerThread::current();
+            if worker_thread.is_null() || (*worker_thread).registry().id() != self.id() {
+                return;
+            }
+            let index = (*worker_thread).index();
+            let info = &self.thread_infos[index];
+            let panics = info.consecutive_panics.fetch_add(1, Ordering::SeqCst) + 1;
+            if panics >= max {
+                let _ = writeln!(&mut stderr(),
+                                 "Rayon: worker {} saw {} consecutive panicking jobs; aborting",
+                                 index,
+                                 panics);
+                unwind::abort();
+            }
+        }
+    }
+
     /// Waits for the worker threads to get up and running.  This is
     /// meant to be used for benchmarking purposes, primarily, so that
     /// you can get more consistent numbers by having everything
     /// "ready to go".
     pub fn wait_until_primed(&self) {
-        for info in &self.thread_infos {
+        // For a lazy pool, only the workers that have actually been
+        // started can ever become primed.
+        let spawned = self.num_spawned_threads();
+        for info in &self.thread_infos[..spawned] {
             info.primed.wait();
         }
     }
 
+    /// Non-blocking variant of `wait_until_primed()`: returns true if
+    /// every started worker has already signaled that it is up and
+    /// running. Lets benchmarks check whether warmup is still needed
+    /// without committing to a blocking wait.
+    pub fn is_primed(&self) -> bool {
+        let spawned = self.num_spawned_threads();
+        self.thread_infos[..spawned].iter().all(|info| info.primed.probe())
+    }
+
+    /// Returns true if the pool currently appears saturated: no
+    /// worker is asleep, no further worker could be spawned (see
+    /// `Configuration::lazy_threads()`), and at least one injected
+    /// job is still waiting to be picked up. A caller ingesting
+    /// external requests can consult this to decide whether to shed
+    /// load rather than inject more work.
+    ///
+    /// This is a snapshot hint, not a guarantee: by the time the
+    /// caller acts on the answer, workers may have drained the
+    /// backlog and gone to sleep, or vice versa. In particular, an
+    /// awake worker is merely *presumed* busy -- a worker spinning in
+    /// search of work counts as awake too.
+    pub fn is_saturated(&self) -> bool {
+        if self.sleep.num_sleeping() > 0 {
+            return false;
+        }
+        if self.num_spawned_threads() < self.num_threads() {
+            return false;
+        }
+        self.injected_jobs.load(Ordering::SeqCst) > 0
+    }
+
+    /// Keeps the pool's workers out of the deep sleep for the given
+    /// window: sleeping workers are woken immediately, and until the
+    /// window closes idle workers spin in `yield_now()` rather than
+    /// block, so the first jobs of an anticipated burst start with
+    /// minimal wakeup latency. Normal sleeping resumes once the
+    /// window has passed. This deliberately trades CPU for tail
+    /// latency -- idle spinning workers burn their cores -- so the
+    /// window should be kept short and targeted.
+    ///
+    /// With `Configuration::lazy_threads()`, only workers that have
+    /// already been spawned are affected.
+    pub fn warm_up(&self, duration: Duration) {
+        self.sleep.warm_up(duration);
+    }
+
+    /// Returns, for each worker, the fraction of time it spent busy
+    /// (i.e., not asleep waiting for work) since the previous call to
+    /// this method (or since the registry was created). Only
+    /// meaningful if the registry was configured with
+    /// `Configuration::utilization_tracking()`; otherwise no idle
+    /// time is recorded and every worker appears fully busy.
+    ///
+    /// The measurement is coarse: time a worker spends spinning
+    /// before it falls asleep counts as busy.
+    pub fn utilization(&self) -> Vec<f64> {
+        let mut last_reset = self.utilization_reset.lock().unwrap();
+        let elapsed_micros = sleep::duration_micros(last_reset.elapsed());
+        *last_reset = Instant::now();
+
+        let spawned = self.num_spawned_threads();
+        (0..self.num_threads())
+            .map(|index| {
+                if index >= spawned {
+                    // never started, so never busy
+                    return 0.0;
+                }
+                let idle_micros = cmp::min(self.sleep.take_idle_micros(index), elapsed_micros);
+                if elapsed_micros == 0 {
+                    1.0
+                } else {
+                    1.0 - (idle_micros as f64 / elapsed_micros as f64)
+                }
+            })
+            .collect()
+    }
+
+    /// Blocks until this registry is fully idle: every worker thread
+    /// is asleep and there is no pending work anywhere (neither
+    /// injected, targeted, nor sitting in a worker's deque).
+    ///
+    /// This check is inherently racy: work submitted from another
+    /// thread just after we observe the registry as idle may already
+    /// be executing by the time this returns. The result is therefore
+    /// only meaningful if the caller knows that no further
+    /// submissions will happen -- e.g., in tests or batch pipelines,
+    /// once all submitting threads are done.
+    ///
+    /// Must not be called from a worker thread of this registry: that
+    /// worker can never be asleep while it is in here, so the call
+    /// would never return.
+    pub fn wait_until_idle(&self) {
+        debug_assert!(unsafe {
+            let worker_thread = WorkerThread::current();
+            worker_thread.is_null() || (*worker_thread).registry().id() != self.id()
+        });
+        loop {
+            // note: a lazy pool can grow while we wait, so re-read
+            // the spawned count on every iteration
+            if self.sleep.num_sleeping() == self.num_spawned_threads() &&
+               !self.has_pending_work() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Returns true if any job is still waiting to be picked up by a
+    /// worker. Note that this says nothing about jobs that are
+    /// already executing.
+    fn has_pending_work(&self) -> bool {
+        if self.injected_jobs.load(Ordering::SeqCst) > 0 {
+            return true;
+        }
+        self.thread_infos.iter().any(|info| {
+            info.len_hint() > 0 || !info.targeted.lock().unwrap().is_empty()
+        })
+    }
+
+    /// Returns the stack size requested for the worker threads at
+    /// construction, or `None` if they use the platform default.
+    pub fn stack_size(&self) -> Option<usize> {
+        self.stack_size
+    }
+
+    /// Returns true if batch stealing is enabled (see
+    /// `Configuration::steal_batching()`).
+    pub fn steal_batching(&self) -> bool {
+        self.steal_batching
+    }
+
+    /// Returns true if cooperative installs are enabled (see
+    /// `Configuration::cooperative_install()`).
+    pub fn cooperative_install(&self) -> bool {
+        self.cooperative_install
+    }
+
+    /// Returns the scheduling policy for injected jobs (see
+    /// `Configuration::inject_priority()`).
+    pub fn inject_priority(&self) -> InjectPriority {
+        self.inject_priority
+    }
+
+    /// Returns how many tracked detached tasks (see
+    /// `spawn_tracked()`) have been spawned into this pool but have
+    /// not yet finished.
+    #[cfg(feature = "unstable")]
+    pub fn pending_detached(&self) -> usize {
+        self.detached_tasks.load(Ordering::SeqCst)
+    }
+
+    /// Records that a tracked detached task was spawned.
+    #[cfg(feature = "unstable")]
+    pub fn note_detached_spawned(&self) {
+        self.detached_tasks.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Records that a tracked detached task finished.
+    #[cfg(feature = "unstable")]
+    pub fn note_detached_completed(&self) {
+        self.detached_tasks.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Blocks until no tracked detached task is pending -- the
+    /// graceful-shutdown companion of `spawn_tracked()`. Note that
+    /// tasks tracked-spawned *while* this waits extend the wait: the
+    /// caller is responsible for having stopped submission first.
+    /// When called on a worker thread of this pool, the worker keeps
+    /// executing other jobs while it waits.
+    #[cfg(feature = "unstable")]
+    pub fn wait_detached(&self) {
+        unsafe {
+            let worker_thread = WorkerThread::current();
+            if !worker_thread.is_null() && (*worker_thread).registry().id() == self.id() {
+                (*worker_thread).wait_until(&DetachedDrained { registry: self });
+            } else {
+                while self.pending_detached() > 0 {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+
+    /// Returns a snapshot of the scheduling decisions recorded so
+    /// far. Empty unless the pool was built with
+    /// `Configuration::record_steal_trace()`.
+    #[cfg(feature = "unstable")]
+    pub fn scheduler_trace(&self) -> SchedulerTrace {
+        SchedulerTrace {
+            records: match self.steal_trace {
+                Some(ref records) => records.lock().unwrap().clone(),
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /// Appends one steal decision to the trace, if recording.
+    #[cfg(feature = "unstable")]
+    fn trace_steal(&self, thief: usize, victim: usize) {
+        if let Some(ref records) = self.steal_trace {
+            records.lock()
+                   .unwrap()
+                   .push(StealRecord {
+                             thief: thief,
+                             victim: victim,
+                         });
+        }
+    }
+
+    /// Trace recording is unavailable without the `unstable`
+    /// feature; this compiles to nothing.
+    #[cfg(not(feature = "unstable"))]
+    #[inline]
+    fn trace_steal(&self, _thief: usize, _victim: usize) {}
+
+    /// Returns, for each worker, the deepest its deque length hint
+    /// has been since the registry was created (or since the last
+    /// `reset_max_deque_depths()`). Consistently deep deques are
+    /// concrete evidence of over-splitting -- consider raising
+    /// `Configuration::min_split_len()` -- while always-shallow ones
+    /// suggest the workload exposes too little parallelism. The
+    /// update is a relaxed `fetch_max` folded into the existing hint
+    /// increment, cheap enough to always run; the figures inherit the
+    /// hint's approximate nature.
+    pub fn max_deque_depths(&self) -> Vec<usize> {
+        self.thread_infos
+            .iter()
+            .map(|info| info.max_len_hint.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Resets the high-water marks reported by `max_deque_depths()`,
+    /// so that measurements can be scoped to one phase of a program.
+    pub fn reset_max_deque_depths(&self) {
+        for info in &self.thread_infos {
+            info.max_len_hint.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Produces a `RegistrySnapshot` of this pool's current state;
+    /// see `ThreadPool::snapshot()`.
+    #[cfg(feature = "unstable")]
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        let num_spawned = self.num_spawned_threads();
+        let num_sleeping = self.sleep.num_sleeping();
+        RegistrySnapshot {
+            num_threads: self.num_threads(),
+            num_spawned: num_spawned,
+            num_sleeping: num_sleeping,
+            threads_at_work: num_spawned.saturating_sub(num_sleeping),
+            injected_jobs: self.injected_jobs.load(Ordering::SeqCst),
+            pending_jobs: self.pending_jobs.load(Ordering::SeqCst),
+            terminating: self.terminate_latch.probe(),
+            workers: self.thread_infos
+                .iter()
+                .enumerate()
+                .map(|(index, info)| {
+                    WorkerSnapshot {
+                        index: index,
+                        queued: info.len_hint(),
+                        max_queued: info.max_len_hint.load(Ordering::Relaxed),
+                        consecutive_panics: info.consecutive_panics.load(Ordering::SeqCst),
+                        started: info.primed.probe(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Reads the given worker's deque length hint; used by tests of
+    /// the saturating hint arithmetic.
+    #[cfg(test)]
+    pub fn len_hint(&self, index: usize) -> usize {
+        self.thread_infos[index].len_hint()
+    }
+
+    /// Forces the given worker's deque length hint to `len`; used by
+    /// tests to drive the hint near its boundary.
+    #[cfg(test)]
+    pub fn set_len_hint(&self, index: usize, len: usize) {
+        self.thread_infos[index].approx_len.store(len, Ordering::Relaxed);
+    }
+
+    /// Records one push into the given worker's deque length hint,
+    /// as `WorkerThread::push()` would; used by tests.
+    #[cfg(test)]
+    pub fn bump_len_hint(&self, index: usize) {
+        self.thread_infos[index].increment_len_hint();
+    }
+
+    /// Number of workers currently marked dormant; used by tests to
+    /// check that `with_max_threads()` restores the pool.
+    #[cfg(test)]
+    pub fn num_dormant(&self) -> usize {
+        self.thread_infos.iter().filter(|info| info.dormant.load(Ordering::SeqCst)).count()
+    }
+
+    /// Current value of the pending-job counter; used by tests to
+    /// check that the increments and decrements balance out.
+    #[cfg(test)]
+    pub fn pending_jobs(&self) -> usize {
+        self.pending_jobs.load(Ordering::SeqCst)
+    }
+
+    /// Number of workers currently in the deep condvar sleep; used by
+    /// tests to observe the effect of `warm_up()`.
+    #[cfg(test)]
+    pub fn num_sleeping_workers(&self) -> usize {
+        self.sleep.num_sleeping()
+    }
+
+    /// Returns the exact `(recorded, taken)` job totals -- so tests
+    /// can check that they balance at quiescence.
+    #[cfg(all(test, debug_assertions))]
+    pub fn job_accounting(&self) -> (usize, usize) {
+        (self.job_accounting.recorded.load(Ordering::SeqCst),
+         self.job_accounting.taken.load(Ordering::SeqCst))
+    }
+
     /// Waits for the worker threads to stop. This is used for testing
     /// -- so we can check that termination actually works.
     #[cfg(test)]
@@ -246,30 +1486,271 @@ impl Registry {
     /// Unsafe: caller asserts that injected jobs will remain valid
     /// until they are executed.
     pub unsafe fn inject(&self, injected_jobs: &[JobRef]) {
+        // It should not be possible for the registry to be
+        // terminating here. Termination only begins when the user
+        // creates (and drops) a `ThreadPool`; and, in that case, they
+        // cannot be calling `inject()` later, since they dropped
+        // their `ThreadPool`. Callers that *can* race a shutdown
+        // (external producers) should use `inject_checked()`.
+        if self.inject_checked(injected_jobs).is_err() {
+            panic!("inject() sees state.terminate as true");
+        }
+    }
+
+    /// Like `inject()`, but handles a racing shutdown gracefully: if
+    /// the registry has already begun terminating, no job is queued
+    /// and the batch is handed back as an `Err` for the caller to
+    /// dispose of -- typically by running or dropping the jobs itself
+    /// -- rather than crashing the submitter.
+    ///
+    /// Unsafe for the same reason as `inject()`.
+    pub unsafe fn inject_checked(&self, injected_jobs: &[JobRef])
+                                 -> Result<(), Vec<JobRef>> {
         log!(InjectJobs { count: injected_jobs.len() });
+        // Under strict ordering the batch's place in line is claimed
+        // *now*, before the lock: even if this thread is preempted
+        // here, workers will not run later-submitted jobs ahead of
+        // these, they will wait for the insertion below. A rejected
+        // batch leaves a hole in the sequence, but a harmless one:
+        // every later submission is rejected too, so no queued job
+        // ever waits behind the hole.
+        let first_seq = if self.strict_inject_order {
+            self.inject_seq.fetch_add(injected_jobs.len(), Ordering::SeqCst)
+        } else {
+            0
+        };
         {
-            let state = self.state.lock().unwrap();
+            let mut state = self.state.lock().unwrap();
+
+            if self.terminate_latch.probe() {
+                return Err(injected_jobs.to_vec());
+            }
 
-            // It should not be possible for `state.terminate` to be true
-            // here. It is only set to true when the user creates (and
-            // drops) a `ThreadPool`; and, in that case, they cannot be
-            // calling `inject()` later, since they dropped their
-            // `ThreadPool`.
-            assert!(!self.terminate_latch.probe(), "inject() sees state.terminate as true");
+            if let Some(max) = self.max_injected {
+                // Backpressure: wait until there is room for the
+                // whole batch. A batch larger than the bound is
+                // accepted once the queue is completely empty, so
+                // that it cannot block forever.
+                // The counter lives outside the lock, but it is
+                // re-read under it on every pass: see
+                // `pop_injected_job()` for why the wakeup cannot be
+                // lost.
+                while self.injected_jobs.load(Ordering::SeqCst) + injected_jobs.len() > max &&
+                      self.injected_jobs.load(Ordering::SeqCst) > 0 {
+                    state = self.inject_space.wait(state).unwrap();
+                }
 
-            for &job_ref in injected_jobs {
-                state.job_injector.push(job_ref);
+                // The lock was released while we waited for room, so
+                // a shutdown may have begun in the meantime; queueing
+                // into a terminating pool would strand the jobs (and
+                // anyone waiting on their latches) forever.
+                if self.terminate_latch.probe() {
+                    return Err(injected_jobs.to_vec());
+                }
             }
+
+            self.debug_note_jobs_recorded(injected_jobs.len());
+            if self.strict_inject_order {
+                for (offset, &job_ref) in injected_jobs.iter().enumerate() {
+                    state.sequenced_jobs.push(SequencedJob {
+                                                  seq: first_seq + offset,
+                                                  job: job_ref,
+                                              });
+                }
+            } else {
+                for &job_ref in injected_jobs {
+                    state.job_injector.push(job_ref);
+                }
+            }
+            // Incremented under the lock, so that admission checks
+            // against `max_injected` stay serialized.
+            self.injected_jobs.fetch_add(injected_jobs.len(), Ordering::SeqCst);
+        }
+        self.note_jobs_pending(injected_jobs.len());
+        // Only `injected_jobs.len()` workers can possibly find work,
+        // so don't wake more sleepers than that -- or than the
+        // configured per-batch wake bound, if any.
+        self.sleep.tickle_many(usize::MAX,
+                               cmp::min(injected_jobs.len(), self.wake_batch_limit));
+        Ok(())
+    }
+
+    /// Like `inject()`, but never blocks: if the bounded queue does
+    /// not have room for the whole batch, no job is queued and
+    /// `false` is returned, leaving the jobs with the caller. Always
+    /// succeeds when no bound is configured.
+    ///
+    /// Unsafe for the same reason as `inject()`.
+    pub unsafe fn try_inject(&self, injected_jobs: &[JobRef]) -> bool {
+        {
+            let mut state = self.state.lock().unwrap();
+            assert!(!self.terminate_latch.probe(), "try_inject() sees state.terminate as true");
+
+            if let Some(max) = self.max_injected {
+                if self.injected_jobs.load(Ordering::SeqCst) + injected_jobs.len() > max &&
+                   self.injected_jobs.load(Ordering::SeqCst) > 0 {
+                    return false;
+                }
+            }
+
+            log!(InjectJobs { count: injected_jobs.len() });
+            self.debug_note_jobs_recorded(injected_jobs.len());
+            if self.strict_inject_order {
+                // The sequence numbers are claimed under the lock
+                // here, after the decision to accept the batch: a
+                // rejected `try_inject()` must not leave a hole in
+                // the sequence, since workers would wait for it
+                // forever. Submission order is thus lock order for
+                // this entry point.
+                let first_seq = self.inject_seq
+                    .fetch_add(injected_jobs.len(), Ordering::SeqCst);
+                for (offset, &job_ref) in injected_jobs.iter().enumerate() {
+                    state.sequenced_jobs.push(SequencedJob {
+                                                  seq: first_seq + offset,
+                                                  job: job_ref,
+                                              });
+                }
+            } else {
+                for &job_ref in injected_jobs {
+                    state.job_injector.push(job_ref);
+                }
+            }
+            // Incremented under the lock, so that admission checks
+            // against `max_injected` stay serialized.
+            self.injected_jobs.fetch_add(injected_jobs.len(), Ordering::SeqCst);
+        }
+        self.note_jobs_pending(injected_jobs.len());
+        self.sleep.tickle_many(usize::MAX,
+                               cmp::min(injected_jobs.len(), self.wake_batch_limit));
+        true
+    }
+
+    /// Injects a job carrying a user-assigned priority: idle workers
+    /// drain the prioritized heap highest-priority first, and only
+    /// consult it ahead of the FIFO injected queue. Equal priorities
+    /// run in submission order. Higher `priority` values are more
+    /// urgent; the scale is entirely the caller's.
+    ///
+    /// Prioritized jobs count toward `injected_jobs` (so
+    /// `is_saturated()` and `wait_until_idle()` see them) but are
+    /// admitted without regard to the `max_injected` bound, which
+    /// only meters the FIFO queue.
+    ///
+    /// Unsafe for the same reason as `inject()`.
+    #[cfg(feature = "unstable")]
+    pub unsafe fn inject_prioritized(&self, job_ref: JobRef, priority: i32) {
+        log!(InjectJobs { count: 1 });
+        {
+            let mut state = self.state.lock().unwrap();
+            assert!(!self.terminate_latch.probe(),
+                    "inject_prioritized() sees state.terminate as true");
+            self.debug_note_jobs_recorded(1);
+            let seq = state.next_prioritized_seq;
+            state.next_prioritized_seq += 1;
+            state.prioritized_jobs.push(PrioritizedJob {
+                priority: priority,
+                seq: seq,
+                job: job_ref,
+            });
+            self.prioritized_len.fetch_add(1, Ordering::SeqCst);
+            // Incremented under the lock, so that admission checks
+            // against `max_injected` stay serialized.
+            self.injected_jobs.fetch_add(1, Ordering::SeqCst);
+        }
+        self.note_jobs_pending(1);
+        self.sleep.tickle_many(usize::MAX, 1);
+    }
+
+    /// Runs `op` on every worker thread in this registry, passing the
+    /// worker index, and returns the results indexed by worker. Each
+    /// invocation runs on a distinct worker thread: the jobs are
+    /// "targeted" at a particular worker's queue and are never stolen.
+    ///
+    /// Blocks until all workers have executed `op`; if the workers
+    /// are busy with long-running jobs, this may take correspondingly
+    /// long. If called from a worker thread of this registry, that
+    /// worker executes its own invocation while it waits.
+    pub fn broadcast<OP, R>(&self, op: OP) -> Vec<R>
+        where OP: Fn(usize) -> R + Sync,
+              R: Send
+    {
+        unsafe {
+            let n_threads = self.num_threads();
+            let op = &op; // closures capture a `&OP`, which is `Send` since `OP: Sync`
+            let jobs: Vec<_> = (0..n_threads)
+                .map(|index| StackJob::new(move || op(index), LockLatch::new()))
+                .collect();
+            for (index, job) in jobs.iter().enumerate() {
+                self.inject_targeted(index, job.as_job_ref());
+            }
+
+            let worker_thread = WorkerThread::current();
+            if !worker_thread.is_null() && (*worker_thread).registry().id() == self.id() {
+                // One of the jobs above targets us; execute it (and
+                // keep busy otherwise) while we wait for the rest.
+                for job in &jobs {
+                    (*worker_thread).wait_until(&job.latch);
+                }
+            } else {
+                for job in &jobs {
+                    job.latch.wait();
+                }
+            }
+
+            jobs.into_iter().map(|job| job.into_result()).collect()
+        }
+    }
+
+    /// Push a job that may only be executed by the worker with the
+    /// given index. Unsafe: caller asserts that the job will remain
+    /// valid until it is executed.
+    pub unsafe fn inject_targeted(&self, index: usize, job_ref: JobRef) {
+        {
+            let mut targeted = self.thread_infos[index].targeted.lock().unwrap();
+            targeted.push(job_ref);
         }
         self.sleep.tickle(usize::MAX);
     }
 
     fn pop_injected_job(&self, worker_index: usize) -> Option<JobRef> {
+        #[cfg(feature = "unstable")]
+        {
+            // Prioritized jobs preempt the FIFO queue. The lock-free
+            // length check keeps pools that never use
+            // `inject_prioritized()` off the `state` lock here.
+            if self.prioritized_len.load(Ordering::SeqCst) > 0 {
+                if let Some(job) = self.pop_prioritized_job(worker_index) {
+                    return Some(job);
+                }
+            }
+        }
+        if self.strict_inject_order {
+            return self.pop_injected_in_order(worker_index);
+        }
         loop {
             match self.job_uninjector.steal() {
                 Stolen::Empty => return None,
                 Stolen::Abort => (), // retry
                 Stolen::Data(v) => {
+                    // The hot path: just a fetch_sub, no lock. Only a
+                    // bounded pool pays for the condvar handshake,
+                    // and even then the lock is taken empty, purely
+                    // for ordering: a blocked injector re-reads the
+                    // counter while holding the lock before it
+                    // waits, so by the time we can acquire the lock
+                    // here the injector has either seen our
+                    // decrement or is already parked where
+                    // `notify_all()` reaches it. Notifying without
+                    // the lock could race into the gap between its
+                    // re-read and its wait -- the classic lost
+                    // wakeup.
+                    self.injected_jobs.fetch_sub(1, Ordering::SeqCst);
+                    if self.max_injected.is_some() {
+                        let _state = self.state.lock().unwrap();
+                        self.inject_space.notify_all();
+                    }
+                    self.note_job_taken();
+                    self.debug_note_job_taken();
                     log!(UninjectedWork { worker: worker_index });
                     return Some(v);
                 }
@@ -277,6 +1758,245 @@ impl Registry {
         }
     }
 
+    /// Strict-FIFO variant of `pop_injected_job()` (see
+    /// `Configuration::strict_inject_order()`): only the job holding
+    /// the next sequence number may be taken. If that job has been
+    /// assigned its number but not yet inserted -- its submitter was
+    /// preempted between the two -- this returns `None` and the
+    /// worker idles, rather than running a later-submitted job ahead
+    /// of it; the insertion's tickle will bring the worker back.
+    fn pop_injected_in_order(&self, worker_index: usize) -> Option<JobRef> {
+        let job = {
+            let mut state = self.state.lock().unwrap();
+            match state.sequenced_jobs.peek() {
+                Some(sequenced) if sequenced.seq == state.next_inject_run => {}
+                _ => return None,
+            }
+            let sequenced = state.sequenced_jobs.pop().unwrap();
+            state.next_inject_run += 1;
+            self.injected_jobs.fetch_sub(1, Ordering::SeqCst);
+            if self.max_injected.is_some() {
+                // someone may be blocked waiting for room
+                self.inject_space.notify_all();
+            }
+            sequenced.job
+        };
+        self.note_job_taken();
+        self.debug_note_job_taken();
+        log!(UninjectedWork { worker: worker_index });
+        Some(job)
+    }
+
+    /// Takes the highest-priority job out of the prioritized heap
+    /// (see `inject_prioritized()`), if any.
+    #[cfg(feature = "unstable")]
+    fn pop_prioritized_job(&self, worker_index: usize) -> Option<JobRef> {
+        let job = {
+            let mut state = self.state.lock().unwrap();
+            let prioritized = match state.prioritized_jobs.pop() {
+                Some(prioritized) => prioritized,
+                None => return None,
+            };
+            self.prioritized_len.fetch_sub(1, Ordering::SeqCst);
+            self.injected_jobs.fetch_sub(1, Ordering::SeqCst);
+            if self.max_injected.is_some() {
+                // someone may be blocked waiting for room
+                self.inject_space.notify_all();
+            }
+            prioritized.job
+        };
+        self.note_job_taken();
+        self.debug_note_job_taken();
+        log!(UninjectedWork { worker: worker_index });
+        Some(job)
+    }
+
+    /// Records that `count` stealable jobs were made available
+    /// (pushed onto a deque or injected). The increment must happen
+    /// *before* the corresponding tickle, so that a worker that
+    /// observes the counter at zero and goes to sleep is guaranteed
+    /// to be covered by that tickle.
+    #[inline]
+    fn note_jobs_pending(&self, count: usize) {
+        self.pending_jobs.fetch_add(count, Ordering::SeqCst);
+    }
+
+    /// Records that one previously-counted job was taken out of its
+    /// queue. Saturates at zero rather than wrapping: the counter is
+    /// advisory, and an undercount merely costs an early sleep, while
+    /// wrapping around would make `has_pending_jobs()` report
+    /// phantom work forever and pin every idle worker at full spin.
+    #[inline]
+    fn note_job_taken(&self) {
+        let mut pending = self.pending_jobs.load(Ordering::SeqCst);
+        while pending > 0 {
+            match self.pending_jobs.compare_exchange_weak(pending,
+                                                          pending - 1,
+                                                          Ordering::SeqCst,
+                                                          Ordering::SeqCst) {
+                Ok(_) => return,
+                Err(actual) => pending = actual,
+            }
+        }
+    }
+
+    /// In debug builds, records that `count` stealable jobs are about
+    /// to be published (pushed onto a deque or injected). Must be
+    /// called *before* the jobs become visible to other threads; see
+    /// `JobAccounting`. Compiles away in release builds.
+    #[cfg(debug_assertions)]
+    #[inline]
+    fn debug_note_jobs_recorded(&self, count: usize) {
+        self.job_accounting.recorded.fetch_add(count, Ordering::SeqCst);
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline]
+    fn debug_note_jobs_recorded(&self, _count: usize) {}
+
+    /// In debug builds, records that one previously-recorded job was
+    /// taken back out of its queue and validates the accounting: the
+    /// number of jobs ever taken must never exceed the number ever
+    /// recorded. A violation means the scheduler executed some job
+    /// twice or let one bypass the counters -- exactly the class of
+    /// bug that the saturating `pending_jobs` and `approx_len`
+    /// counters are designed to absorb silently.
+    #[cfg(debug_assertions)]
+    #[inline]
+    fn debug_note_job_taken(&self) {
+        let taken = self.job_accounting.taken.fetch_add(1, Ordering::SeqCst) + 1;
+        let recorded = self.job_accounting.recorded.load(Ordering::SeqCst);
+        debug_assert!(taken <= recorded,
+                      "{} jobs taken but only {} recorded; \
+                       some job was double-executed or never counted",
+                      taken,
+                      recorded);
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline]
+    fn debug_note_job_taken(&self) {}
+
+    /// Returns true if some stealable job is believed to be queued
+    /// somewhere in the pool. Lock-free; used by an idle worker to
+    /// decide whether to keep spinning for the work it has so far
+    /// failed to steal (a lost race, most likely) rather than start
+    /// going to sleep. A false negative only sends the worker to
+    /// sleep, where the regular tickle protocol will wake it; a false
+    /// positive only costs some extra spinning. No wakeup is ever
+    /// lost on account of this counter.
+    #[inline]
+    fn has_pending_jobs(&self) -> bool {
+        self.pending_jobs.load(Ordering::SeqCst) > 0
+    }
+
+    /// Returns this pool's minimum split length (see
+    /// `Configuration::min_split_len()`).
+    pub fn min_split_len(&self) -> usize {
+        self.min_split_len
+    }
+
+    /// Returns this pool's join-depth cap, if any (see
+    /// `Configuration::max_recursion_depth()`).
+    #[cfg(feature = "unstable")]
+    pub fn max_recursion_depth(&self) -> Option<usize> {
+        self.max_recursion_depth
+    }
+
+    /// Returns true if aborted jobs' drop glue should run on the
+    /// shared cleanup thread (see
+    /// `Configuration::offload_aborted_drops()`).
+    pub fn offload_aborted_drops(&self) -> bool {
+        self.offload_aborted_drops
+    }
+
+    /// Returns the range of valid worker indices for this registry,
+    /// `0..num_threads()`. The range covers every worker slot,
+    /// including workers that are currently parked (lazy pools, or
+    /// after `resize_pool()`): an index stays valid for the life of
+    /// the pool regardless of resizing.
+    pub fn worker_indices(&self) -> ::std::ops::Range<usize> {
+        0..self.num_threads()
+    }
+
+    /// Runs `op` with at most `num_threads` of this pool's workers
+    /// actively acquiring work; the rest are marked dormant for the
+    /// duration and restored afterwards (also on panic). See
+    /// `ThreadPool::with_max_threads()` for the semantics.
+    pub fn with_max_threads<OP, R>(&self, num_threads: usize, op: OP) -> R
+        where OP: FnOnce() -> R
+    {
+        let num_threads = cmp::max(num_threads, 1);
+
+        // Only remember the flags *we* set, so that when calls nest,
+        // an inner call does not wake workers the outer one parked.
+        let mut marked = Vec::new();
+        for (index, info) in self.thread_infos.iter().enumerate().skip(num_threads) {
+            if !info.dormant.swap(true, Ordering::SeqCst) {
+                marked.push(index);
+            }
+        }
+        let _guard = DormantGuard {
+            registry: self,
+            marked: marked,
+        };
+        op()
+    }
+
+    /// Attempts to pop one injected job and execute it on the calling
+    /// thread, returning whether any work was done. Callable from any
+    /// thread; see `ThreadPool::try_run_one()` for the intended use.
+    ///
+    /// Deliberately restricted to the injected queue: the calling
+    /// thread has no `WorkerThread`, hence no deque to push to or
+    /// steal into, but draining the shared FIFO requires neither.
+    pub fn try_run_one(&self) -> bool {
+        // `usize::MAX` marks "not a worker" in the log events.
+        match self.pop_injected_job(usize::MAX) {
+            Some(job) => {
+                unsafe {
+                    job.execute();
+                }
+                // As in `WorkerThread::execute()`: the job may have
+                // set latches that a sleepy worker is waiting on.
+                self.sleep.tickle(usize::MAX);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Blocks the calling (non-worker) thread until `latch` is set.
+    /// With `Configuration::cooperative_install()` enabled, the
+    /// thread first helps drain the injected queue instead of sitting
+    /// idle -- promoting it, in a limited way, to an extra transient
+    /// worker. Once the queue comes up empty (or the latch is set),
+    /// it falls back to blocking on the latch; `LockLatch::wait()`
+    /// returns immediately if the latch was set in the meantime.
+    pub fn wait_for_injected_latch(&self, latch: &LockLatch) {
+        if self.cooperative_install {
+            while !latch.probe() {
+                if !self.try_run_one() {
+                    break;
+                }
+            }
+        }
+        latch.wait();
+    }
+
+    /// Marks that the calling thread is blocked waiting for work it
+    /// injected into this registry to complete. This should be
+    /// balanced by a call to `unmark_blocked_waiter`. It only feeds
+    /// the deadlock watchdog and has no scheduling effect.
+    pub fn mark_blocked_waiter(&self) {
+        self.blocked_waiters.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Inverse of `mark_blocked_waiter`.
+    pub fn unmark_blocked_waiter(&self) {
+        self.blocked_waiters.fetch_sub(1, Ordering::SeqCst);
+    }
+
     /// Increment the terminate counter. This increment should be
     /// balanced by a call to `terminate`, which will decrement. This
     /// is used when spawning asynchronous work, which needs to
@@ -301,11 +2021,33 @@ impl Registry {
         self.terminate_latch.increment();
     }
 
+    /// Wakes any sleeping workers so that they re-check the latches
+    /// they are waiting on. This must be called after setting a latch
+    /// from a thread outside the pool: a worker tickles automatically
+    /// after each job it executes, but a latch set by a foreign
+    /// thread is otherwise invisible to a worker that has already
+    /// fallen asleep.
+    pub fn tickle(&self) {
+        self.sleep.tickle(usize::MAX);
+    }
+
     /// Signals that the thread-pool which owns this registry has been
     /// dropped. The worker threads will gradually terminate, once any
     /// extant work is completed.
     pub fn terminate(&self) {
         self.terminate_latch.set();
+        if self.terminate_latch.probe() {
+            // Workers of a lazy pool that were never spawned cannot
+            // observe the termination; mark them directly so that
+            // shutdown does not wait for them.
+            for u in self.unspawned.lock().unwrap().drain(..) {
+                self.thread_infos[u.index].primed.set();
+                self.thread_infos[u.index].stopped.set();
+            }
+            if self.owns_event_sink {
+                ::log::clear_event_sink();
+            }
+        }
         self.sleep.tickle(usize::MAX);
     }
 }
@@ -319,6 +2061,12 @@ impl RegistryState {
     pub fn new(job_injector: Worker<JobRef>) -> RegistryState {
         RegistryState {
             job_injector: job_injector,
+            sequenced_jobs: BinaryHeap::new(),
+            next_inject_run: 0,
+            #[cfg(feature = "unstable")]
+            prioritized_jobs: BinaryHeap::new(),
+            #[cfg(feature = "unstable")]
+            next_prioritized_seq: 0,
         }
     }
 }
@@ -335,16 +2083,103 @@ struct ThreadInfo {
 
     /// the "stealer" half of the worker's deque
     stealer: Stealer<JobRef>,
+
+    /// the "stealer" half of the worker's high-priority deque; see
+    /// `WorkerThread::push_priority()`
+    priority_stealer: Stealer<JobRef>,
+
+    /// Jobs that must be executed by this worker specifically; used
+    /// to implement `Registry::broadcast()`. Pushed by any thread,
+    /// drained only by the owning worker.
+    targeted: Mutex<Vec<JobRef>>,
+
+    /// Set when this worker has been asked to retire by
+    /// `resize_pool()`. Checked (together with the terminate latch)
+    /// each time the worker looks for work in its main loop; the
+    /// worker hands its deques back to the unspawned list and exits.
+    please_exit: AtomicBool,
+
+    /// Approximate number of jobs currently in this worker's deque.
+    /// Updated on push/pop/steal with relaxed ordering, so it may
+    /// drift from the true length; it is only ever used as a cheap
+    /// hint to direct steal attempts at busier victims.
+    approx_len: AtomicUsize,
+
+    /// High-water mark of `approx_len` since the last call to
+    /// `Registry::reset_max_deque_depths()`. As approximate as the
+    /// hint itself; used for tuning only (see
+    /// `Registry::max_deque_depths()`).
+    max_len_hint: AtomicUsize,
+
+    /// Set while this worker is temporarily dormant (see
+    /// `ThreadPool::with_max_threads()`). A dormant worker finishes
+    /// work it already has but does not steal or take injected jobs,
+    /// so the pool's effective parallelism drops without any threads
+    /// being torn down.
+    dormant: AtomicBool,
+
+    /// Number of consecutive jobs on this worker whose panic was
+    /// captured by the panic handler; reset by any job that completes
+    /// normally. Only maintained when
+    /// `Configuration::max_consecutive_panics()` is set.
+    consecutive_panics: AtomicUsize,
 }
 
 impl ThreadInfo {
-    fn new(stealer: Stealer<JobRef>) -> ThreadInfo {
+    fn new(stealer: Stealer<JobRef>, priority_stealer: Stealer<JobRef>) -> ThreadInfo {
         ThreadInfo {
             primed: LockLatch::new(),
             stopped: LockLatch::new(),
             stealer: stealer,
+            priority_stealer: priority_stealer,
+            targeted: Mutex::new(Vec::new()),
+            please_exit: AtomicBool::new(false),
+            approx_len: AtomicUsize::new(0),
+            max_len_hint: AtomicUsize::new(0),
+            dormant: AtomicBool::new(false),
+            consecutive_panics: AtomicUsize::new(0),
+        }
+    }
+
+    /// Record that a job was pushed onto this worker's deque.
+    /// Saturating, like `decrement_len_hint()`: a hint that has
+    /// reached `usize::MAX` stays there rather than wrapping to zero,
+    /// which would make a busy worker look empty to the steal
+    /// heuristic. Reaching the boundary takes a pathological
+    /// imbalance between recorded pushes and pops; since the hint is
+    /// approximate anyway, degrading to "always looks busy" is the
+    /// graceful failure mode.
+    fn increment_len_hint(&self) {
+        let mut len = self.approx_len.load(Ordering::Relaxed);
+        while len < usize::MAX {
+            match self.approx_len
+                .compare_exchange_weak(len, len + 1, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => {
+                    self.max_len_hint.fetch_max(len + 1, Ordering::Relaxed);
+                    return;
+                }
+                Err(l) => len = l,
+            }
+        }
+    }
+
+    /// Record that a job was removed (popped or stolen) from this
+    /// worker's deque. Saturating, since the hint is approximate and
+    /// may have drifted out of sync with the real deque length.
+    fn decrement_len_hint(&self) {
+        let mut len = self.approx_len.load(Ordering::Relaxed);
+        while len > 0 {
+            match self.approx_len
+                .compare_exchange_weak(len, len - 1, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(l) => len = l,
+            }
         }
     }
+
+    fn len_hint(&self) -> usize {
+        self.approx_len.load(Ordering::Relaxed)
+    }
 }
 
 /// ////////////////////////////////////////////////////////////////////////
@@ -354,12 +2189,86 @@ pub struct WorkerThread {
     worker: Worker<JobRef>,
     index: usize,
 
+    /// A second, high-priority deque, checked before `worker` both
+    /// when popping locally and when being stolen from. Only
+    /// `push_priority()` publishes into it; the normal scheduling
+    /// path is unaffected when it is empty.
+    priority_worker: Worker<JobRef>,
+
+    /// "Sticky" jobs, which are never published to other workers and
+    /// hence can never be stolen. They are executed only when this
+    /// worker returns to the scheduler (see `pop()`).
+    sticky_jobs: UnsafeCell<Vec<JobRef>>,
+
+    /// For each victim, how many consecutive steal probes found it
+    /// empty; once the count passes a threshold the victim is mostly
+    /// skipped, with a periodic re-probe (see `steal_from()`). Only
+    /// this worker reads or writes its own counters, so no
+    /// synchronization is needed.
+    steal_backoff: UnsafeCell<Vec<usize>>,
+
+    /// Under `InjectPriority::RoundRobin`, whether the next idle
+    /// round checks the injected queue before the in-pool work;
+    /// flipped every round. Unused under the other policies.
+    injected_turn: Cell<bool>,
+
     /// A weak random number generator.
     rng: UnsafeCell<rand::XorShiftRng>,
 
+    /// When scheduling fuzzing is enabled, a dedicated RNG driving
+    /// the injected yields (see `fuzz_tick()`). Kept separate from
+    /// `rng` so that victim selection is unaffected and the yield
+    /// decisions are replayable from the configured seed.
+    #[cfg(feature = "unstable")]
+    fuzz_rng: Option<UnsafeCell<rand::XorShiftRng>>,
+
+    /// When replaying a recorded trace, the script of victims this
+    /// worker must steal from, in order (see `steal_scripted()`).
+    /// Only this worker touches its own script, so no synchronization
+    /// is needed.
+    #[cfg(feature = "unstable")]
+    replay_script: Option<UnsafeCell<ReplayScript>>,
+
+    /// This worker's preferred order of steal victims, if the pool
+    /// was configured with `Configuration::steal_order()` (see
+    /// `steal()`). `None` means random victim rotation.
+    #[cfg(feature = "unstable")]
+    steal_order: Option<Vec<usize>>,
+
+    /// How many `join()` calls are currently live on this worker's
+    /// stack, counting stolen jobs' joins too, since they share the
+    /// same physical stack (see
+    /// `Configuration::max_recursion_depth()`).
+    #[cfg(feature = "unstable")]
+    join_depth: Cell<usize>,
+
     registry: Arc<Registry>,
 }
 
+/// Per-worker state of a trace replay (see
+/// `Configuration::replay_steal_trace()`): the victims recorded for
+/// this worker, and how far along the script it has come.
+#[cfg(feature = "unstable")]
+struct ReplayScript {
+    /// Recorded victim indices, in the order the steals happened.
+    victims: Vec<usize>,
+
+    /// Next entry of `victims` to force.
+    cursor: usize,
+
+    /// How many times in a row the current entry's victim has come up
+    /// empty.
+    empty_probes: usize,
+}
+
+/// How many empty probes of a scripted victim a replaying worker
+/// tolerates before abandoning that entry of its script. Replay is
+/// best-effort: if the run has diverged from the recorded one, the
+/// recorded victim may never again hold the job in question, and
+/// insisting forever would hang the pool.
+#[cfg(feature = "unstable")]
+const REPLAY_EMPTY_PROBES: usize = 4096;
+
 // This is a bit sketchy, but basically: the WorkerThread is
 // allocated on the stack of the worker on entry and stored into this
 // thread local variable. So it will remain valid at least until the
@@ -370,6 +2279,21 @@ thread_local! {
         Cell::new(0 as *const WorkerThread)
 }
 
+/// Number of consecutive empty steal probes after which a victim is
+/// considered idle and mostly skipped during full sweeps.
+const EMPTY_PROBES_UNTIL_BACKOFF: usize = 4;
+
+/// While backing off, an idle victim is still re-probed once every
+/// this many sweeps, so that it is discovered again within a few
+/// rounds once it becomes busy.
+const BACKOFF_REPROBE_PERIOD: usize = 8;
+
+/// Under `Configuration::steal_order()`, roughly one idle sweep in
+/// this many ignores the configured order and uses a random rotation
+/// instead; see `steal()` for why a fixed order alone is not enough.
+#[cfg(feature = "unstable")]
+const STEAL_ORDER_RANDOM_PERIOD: u32 = 8;
+
 impl WorkerThread {
     /// Gets the `WorkerThread` index for the current thread; returns
     /// NULL if this is not a worker thread. This pointer is valid
@@ -388,6 +2312,13 @@ impl WorkerThread {
         });
     }
 
+    /// Clears the worker thread pointer for the current thread. Done
+    /// when a worker retires (see `resize_pool()`), since its
+    /// `WorkerThread` is dismantled before the thread finishes.
+    unsafe fn unset_current() {
+        WORKER_THREAD_STATE.with(|t| t.set(0 as *const WorkerThread));
+    }
+
     /// Returns the registry that owns this worker thread.
     pub fn registry(&self) -> &Arc<Registry> {
         &self.registry
@@ -399,17 +2330,145 @@ impl WorkerThread {
         self.index
     }
 
+    /// How many `join()` calls are live on this worker's stack (see
+    /// `Configuration::max_recursion_depth()`).
+    #[cfg(feature = "unstable")]
+    #[inline]
+    pub fn join_depth(&self) -> usize {
+        self.join_depth.get()
+    }
+
+    /// Updates the live-`join()` count; only `join()` itself should
+    /// call this, bracketing its parallel path.
+    #[cfg(feature = "unstable")]
+    #[inline]
+    pub fn set_join_depth(&self, depth: usize) {
+        self.join_depth.set(depth);
+    }
+
+    /// Returns true if this worker is temporarily dormant (see
+    /// `with_max_threads()`).
+    #[inline]
+    fn is_dormant(&self) -> bool {
+        self.registry.thread_infos[self.index].dormant.load(Ordering::SeqCst)
+    }
+
+    /// When scheduling fuzzing is enabled (see
+    /// `Configuration::scheduler_fuzz()`), yield the thread at random
+    /// so that interleavings rarely seen in normal runs get
+    /// exercised. Called from the scheduler's hot paths (push, pop,
+    /// and the idle loop); without the `unstable` feature it compiles
+    /// down to nothing.
+    #[cfg(feature = "unstable")]
+    #[inline]
+    unsafe fn fuzz_tick(&self) {
+        if let Some(ref rng) = self.fuzz_rng {
+            if (*rng.get()).next_u32() & 1 == 0 {
+                thread::yield_now();
+            }
+        }
+    }
+
+    #[cfg(not(feature = "unstable"))]
+    #[inline]
+    unsafe fn fuzz_tick(&self) {}
+
+    /// Releases the buffers of this worker's emptied side queues, so
+    /// that an idle worker does not hold the high-water-mark capacity
+    /// of a past burst forever (see
+    /// `Configuration::shrink_idle_deques()`). The capacity checks
+    /// make repeated calls free.
+    unsafe fn shrink_idle_storage(&self) {
+        let sticky_jobs = &mut *self.sticky_jobs.get();
+        if sticky_jobs.is_empty() && sticky_jobs.capacity() > 0 {
+            sticky_jobs.shrink_to_fit();
+        }
+        let mut targeted = self.registry.thread_infos[self.index].targeted.lock().unwrap();
+        if targeted.is_empty() && targeted.capacity() > 0 {
+            targeted.shrink_to_fit();
+        }
+    }
+
+    /// Reads the current capacity of this worker's sticky queue; used
+    /// by tests of the idle shrinking. Only meaningful from the
+    /// worker itself.
+    #[cfg(test)]
+    pub unsafe fn sticky_capacity(&self) -> usize {
+        (*self.sticky_jobs.get()).capacity()
+    }
+
     #[inline]
     pub unsafe fn push(&self, job: JobRef) {
+        self.fuzz_tick();
+        // Bounded local queue (see `Configuration::local_queue_cap()`):
+        // beyond the cap the job goes to the shared injected queue,
+        // where every worker sees it immediately, instead of growing
+        // this worker's private deque. `try_inject()` keeps the
+        // balancing invariants intact -- the job is counted as
+        // pending exactly once, and the length hint feeding the
+        // steal heuristics only ever counts jobs really sitting in
+        // the local deque. The *non-blocking* variant matters: a
+        // worker that blocked here waiting for injector room could
+        // never drain that room itself. If the bounded injected
+        // queue is full, the job simply stays local.
+        if self.registry.thread_infos[self.index].len_hint() >= self.registry.local_queue_cap &&
+           self.registry.try_inject(&[job]) {
+            return;
+        }
+        self.registry.debug_note_jobs_recorded(1);
         self.worker.push(job);
+        self.registry.thread_infos[self.index].increment_len_hint();
+        self.registry.note_jobs_pending(1);
+        self.registry.sleep.tickle(self.index);
+    }
+
+    /// Push a job onto the high-priority deque. Such jobs are
+    /// executed (and stolen) in preference to anything on the regular
+    /// deque, which makes them suitable for latency-critical
+    /// subtrees; but note that a steady stream of high-priority jobs
+    /// will starve the regular deque.
+    #[inline]
+    pub unsafe fn push_priority(&self, job: JobRef) {
+        self.registry.debug_note_jobs_recorded(1);
+        self.priority_worker.push(job);
+        self.registry.thread_infos[self.index].increment_len_hint();
+        self.registry.note_jobs_pending(1);
         self.registry.sleep.tickle(self.index);
     }
 
+    /// Push a job that only this worker will ever execute. Unlike
+    /// `push()`, the job is not made visible to other workers, so it
+    /// cannot be stolen. Sticky jobs take priority over the regular
+    /// deque in `pop()`; but note that if this worker never returns
+    /// to the scheduler (e.g., because it is busy with one very long
+    /// job), its sticky jobs are starved indefinitely.
+    #[inline]
+    pub unsafe fn push_sticky(&self, job: JobRef) {
+        // OK to use this UnsafeCell because this data is confined to
+        // the current thread, as WorkerThread is not Send nor Sync.
+        (*self.sticky_jobs.get()).push(job);
+    }
+
     /// Pop `job` from top of stack, returning `false` if it has been
     /// stolen.
     #[inline]
     pub unsafe fn pop(&self) -> Option<JobRef> {
-        self.worker.pop()
+        self.fuzz_tick();
+        if let Some(job) = self.priority_worker.pop() {
+            self.registry.thread_infos[self.index].decrement_len_hint();
+            self.registry.note_job_taken();
+            self.registry.debug_note_job_taken();
+            return Some(job);
+        }
+        (*self.sticky_jobs.get()).pop().or_else(|| {
+            let job = self.worker.pop();
+            if job.is_some() {
+                self.registry.thread_infos[self.index].decrement_len_hint();
+                self.registry.note_job_taken();
+                self.registry.debug_note_job_taken();
+            }
+            job
+        })
     }
 
     /// Wait until the latch is set. Try to keep busy by popping and
@@ -422,6 +2481,32 @@ impl WorkerThread {
         }
     }
 
+    /// One idle round's worth of job hunting for a non-dormant
+    /// worker: local deque, stealing from siblings, and the injected
+    /// queue, in the order dictated by the pool's `InjectPriority`
+    /// policy.
+    unsafe fn find_nondormant_job(&self) -> Option<JobRef> {
+        let injected_first = match self.registry.inject_priority {
+            InjectPriority::PreferInjected => true,
+            InjectPriority::PreferLocal => false,
+            InjectPriority::RoundRobin => {
+                let turn = self.injected_turn.get();
+                self.injected_turn.set(!turn);
+                turn
+            }
+        };
+        if injected_first {
+            self.registry
+                .pop_injected_job(self.index)
+                .or_else(|| self.pop())
+                .or_else(|| self.steal())
+        } else {
+            self.pop()
+                .or_else(|| self.steal())
+                .or_else(|| self.registry.pop_injected_job(self.index))
+        }
+    }
+
     #[cold]
     unsafe fn wait_until_cold<L: LatchProbe + ?Sized>(&self, latch: &L) {
         // the code below should swallow all panics and hence never
@@ -433,17 +2518,62 @@ impl WorkerThread {
 
         let mut yields = 0;
         while !latch.probe() {
-            // Try to find some work to do. We give preference first
-            // to things in our local deque, then in other workers
-            // deques, and finally to injected jobs from the
-            // outside. The idea is to finish what we started before
-            // we take on something new.
-            if let Some(job) = self.pop()
-                                   .or_else(|| self.steal())
-                                   .or_else(|| self.registry.pop_injected_job(self.index)) {
+            // Try to find some work to do. By default we give
+            // preference first to things in our local deque, then in
+            // other workers deques, and finally to injected jobs from
+            // the outside -- the idea being to finish what we started
+            // before we take on something new -- but the pool's
+            // `InjectPriority` policy can reorder the injected queue
+            // relative to the in-pool work (see
+            // `find_nondormant_job()`).
+            //
+            // A dormant worker (see `with_max_threads()`) only
+            // finishes work that is already its own -- local and
+            // reserved jobs, which nobody else could run -- and does
+            // not steal or take injected jobs, so that the pool's
+            // effective parallelism drops.
+            //
+            // Note that a worker woken from the condvar re-enters
+            // this loop with its yield count reset, so even one that
+            // was woken "for nothing" -- say it lost the race for a
+            // single injected job -- performs a full round of sweeps
+            // before it can fall asleep again. That is deliberate:
+            // the winner of such a race is often about to fan out
+            // subtasks (a tree-spawning root job), and the losing
+            // wakers are exactly the threads positioned to steal
+            // them.
+            self.fuzz_tick();
+            let dormant = self.is_dormant();
+            let job = if dormant {
+                self.pop().or_else(|| self.take_targeted_job())
+            } else {
+                self.find_nondormant_job()
+                    .or_else(|| self.take_targeted_job())
+            };
+            if let Some(job) = job {
                 yields = self.registry.sleep.work_found(self.index, yields);
                 self.execute(job);
+            } else if dormant {
+                if self.registry.has_pending_jobs() {
+                    // A tickle meant for that work may have woken us
+                    // instead of a worker that would take it; pass
+                    // the wakeup along before settling back down.
+                    self.registry.sleep.tickle(self.index);
+                }
+                yields = self.registry.sleep.no_work_found(self.index, yields);
+            } else if self.registry.has_pending_jobs() {
+                // Our sweep came up empty, yet a stealable job is
+                // queued somewhere -- most likely we lost a race for
+                // it, or it was pushed after we probed its deque.
+                // Keep spinning rather than escalate toward sleep:
+                // checking the counter is lock-free, and sleeping now
+                // would just mean being tickled right back awake.
+                yields = self.registry.sleep.work_found(self.index, yields);
+                thread::yield_now();
             } else {
+                if self.registry.shrink_idle_deques {
+                    self.shrink_idle_storage();
+                }
                 yields = self.registry.sleep.no_work_found(self.index, yields);
             }
         }
@@ -457,8 +2587,50 @@ impl WorkerThread {
         mem::forget(abort_guard); // successful execution, do not abort
     }
 
+    /// Take a job that was targeted specifically at this worker, if
+    /// any. See `Registry::broadcast()`.
+    unsafe fn take_targeted_job(&self) -> Option<JobRef> {
+        let info = &self.registry.thread_infos[self.index];
+        let mut targeted = info.targeted.lock().unwrap();
+        let job = targeted.pop();
+        if job.is_some() {
+            log!(TargetedWork { worker: self.index });
+        }
+        job
+    }
+
     pub unsafe fn execute(&self, job: JobRef) {
-        job.execute();
+        // When a consecutive-panic limit is configured, note the
+        // panic count before running the job: if it is unchanged
+        // afterwards, the job completed without a captured panic and
+        // the count starts over (see `note_captured_panic()`).
+        let panics_before = match self.registry.max_consecutive_panics {
+            Some(_) => {
+                self.registry.thread_infos[self.index].consecutive_panics.load(Ordering::SeqCst)
+            }
+            None => 0,
+        };
+
+        // Timed only when a profiler is configured, so the common
+        // case pays for nothing but this branch.
+        match self.registry.job_profiler {
+            Some(ref profiler) => {
+                let started = Instant::now();
+                job.execute();
+                profiler(JobTiming {
+                    worker: self.index,
+                    duration: started.elapsed(),
+                });
+            }
+            None => job.execute(),
+        }
+
+        if self.registry.max_consecutive_panics.is_some() {
+            let info = &self.registry.thread_infos[self.index];
+            if info.consecutive_panics.load(Ordering::SeqCst) == panics_before {
+                info.consecutive_panics.store(0, Ordering::SeqCst);
+            }
+        }
 
         // Subtle: executing this job will have `set()` some of its
         // latches.  This may mean that a sleepy (or sleeping) worker
@@ -483,6 +2655,43 @@ impl WorkerThread {
         assert!(num_threads < (u32::MAX as usize),
                 "we do not support more than u32::MAX worker threads");
 
+        // When replaying a recorded trace, victim selection is
+        // scripted rather than random (see `steal_scripted()`).
+        #[cfg(feature = "unstable")]
+        {
+            if let Some(ref script) = self.replay_script {
+                // OK to use this UnsafeCell for the same reason as
+                // `rng` below: the script is confined to this thread.
+                let script = &mut *script.get();
+                if script.cursor < script.victims.len() {
+                    return self.steal_scripted(script);
+                }
+            }
+        }
+
+        // With a configured steal order (see
+        // `Configuration::steal_order()`), probe the victims in that
+        // order -- but every so often fall back to a random rotation
+        // anyway, so that a victim ranked last by everyone is still
+        // raided promptly when it is the only one with work, and so
+        // that fixed orders cannot lock a set of workers into
+        // probing each other in lockstep.
+        #[cfg(feature = "unstable")]
+        {
+            if let Some(ref order) = self.steal_order {
+                let randomize = {
+                    // OK to use this UnsafeCell for the same reason
+                    // as below: the rng is confined to this thread.
+                    let rng = &mut *self.rng.get();
+                    rng.next_u32() % STEAL_ORDER_RANDOM_PERIOD == 0
+                };
+                if !randomize {
+                    return self.steal_sweep(order.iter().cloned(), true)
+                        .or_else(|| self.steal_sweep(order.iter().cloned(), false));
+                }
+            }
+        }
+
         let start = {
             // OK to use this UnsafeCell because (a) this data is
             // confined to current thread, as WorkerThread is not Send
@@ -491,37 +2700,432 @@ impl WorkerThread {
             let rng = &mut *self.rng.get();
             rng.next_u32() % num_threads as u32
         } as usize;
-        (start .. num_threads)
-            .chain(0 .. start)
+
+        // First prefer victims whose length hint says they have work,
+        // to avoid wasting steal attempts (and cross-core traffic) on
+        // deques that are likely empty. The hint is approximate, so
+        // if that sweep comes up empty we still fall back to trying
+        // every victim.
+        self.steal_from(start, num_threads, true)
+            .or_else(|| self.steal_from(start, num_threads, false))
+    }
+
+    /// Returns true if `victim_index` should be skipped in this
+    /// sweep because it has come up empty so many times in a row that
+    /// we are backing off from it. Counts the skipped sweep towards
+    /// the next periodic re-probe.
+    unsafe fn in_steal_backoff(&self, victim_index: usize) -> bool {
+        // OK to use this UnsafeCell for the same reason as `rng`:
+        // the data is confined to the current thread.
+        let backoff = &mut *self.steal_backoff.get();
+        let count = backoff[victim_index];
+        if count >= EMPTY_PROBES_UNTIL_BACKOFF &&
+           count < EMPTY_PROBES_UNTIL_BACKOFF + BACKOFF_REPROBE_PERIOD {
+            backoff[victim_index] = count + 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Updates the backoff counter for `victim_index` after an actual
+    /// steal probe: a success makes the victim interesting again,
+    /// while an empty probe brings it closer to (or keeps it in)
+    /// backoff. Probes of the hint-directed sweep don't count -- they
+    /// only ever target victims that look busy.
+    unsafe fn record_steal_outcome(&self, victim_index: usize, only_busy: bool, stolen: bool) {
+        let backoff = &mut *self.steal_backoff.get();
+        if stolen {
+            backoff[victim_index] = 0;
+        } else if !only_busy {
+            // A failed re-probe restarts the period rather than
+            // growing the counter without bound.
+            backoff[victim_index] =
+                cmp::min(backoff[victim_index] + 1, EMPTY_PROBES_UNTIL_BACKOFF);
+        }
+    }
+
+    /// Under `Configuration::steal_batching()`, after one job has
+    /// been stolen from `victim`, moves up to half of the victim's
+    /// remaining (apparent) regular deque into our own as part of the
+    /// same raid, so that a deep backlog is rebalanced in one
+    /// round-trip rather than one job at a time. Only the regular
+    /// deque is batched: taking extra high-priority jobs would demote
+    /// them to our regular deque. The extra jobs stay stealable from
+    /// us in turn. Any contention or emptiness simply ends the batch
+    /// early -- we already have the one job we came for.
+    unsafe fn steal_batch_from(&self, victim: &ThreadInfo) {
+        let extra = victim.len_hint() / 2;
+        for _ in 0..extra {
+            match victim.stealer.steal() {
+                Stolen::Empty | Stolen::Abort => break,
+                Stolen::Data(v) => {
+                    victim.decrement_len_hint();
+                    self.registry.note_job_taken();
+                    self.registry.debug_note_job_taken();
+                    self.push(v);
+                }
+            }
+        }
+    }
+
+    unsafe fn steal_from(&self,
+                         start: usize,
+                         num_threads: usize,
+                         only_busy: bool)
+                         -> Option<JobRef> {
+        self.steal_sweep((start .. num_threads).chain(0 .. start), only_busy)
+    }
+
+    /// One pass over the given victims, in the given order; see
+    /// `steal()` for the two-sweep (hint-directed, then exhaustive)
+    /// protocol built on top of this.
+    unsafe fn steal_sweep<I>(&self, victims: I, only_busy: bool) -> Option<JobRef>
+        where I: Iterator<Item = usize>
+    {
+        #[cfg(test)]
+        STEAL_SWEEPS.with(|c| c.set(c.get() + 1));
+        victims
             .filter(|&i| i != self.index)
             .filter_map(|victim_index| {
                 let victim = &self.registry.thread_infos[victim_index];
-                loop {
-                    match victim.stealer.steal() {
-                        Stolen::Empty => return None,
-                        Stolen::Abort => (), // retry
-                        Stolen::Data(v) => {
-                            log!(StoleWork { worker: self.index, victim: victim_index });
-                            return Some(v);
-                        }
-                    }
+                if only_busy && victim.len_hint() == 0 {
+                    return None;
+                }
+                if !only_busy && self.in_steal_backoff(victim_index) {
+                    return None;
                 }
+                // prefer the victim's high-priority deque
+                let stolen = steal_one(&victim.priority_stealer)
+                    .or_else(|| steal_one(&victim.stealer));
+                self.record_steal_outcome(victim_index, only_busy, stolen.is_some());
+                stolen.map(|v| {
+                    victim.decrement_len_hint();
+                    self.registry.note_job_taken();
+                    self.registry.debug_note_job_taken();
+                    self.registry.trace_steal(self.index, victim_index);
+                    log!(StoleWork { worker: self.index, victim: victim_index });
+                    if self.registry.steal_batching {
+                        self.steal_batch_from(victim);
+                    }
+                    v
+                })
             })
             .next()
     }
+
+    /// Performs the next scripted steal of a replayed trace (see
+    /// `Configuration::replay_steal_trace()`): only the recorded
+    /// victim is probed, so that the steal commits against the same
+    /// deque as in the recorded run. A successful steal advances the
+    /// script; an empty probe leaves the script in place, and the
+    /// caller simply retries on its next idle round, until the
+    /// divergence bail-out of `REPLAY_EMPTY_PROBES` gives up on the
+    /// entry.
+    #[cfg(feature = "unstable")]
+    unsafe fn steal_scripted(&self, script: &mut ReplayScript) -> Option<JobRef> {
+        let victim_index = script.victims[script.cursor];
+        let victim = &self.registry.thread_infos[victim_index];
+        // prefer the victim's high-priority deque, as in `steal_from()`
+        let stolen = steal_one(&victim.priority_stealer)
+            .or_else(|| steal_one(&victim.stealer));
+        match stolen {
+            Some(job) => {
+                script.cursor += 1;
+                script.empty_probes = 0;
+                victim.decrement_len_hint();
+                self.registry.note_job_taken();
+                self.registry.debug_note_job_taken();
+                self.registry.trace_steal(self.index, victim_index);
+                log!(StoleWork { worker: self.index, victim: victim_index });
+                Some(job)
+            }
+            None => {
+                script.empty_probes += 1;
+                if script.empty_probes >= REPLAY_EMPTY_PROBES {
+                    script.cursor += 1;
+                    script.empty_probes = 0;
+                }
+                None
+            }
+        }
+    }
+}
+
+/// How many times to retry a victim whose deque reports contention
+/// (`Stolen::Abort`) before moving on. `Abort` means "try again",
+/// not "empty" -- giving up right away would miss available work and
+/// force an extra full sweep -- but retrying without bound could
+/// spin indefinitely on a heavily contended deque while other
+/// victims have work.
+const ABORT_RETRIES: usize = 32;
+
+/// Takes one job off `stealer`, retrying a bounded number of times
+/// under contention.
+fn steal_one(stealer: &Stealer<JobRef>) -> Option<JobRef> {
+    for _ in 0..ABORT_RETRIES {
+        match stealer.steal() {
+            Stolen::Empty => return None,
+            Stolen::Abort => (), // contention; retry
+            Stolen::Data(v) => return Some(v),
+        }
+    }
+    None
 }
 
 /// ////////////////////////////////////////////////////////////////////////
 
-unsafe fn main_loop(worker: Worker<JobRef>, registry: Arc<Registry>, index: usize) {
+/// How long every worker must remain asleep, while some caller is
+/// blocked on the pool, before the watchdog reports a potential
+/// deadlock.
+const DEADLOCK_WATCHDOG_SECONDS: u64 = 10;
+
+/// Body of the watchdog thread spawned when
+/// `Configuration::deadlock_detection()` is enabled. Once a second,
+/// we check whether every worker is asleep even though some caller is
+/// still blocked waiting on the pool; if that persists for
+/// `DEADLOCK_WATCHDOG_SECONDS`, we log a warning. This is a coarse
+/// heuristic: it cannot see workers that are blocked inside user code
+/// (e.g. on a channel), only workers that have gone to sleep because
+/// they found no work.
+fn deadlock_watchdog(registry: Arc<Registry>) {
+    let mut stuck_since: Option<Instant> = None;
+    let mut reported = false;
+    while !registry.terminate_latch.probe() {
+        thread::sleep(Duration::from_secs(1));
+
+        let num_sleeping = registry.sleep.num_sleeping();
+        let num_waiters = registry.blocked_waiters.load(Ordering::SeqCst);
+        if num_sleeping == registry.num_spawned_threads() && num_waiters > 0 {
+            if stuck_since.is_none() {
+                stuck_since = Some(Instant::now());
+            }
+            let since = stuck_since.unwrap();
+            if !reported && since.elapsed() >= Duration::from_secs(DEADLOCK_WATCHDOG_SECONDS) {
+                let _ = writeln!(&mut stderr(),
+                                 "Rayon: all {} worker threads have been idle for {} seconds, \
+                                  but {} caller(s) are still blocked on the pool; this may \
+                                  indicate a deadlock (e.g., a wait that can never be satisfied)",
+                                 registry.num_threads(),
+                                 DEADLOCK_WATCHDOG_SECONDS,
+                                 num_waiters);
+                reported = true;
+            }
+        } else {
+            stuck_since = None;
+            reported = false;
+        }
+    }
+}
+
+/// Starts one more worker of a lazy pool, if any remain.
+///
+/// Not a public API, but used elsewhere in Rayon.
+pub fn spawn_unspawned_worker(registry: &Arc<Registry>) {
+    // A caller-driven pool never starts workers (see
+    // `Configuration::caller_driven()`).
+    if registry.caller_driven {
+        return;
+    }
+    let unspawned = registry.unspawned.lock().unwrap().pop();
+    if let Some(u) = unspawned {
+        spawn_worker(registry, u);
+    }
+}
+
+/// Starts the worker with the given index if it is parked in the
+/// unspawned list (because the pool is lazy, or was shrunk by
+/// `resize_pool()`); a no-op if that worker is already running.
+/// Needed by operations that reserve work for this one worker (see
+/// `spawn_on()`), which would otherwise wait forever on a worker that
+/// was never going to start.
+///
+/// Not a public API, but used elsewhere in Rayon.
+pub fn ensure_worker_spawned(registry: &Arc<Registry>, index: usize) {
+    // A caller-driven pool never starts workers.
+    if registry.caller_driven {
+        return;
+    }
+    let unspawned = {
+        let mut list = registry.unspawned.lock().unwrap();
+        match list.iter().position(|u| u.index == index) {
+            Some(pos) => Some(list.remove(pos)),
+            None => None,
+        }
+    };
+    if let Some(u) = unspawned {
+        spawn_worker(registry, u);
+    }
+}
+
+/// Starts one parked worker. A spawn failure (resource exhaustion) is
+/// not fatal: the pool keeps running with the threads it already has.
+fn spawn_worker(registry: &Arc<Registry>, u: UnspawnedWorker) {
+    let UnspawnedWorker { index, worker, priority_worker, name, stack_size } = u;
+    let spawned = registry.spawn_thread(ThreadBuilder {
+        name: name,
+        stack_size: stack_size,
+        index: index,
+        worker: worker,
+        priority_worker: priority_worker,
+        registry: registry.clone(),
+    });
+    if spawned.is_ok() {
+        registry.num_spawned.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Starts every remaining worker of a lazy pool. Needed by operations
+/// that reserve work for specific workers (e.g. `broadcast()`), which
+/// would otherwise wait forever on a worker that was never going to
+/// start.
+///
+/// Not a public API, but used elsewhere in Rayon.
+pub fn spawn_all_unspawned(registry: &Arc<Registry>) {
+    // A caller-driven pool never starts workers; without this check
+    // the loop below would never terminate.
+    if registry.caller_driven {
+        return;
+    }
+    while !registry.unspawned.lock().unwrap().is_empty() {
+        spawn_unspawned_worker(registry);
+    }
+}
+
+/// Sets the number of active worker threads in `registry` to
+/// `num_threads`, clamped to between one and the thread count the
+/// pool was created with. The worker deques and their stealer lists
+/// are allocated at pool creation, so a pool can never grow past that
+/// count; to leave headroom for growth, create the pool with a larger
+/// `num_threads()`, possibly combined with `lazy_threads()`.
+///
+/// Growing starts workers from the unspawned list right away.
+/// Shrinking is asynchronous: the highest-indexed active workers are
+/// asked to retire, which they do the next time they look for work --
+/// a worker in the middle of a long job keeps running until that job
+/// completes, and jobs left in a retiring worker's deque are either
+/// finished by the worker itself or remain stealable by the others.
+///
+/// Not a public API, but used elsewhere in Rayon.
+pub fn resize_pool(registry: &Arc<Registry>, num_threads: usize) {
+    let target = cmp::max(1, cmp::min(num_threads, registry.num_threads()));
+
+    // Cancel any retirement still pending from a previous shrink;
+    // who must go is recomputed below.
+    for info in &registry.thread_infos {
+        info.please_exit.store(false, Ordering::SeqCst);
+    }
+
+    // A worker is active unless it is parked in the unspawned list.
+    // This can briefly overcount: a worker that is just completing
+    // its retirement is no longer flagged, but has not reached the
+    // list yet. Resizing is best-effort in the face of such races.
+    let parked: Vec<usize> =
+        registry.unspawned.lock().unwrap().iter().map(|u| u.index).collect();
+    let active: Vec<usize> =
+        (0..registry.num_threads()).filter(|i| !parked.contains(i)).collect();
+
+    if active.len() < target {
+        for _ in active.len()..target {
+            spawn_unspawned_worker(registry);
+        }
+    } else {
+        for &index in &active[target..] {
+            registry.thread_infos[index].please_exit.store(true, Ordering::SeqCst);
+        }
+        // Sleeping workers will not notice the flag on their own.
+        registry.sleep.tickle(usize::MAX);
+    }
+}
+
+/// Sets the number of active worker threads in the global thread pool
+/// to `num_threads`; see `ThreadPool::resize()` for the semantics.
+/// If the global pool has not been initialized yet, it is first
+/// initialized with the default configuration.
+///
+/// Note that the global pool can only grow back up to the thread
+/// count it was initialized with, since worker slots are allocated up
+/// front. A service that wants elasticity should `initialize()` the
+/// pool with the largest count it may ever want, typically with
+/// `lazy_threads()` enabled so that the extra workers are not started
+/// until asked for.
+#[cfg(feature = "unstable")]
+pub fn resize_global_pool(num_threads: usize) {
+    resize_pool(global_registry(), num_threads);
+}
+
+/// Grows a lazy pool by one worker if work was just made available
+/// while every running worker was already busy (nobody asleep to pick
+/// the work up). A no-op for eager pools and saturated lazy pools.
+///
+/// Not a public API, but used elsewhere in Rayon.
+pub fn grow_if_saturated(registry: &Arc<Registry>) {
+    if registry.sleep.num_sleeping() == 0 {
+        spawn_unspawned_worker(registry);
+    }
+}
+
+/// What the worker main loop waits on: a worker leaves its main loop
+/// either because the registry is terminating, or because it has been
+/// asked to retire by `resize_pool()`.
+struct TerminateOrRetire<'a> {
+    registry: &'a Registry,
+    index: usize,
+}
+
+impl<'a> LatchProbe for TerminateOrRetire<'a> {
+    #[inline]
+    fn probe(&self) -> bool {
+        self.registry.terminate_latch.probe() ||
+        self.registry.thread_infos[self.index].please_exit.load(Ordering::SeqCst)
+    }
+}
+
+unsafe fn main_loop(worker: Worker<JobRef>,
+                    priority_worker: Worker<JobRef>,
+                    registry: Arc<Registry>,
+                    index: usize) {
     let worker_thread = WorkerThread {
         worker: worker,
+        priority_worker: priority_worker,
         index: index,
+        sticky_jobs: UnsafeCell::new(Vec::new()),
+        steal_backoff: UnsafeCell::new(vec![0; registry.num_threads()]),
+        injected_turn: Cell::new(false),
         rng: UnsafeCell::new(rand::weak_rng()),
+        #[cfg(feature = "unstable")]
+        fuzz_rng: registry.fuzz_seed.map(|seed| {
+            // XorShift seeds must be nonzero; mix the worker index in
+            // so each worker gets a distinct, replayable stream.
+            UnsafeCell::new(rand::XorShiftRng::from_seed([seed as u32 | 1,
+                                                          (seed >> 32) as u32 | 1,
+                                                          index as u32 + 1,
+                                                          0x9E37_79B9]))
+        }),
+        #[cfg(feature = "unstable")]
+        replay_script: registry.replay_trace.as_ref().map(|trace| {
+            UnsafeCell::new(ReplayScript {
+                victims: trace.records()
+                              .iter()
+                              .filter(|record| record.thief == index)
+                              .map(|record| record.victim)
+                              .collect(),
+                cursor: 0,
+                empty_probes: 0,
+            })
+        }),
+        #[cfg(feature = "unstable")]
+        steal_order: registry.steal_orders.as_ref().map(|orders| orders[index].clone()),
+        #[cfg(feature = "unstable")]
+        join_depth: Cell::new(0),
         registry: registry.clone(),
     };
     WorkerThread::set_current(&worker_thread);
 
+    // The flag is thread-local, so setting it here scopes the
+    // abort-on-panic behavior to this pool's workers.
+    unwind::set_abort_on_user_panic(registry.panic_abort);
+
     // let registry know we are ready to do work
     registry.thread_infos[index].primed.set();
 
@@ -530,6 +3134,14 @@ unsafe fn main_loop(worker: Worker<JobRef>, registry: Arc<Registry>, index: usiz
     // **user code** panics, we should catch that and redirect.
     let abort_guard = unwind::AbortIfPanic;
 
+    // Likewise, the only legitimate ways out of the loop below are
+    // termination of the registry and retirement via `resize_pool()`;
+    // anything else (a future refactor sprouting an early return, the
+    // OS unwinding the thread) would leave the pool hung on latches
+    // this worker was responsible for. Turn such an exit into a loud
+    // abort instead of a silent hang.
+    let exit_guard = unwind::AbortOnUnexpectedExit;
+
     // Inform a user callback that we started a thread.
     if let Some(ref handler) = registry.start_handler {
         let registry = registry.clone();
@@ -542,7 +3154,73 @@ unsafe fn main_loop(worker: Worker<JobRef>, registry: Arc<Registry>, index: usiz
         }
     }
 
-    worker_thread.wait_until(&registry.terminate_latch);
+    let goal = TerminateOrRetire { registry: &registry, index: index };
+    match unwind::halt_unwinding(|| worker_thread.wait_until(&goal)) {
+        Ok(()) => {
+        }
+        Err(err) => {
+            // A panic unwound out of the worker's main loop itself --
+            // this is an internal error, not a user panic, and the
+            // scheduler state may be corrupted, so we must abort. But
+            // first give the panic handler, if any, a chance to leave
+            // a breadcrumb (log the error, flush telemetry, etc).
+            // `handle_panic` guards against the handler itself
+            // panicking; if it returns, we abort ourselves.
+            registry.handle_panic(err);
+            let _ = unwind::AbortIfPanic; // let this drop: abort
+        }
+    }
+
+    if !registry.terminate_latch.probe() {
+        // We were asked to retire (see `resize_pool()`), not to
+        // terminate. Finish anything still sitting in our local
+        // queues -- in particular sticky jobs, which no other worker
+        // can ever execute -- before we go.
+        while let Some(job) = worker_thread.pop() {
+            worker_thread.execute(job);
+        }
+
+        // Hand our deques back to the unspawned list so that a later
+        // grow can restart us. Taking the lock first closes a race
+        // with `terminate()`: once we re-check the latch under the
+        // lock, either termination has not begun and its later drain
+        // of the list will mark us stopped, or it has and we fall
+        // through to a normal stop below.
+        let mut unspawned = registry.unspawned.lock().unwrap();
+        if !registry.terminate_latch.probe() {
+            registry.thread_infos[index].please_exit.store(false, Ordering::SeqCst);
+            registry.num_spawned.fetch_sub(1, Ordering::SeqCst);
+            let name = thread::current().name().map(|s| s.to_string());
+            let stack_size = registry.stack_size;
+            WorkerThread::unset_current();
+            let WorkerThread { worker, priority_worker, .. } = worker_thread;
+            unspawned.push(UnspawnedWorker {
+                index: index,
+                worker: worker,
+                priority_worker: priority_worker,
+                name: name,
+                stack_size: stack_size,
+            });
+            drop(unspawned);
+
+            // Normal (if early) exit, do not abort.
+            mem::forget(abort_guard);
+            mem::forget(exit_guard);
+
+            // Inform a user callback that we exited a thread.
+            if let Some(ref handler) = registry.exit_handler {
+                let registry = registry.clone();
+                match unwind::halt_unwinding(|| handler(index)) {
+                    Ok(()) => {
+                    }
+                    Err(err) => {
+                        registry.handle_panic(err);
+                    }
+                }
+            }
+            return;
+        }
+    }
 
     // Should not be any work left in our queue.
     debug_assert!(worker_thread.pop().is_none());
@@ -552,6 +3230,7 @@ unsafe fn main_loop(worker: Worker<JobRef>, registry: Arc<Registry>, index: usiz
 
     // Normal termination, do not abort.
     mem::forget(abort_guard);
+    mem::forget(exit_guard);
 
     // Inform a user callback that we exited a thread.
     if let Some(ref handler) = registry.exit_handler {
@@ -567,6 +3246,152 @@ unsafe fn main_loop(worker: Worker<JobRef>, registry: Arc<Registry>, index: usiz
     }
 }
 
+/// Returns the minimum split length configured for the pool the
+/// calling thread belongs to -- the global pool's value when called
+/// from outside any pool. This is what the built-in fork-join helpers
+/// fall back to when given a `min_chunk` of zero; it is exposed so
+/// that user-written helpers can agree with them on the threshold.
+pub fn current_min_split_len() -> usize {
+    #[cfg(feature = "unstable")]
+    {
+        let overridden = MIN_SPLIT_LEN_OVERRIDE.with(|c| c.get());
+        if overridden != 0 {
+            return overridden;
+        }
+    }
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        if !worker_thread.is_null() {
+            (*worker_thread).registry().min_split_len()
+        } else {
+            global_registry().min_split_len()
+        }
+    }
+}
+
+#[cfg(feature = "unstable")]
+thread_local! {
+    /// Scoped override of the minimum split length; zero when no
+    /// `with_min_split_len()` call is in progress on this thread.
+    static MIN_SPLIT_LEN_OVERRIDE: Cell<usize> = Cell::new(0)
+}
+
+/// Runs `op` with the minimum split length overridden to `len` on the
+/// current thread: fork-join helpers called within `op` with a
+/// `min_chunk` of zero use `len` as their threshold instead of the
+/// pool-wide default, as does `current_min_split_len()` itself. This
+/// lets different algorithms in one program pick different
+/// granularities without threading the parameter through every
+/// helper call. Values below one are treated as one, like
+/// `Configuration::min_split_len()`. Calls nest: an inner override
+/// shadows the outer one and the previous value is restored on
+/// return, including by panic.
+///
+/// The override is per *thread*, but that suffices for the built-in
+/// helpers: each resolves its threshold once on entry, on the calling
+/// thread, and hands the resolved value down through the recursion,
+/// so the whole computation honors the override no matter which
+/// workers end up running its pieces. What the override does **not**
+/// reach is a fresh helper call made from inside the parallel region
+/// -- say, from a `map` closure -- since that call re-resolves its
+/// threshold on whichever worker executes it.
+#[cfg(feature = "unstable")]
+pub fn with_min_split_len<R, OP>(len: usize, op: OP) -> R
+    where OP: FnOnce() -> R
+{
+    let previous = MIN_SPLIT_LEN_OVERRIDE.with(|c| c.replace(cmp::max(len, 1)));
+    let _guard = MinSplitLenOverrideGuard { previous: previous };
+    op()
+}
+
+/// Restores the enclosing override (or lack of one) when a
+/// `with_min_split_len()` scope is left, by return or by panic.
+#[cfg(feature = "unstable")]
+struct MinSplitLenOverrideGuard {
+    previous: usize,
+}
+
+#[cfg(feature = "unstable")]
+impl Drop for MinSplitLenOverrideGuard {
+    fn drop(&mut self) {
+        MIN_SPLIT_LEN_OVERRIDE.with(|c| c.set(self.previous));
+    }
+}
+
+/// Blocks until every latch in `latches` has been set. On a worker
+/// thread this waits cooperatively, executing other pool work until
+/// the condition holds, just like the wait at the end of a `join()`;
+/// on any other thread it simply blocks on each latch in turn.
+pub fn wait_all(latches: &[&LockLatch]) {
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        if !worker_thread.is_null() {
+            (*worker_thread).wait_until(&AllLatches::new(latches));
+        } else {
+            for latch in latches {
+                latch.wait();
+            }
+        }
+    }
+}
+
+/// Blocks until at least one latch in `latches` has been set, and
+/// returns the index of the lowest set latch. On a worker thread this
+/// waits cooperatively, as with `wait_all()`. On other threads there
+/// is no single condvar covering all the latches, so the wait
+/// degrades to a probe-and-yield loop.
+///
+/// Panics if `latches` is empty, since that wait could never end.
+pub fn wait_any(latches: &[&LockLatch]) -> usize {
+    assert!(!latches.is_empty(), "wait_any() requires at least one latch");
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        let probe = AnyLatches::new(latches);
+        if !worker_thread.is_null() {
+            (*worker_thread).wait_until(&probe);
+        } else {
+            while !probe.probe() {
+                thread::yield_now();
+            }
+        }
+    }
+    latches.iter().position(|l| l.probe()).unwrap()
+}
+
+/// Runs `f` with mutable access to the current worker thread's
+/// random number generator and returns its result, or returns `None`
+/// when called from outside the pool. Monte Carlo and other
+/// randomized jobs often want a fast per-thread RNG; every worker
+/// already carries one for steal-victim selection, and borrowing it
+/// beats allocating a parallel set of thread-locals.
+///
+/// The generator is *weak*: fast and statistically decent, but in no
+/// way cryptographic, and its stream is shared with the scheduler's
+/// own draws. Each worker's stream is independent, but which worker
+/// runs a given job -- and where in its stream that worker happens
+/// to be -- depends on scheduling, so results built from these draws
+/// are not reproducible run to run.
+#[cfg(feature = "unstable")]
+pub fn with_worker_rng<F, R>(f: F) -> Option<R>
+    where F: FnOnce(&mut rand::XorShiftRng) -> R
+{
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        if worker_thread.is_null() {
+            return None;
+        }
+        // Move the generator out for the duration of the call: if
+        // `f` re-enters the pool (a `join()`, say) and this worker
+        // steals, the steal path draws from `rng` too, and handing
+        // `f` a direct `&mut` into the cell would alias that access.
+        let cell = &(*worker_thread).rng;
+        let mut rng = (*cell.get()).clone();
+        let result = f(&mut rng);
+        *cell.get() = rng;
+        Some(result)
+    }
+}
+
 /// If already in a worker-thread, just execute `op`.  Otherwise,
 /// execute `op` in the default thread-pool. Either way, block until
 /// `op` completes and return its return value. If `op` panics, that
@@ -587,14 +3412,106 @@ pub fn in_worker<OP, R>(op: OP) -> R
     }
 }
 
+/// Runs `op` on the global thread-pool and blocks until it completes,
+/// returning its result; called from inside a pool, `op` simply runs
+/// on the current worker. If `allow_inline` is true and the global
+/// pool looks saturated (see `Registry::is_saturated()`), `op` is
+/// instead executed directly on the calling thread: for a tiny
+/// "submit one thing and wait" job under load, the lock, wakeup, and
+/// steal of an injection cost more than the job itself, and the
+/// caller was going to block for the result anyway.
+///
+/// Because `op` may run on the calling thread, this entry point
+/// deliberately does not hand it a `&WorkerThread`: operations that
+/// rely on worker-local state must go through `in_worker()`, which
+/// always runs them on a real worker.
+///
+/// Saturation is a snapshot hint, so the inline path is best-effort:
+/// under a racing load the job may still be injected even though the
+/// pool was momentarily full, or run inline even though a worker was
+/// about to free up.
+#[cfg(feature = "unstable")]
+pub fn inject_or_run_inline<OP, R>(allow_inline: bool, op: OP) -> R
+    where OP: FnOnce() -> R + Send, R: Send
+{
+    unsafe {
+        if !WorkerThread::current().is_null() {
+            return op();
+        }
+        if allow_inline && global_registry().is_saturated() {
+            return op();
+        }
+        in_worker_cold(move |_| op())
+    }
+}
+
+/// Runs every job currently queued on the calling worker -- its
+/// high-priority, sticky, and regular deques -- to completion before
+/// returning: a local barrier for staged pipelines, where a worker
+/// must see the side effects of everything it spawned in phase N
+/// before starting phase N+1. Jobs spawned *by* the drained jobs are
+/// drained in turn, so the worker's queues are empty when this
+/// returns.
+///
+/// Only this worker's own queues are touched: jobs that other workers
+/// have already stolen are no longer local and are not waited for
+/// (use `scope()` when completion of stolen work matters), and no
+/// injected or targeted work is pulled in. A no-op when called from
+/// outside the pool, where there are no local queues to drain.
+#[cfg(feature = "unstable")]
+pub fn drain_local_spawned() {
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        if worker_thread.is_null() {
+            return;
+        }
+        let worker_thread = &*worker_thread;
+        while let Some(job) = worker_thread.pop() {
+            worker_thread.execute(job);
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static IN_WORKER_COLD_CALLS: Cell<usize> = Cell::new(0)
+}
+
+#[cfg(test)]
+thread_local! {
+    static STEAL_SWEEPS: Cell<usize> = Cell::new(0)
+}
+
+/// Number of steal sweeps (`steal_from` calls) the current worker
+/// thread has performed. Used by tests to check that a
+/// single-threaded pool never attempts to steal.
+#[cfg(test)]
+pub fn steal_sweeps() -> usize {
+    STEAL_SWEEPS.with(|c| c.get())
+}
+
+/// Number of times `in_worker_cold` has run on the current thread.
+/// Used by tests to check that nested `scope`/`join` calls stay on
+/// the fast path and never re-enter the pool from the outside.
+#[cfg(test)]
+pub fn in_worker_cold_calls() -> usize {
+    IN_WORKER_COLD_CALLS.with(|c| c.get())
+}
+
 #[cold]
 unsafe fn in_worker_cold<OP, R>(op: OP) -> R
     where OP: FnOnce(&WorkerThread) -> R + Send, R: Send
 {
     // never run from a worker thread; just shifts over into worker threads
     debug_assert!(WorkerThread::current().is_null());
+    #[cfg(test)]
+    IN_WORKER_COLD_CALLS.with(|c| c.set(c.get() + 1));
+    let registry = global_registry();
     let job = StackJob::new(|| in_worker(op), LockLatch::new());
-    global_registry().inject(&[job.as_job_ref()]);
+    registry.inject(&[job.as_job_ref()]);
+    grow_if_saturated(registry);
+    registry.mark_blocked_waiter();
     job.latch.wait();
+    registry.unmark_blocked_waiter();
     job.into_result()
 }
@@ -1,5 +1,7 @@
 #[cfg(feature = "unstable")]
 use future::{self, Future, RayonFuture};
+#[cfg(feature = "unstable")]
+use join::{fn_context, FnContext};
 use latch::{Latch, CountLatch};
 use log::Event::*;
 use job::HeapJob;
@@ -9,6 +11,7 @@ use std::mem;
 use std::ptr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicPtr, Ordering};
+use std::time::Instant;
 use registry::{in_worker, Registry, WorkerThread};
 use unwind;
 
@@ -28,6 +31,16 @@ pub struct Scope<'scope> {
     /// latch to set when the counter drops to zero (and hence this scope is complete)
     job_completed_latch: CountLatch,
 
+    /// if true, spawned jobs that have not yet started by the time
+    /// some other job of this scope panics are dropped (running only
+    /// the destructors of their captures) instead of executed; see
+    /// `scope_abort_on_panic()`
+    abort_pending_on_panic: bool,
+
+    /// if set, spawned jobs that have not yet started by this instant
+    /// are dropped instead of executed; see `scope_with_deadline()`
+    deadline: Option<Instant>,
+
     /// you can think of a scope as containing a list of closures to
     /// execute, all of which outlive `'scope`
     marker: PhantomData<Box<FnOnce(&Scope<'scope>) + 'scope>>,
@@ -244,6 +257,55 @@ pub struct Scope<'scope> {
 /// propagated at that point.
 pub fn scope<'scope, OP, R>(op: OP) -> R
     where OP: for<'s> FnOnce(&'s Scope<'scope>) -> R + 'scope + Send, R: Send,
+{
+    scope_internal(false, None, op)
+}
+
+/// Like `scope()`, except that when any job of the scope panics,
+/// spawned jobs that have not yet *started* executing are dropped --
+/// running the destructors of their captures, but not their bodies --
+/// rather than executed. The scope still waits for jobs that are
+/// already running, and still propagates the first panic once all
+/// jobs are accounted for.
+///
+/// This makes early-exit semantics explicit for cancellable
+/// pipelines: with plain `scope()`, all spawned tasks run even if the
+/// spawning task panics, which is the right default for correctness
+/// but can waste a lot of work when the panic means the overall
+/// result is no longer needed.
+#[cfg(feature = "unstable")]
+pub fn scope_abort_on_panic<'scope, OP, R>(op: OP) -> R
+    where OP: for<'s> FnOnce(&'s Scope<'scope>) -> R + 'scope + Send, R: Send,
+{
+    scope_internal(true, None, op)
+}
+
+/// Like `scope()`, except that the scope's work is subject to a hard
+/// time budget: once `deadline` passes, spawned jobs that have not
+/// yet *started* executing are dropped -- running the destructors of
+/// their captures, but not their bodies -- rather than executed. Jobs
+/// that are already running when the deadline passes are not
+/// interrupted; the scope returns as soon as they (and the drops of
+/// the skipped jobs) have drained.
+///
+/// This is the cancellation mechanism of `scope_abort_on_panic()`
+/// keyed on the clock rather than on a panic, and it is useful for
+/// frame-budgeted rendering or request-deadline pipelines where work
+/// past the deadline is wasted. Note that a deadline in the past
+/// means no spawned job runs at all (the closure passed to
+/// `scope_with_deadline()` itself still runs).
+#[cfg(feature = "unstable")]
+pub fn scope_with_deadline<'scope, OP, R>(deadline: Instant, op: OP) -> R
+    where OP: for<'s> FnOnce(&'s Scope<'scope>) -> R + 'scope + Send, R: Send,
+{
+    scope_internal(false, Some(deadline), op)
+}
+
+fn scope_internal<'scope, OP, R>(abort_pending_on_panic: bool,
+                                 deadline: Option<Instant>,
+                                 op: OP)
+                                 -> R
+    where OP: for<'s> FnOnce(&'s Scope<'scope>) -> R + 'scope + Send, R: Send,
 {
     in_worker(|owner_thread| {
         unsafe {
@@ -251,6 +313,8 @@ pub fn scope<'scope, OP, R>(op: OP) -> R
                 owner_thread: owner_thread as *const WorkerThread as *mut WorkerThread,
                 panic: AtomicPtr::new(ptr::null_mut()),
                 job_completed_latch: CountLatch::new(),
+                abort_pending_on_panic: abort_pending_on_panic,
+                deadline: deadline,
                 marker: PhantomData,
             };
             let result = scope.execute_job_closure(op);
@@ -260,12 +324,185 @@ pub fn scope<'scope, OP, R>(op: OP) -> R
     })
 }
 
+/// Like `scope()`, except that every spawned job runs on the one
+/// worker thread that executes the scope, and the spawn closures are
+/// therefore *not* required to be `Send`: per-task scratch such as
+/// `Rc` or `Cell` data created inside the scope body can be moved
+/// into the spawned jobs, because no job ever crosses a thread
+/// boundary. The jobs are pinned with the sticky mechanism (see
+/// `Scope::spawn_sticky()`).
+///
+/// The scope body itself must still be `Send` -- when `local_scope()`
+/// is called from outside the pool, the body is shipped to a worker
+/// -- so `!Send` data cannot be smuggled in from outside; it has to
+/// be created inside the body (see the compile-fail test
+/// `local_scope_smuggle.rs`).
+///
+/// Note what is given up: with every job confined to one worker, the
+/// jobs run serially, so this is a structured-concurrency shape (and
+/// an interleaving with *other* pool work while the scope waits), not
+/// a source of parallelism. Use plain `scope()` for work that should
+/// actually fan out.
+///
+/// # Panics
+///
+/// Panics in the body or in spawned jobs propagate exactly as with
+/// `scope()`.
+#[cfg(feature = "unstable")]
+pub fn local_scope<'scope, OP, R>(op: OP) -> R
+    where OP: for<'s> FnOnce(&'s LocalScope<'scope>) -> R + 'scope + Send, R: Send,
+{
+    in_worker(|owner_thread| {
+        unsafe {
+            let scope = LocalScope {
+                scope: Scope {
+                    owner_thread: owner_thread as *const WorkerThread as *mut WorkerThread,
+                    panic: AtomicPtr::new(ptr::null_mut()),
+                    job_completed_latch: CountLatch::new(),
+                    abort_pending_on_panic: false,
+                    deadline: None,
+                    marker: PhantomData,
+                },
+            };
+            let result = scope.execute_job_closure(op);
+            scope.scope.steal_till_jobs_complete();
+            result.unwrap() // only None if `op` panicked, and that would have been propagated
+        }
+    })
+}
+
+/// A scope whose spawned jobs all run on the worker thread that
+/// created it, so that they may capture `!Send` data; see
+/// `local_scope()`.
+#[cfg(feature = "unstable")]
+pub struct LocalScope<'scope> {
+    scope: Scope<'scope>,
+}
+
+#[cfg(feature = "unstable")]
+impl<'scope> LocalScope<'scope> {
+    /// Spawns a job into the local scope. As with `Scope::spawn()`,
+    /// the job executes sometime before the scope completes and may
+    /// spawn further jobs through its own reference to the scope; but
+    /// it always executes on the worker that owns the scope, which is
+    /// what makes it sound to accept a non-`Send` closure here.
+    pub fn spawn<BODY>(&self, body: BODY)
+        where BODY: FnOnce(&LocalScope<'scope>) + 'scope
+    {
+        unsafe {
+            self.scope.job_completed_latch.increment();
+            let job_ref = Box::new(HeapJob::new(move || self.execute_job(body)))
+                .as_job_ref();
+            let worker_thread = WorkerThread::current();
+
+            // the `LocalScope` is not send or sync, and we only give
+            // out pointers to it from within a worker thread
+            debug_assert!(!WorkerThread::current().is_null());
+
+            // A sticky job never leaves this worker's sticky queue,
+            // so `body` (which need not be `Send`) never crosses a
+            // thread boundary.
+            let worker_thread = &*worker_thread;
+            worker_thread.push_sticky(job_ref);
+        }
+    }
+
+    /// Executes `func` as a local job; compare `Scope::execute_job()`.
+    /// There is no abort-on-panic or deadline handling here because
+    /// local scopes have neither.
+    ///
+    /// Unsafe because it must be executed on a worker thread.
+    unsafe fn execute_job<FUNC>(&self, func: FUNC)
+        where FUNC: FnOnce(&LocalScope<'scope>) + 'scope
+    {
+        let _: Option<()> = self.execute_job_closure(func);
+    }
+
+    /// Executes `func` under the inner scope's panic-capture and
+    /// completion protocol, handing it this `LocalScope` instead of
+    /// the inner `Scope`; compare `Scope::execute_job_closure()`.
+    ///
+    /// Unsafe because this must be executed on a worker thread.
+    unsafe fn execute_job_closure<FUNC, R>(&self, func: FUNC) -> Option<R>
+        where FUNC: FnOnce(&LocalScope<'scope>) -> R + 'scope
+    {
+        match unwind::halt_unwinding(move || func(self)) {
+            Ok(r) => { self.scope.job_completed_ok(); Some(r) }
+            Err(err) => { self.scope.job_panicked(err); None }
+        }
+    }
+}
+
+/// Fans out `count` tasks in a scope, one per index in `0..count`,
+/// and collects their results into a `Vec<T>` ordered by index. This
+/// is the common "compute each slot in parallel" pattern, which
+/// otherwise requires preallocating a vector and writing into it with
+/// unsafe code or a mutex.
+///
+/// Each spawned task writes into its own slot; the scope does not end
+/// until every task has completed, so no slot is ever read while it
+/// may still be written.
+///
+/// # Panics
+///
+/// If `op` should panic for any index, that panic is propagated, as
+/// with `scope()`.
+#[cfg(feature = "unstable")]
+pub fn scope_collect<T, OP>(count: usize, op: OP) -> Vec<T>
+    where T: Send,
+          OP: Fn(&Scope, usize) -> T + Sync
+{
+    let slots: Vec<Slot<T>> = (0..count).map(|_| Slot::new()).collect();
+    {
+        let slots = &slots;
+        let op = &op;
+        scope(|s| for index in 0..count {
+            s.spawn(move |s| unsafe {
+                // Only this task writes slot `index`, and nothing
+                // reads it until the scope has closed.
+                *slots[index].0.get() = Some(op(s, index));
+            });
+        });
+    }
+    slots.into_iter()
+         .map(|slot| {
+             let value = unsafe { slot.0.into_inner() };
+             value.expect("scope closed with an empty slot")
+         })
+         .collect()
+}
+
+/// One result slot of `scope_collect()`.
+#[cfg(feature = "unstable")]
+struct Slot<T>(::std::cell::UnsafeCell<Option<T>>);
+
+#[cfg(feature = "unstable")]
+impl<T> Slot<T> {
+    fn new() -> Slot<T> {
+        Slot(::std::cell::UnsafeCell::new(None))
+    }
+}
+
+/// The spawned tasks only share a `Slot` in the disjoint-write
+/// pattern described on `scope_collect()`, so it is safe to let
+/// references to it cross threads.
+#[cfg(feature = "unstable")]
+unsafe impl<T: Send> Sync for Slot<T> {}
+
 impl<'scope> Scope<'scope> {
     /// Spawns a job into the fork-join scope `self`. This job will
     /// execute sometime before the fork-join scope completes.  The
     /// job is specified as a closure, and this closure receives its
     /// own reference to `self` as argument. This can be used to
     /// inject new jobs into `self`.
+    ///
+    /// Note that the closure is only required to outlive `'scope`,
+    /// not `'static`: it may freely borrow stack data from outside
+    /// the `scope()` call, because `scope()` does not return until
+    /// every spawned job has completed. The borrow checker enforces
+    /// the other direction -- a spawned closure cannot capture
+    /// anything that may die before the scope ends (see the
+    /// compile-fail tests `scope_join_bad.rs` and `scope_escape.rs`).
     pub fn spawn<BODY>(&self, body: BODY)
         where BODY: FnOnce(&Scope<'scope>) + 'scope
     {
@@ -284,6 +521,74 @@ impl<'scope> Scope<'scope> {
         }
     }
 
+    /// Like `spawn()`, except that the closure additionally receives
+    /// an `FnContext` reporting whether it was *migrated*: stolen and
+    /// run on a different worker than the one that spawned it (see
+    /// `FnContext::migrated()`). A job popped back off the spawning
+    /// worker's own deque -- typically in the scope's epilogue --
+    /// reports not-migrated. This gives scope-based parallel loops
+    /// the same adaptive-granularity signal that `join_context()`
+    /// gives fork-join code.
+    #[cfg(feature = "unstable")]
+    pub fn spawn_context<BODY>(&self, body: BODY)
+        where BODY: FnOnce(&Scope<'scope>, FnContext) + 'scope
+    {
+        // As in `join_context()`, the spawning worker's address is
+        // the identity we compare against at execution time.
+        let spawner = WorkerThread::current() as usize;
+        self.spawn(move |s| {
+            let migrated = WorkerThread::current() as usize != spawner;
+            body(s, fn_context(migrated))
+        });
+    }
+
+    /// Like `spawn()`, but the job is pinned to the current worker
+    /// thread: it will never be stolen by other workers. See
+    /// `spawn_sticky()` for more details, including a warning about
+    /// starvation.
+    #[cfg(feature = "unstable")]
+    pub fn spawn_sticky<BODY>(&self, body: BODY)
+        where BODY: FnOnce(&Scope<'scope>) + 'scope
+    {
+        unsafe {
+            self.job_completed_latch.increment();
+            let job_ref = Box::new(HeapJob::new(move || self.execute_job(body)))
+                .as_job_ref();
+            let worker_thread = WorkerThread::current();
+
+            // the `Scope` is not send or sync, and we only give out
+            // pointers to it from within a worker thread
+            debug_assert!(!WorkerThread::current().is_null());
+
+            let worker_thread = &*worker_thread;
+            worker_thread.push_sticky(job_ref);
+        }
+    }
+
+    /// Like `spawn()`, but the job is pushed onto the current
+    /// worker's high-priority deque, so that it is executed (and
+    /// stolen) in preference to regularly spawned jobs. See
+    /// `spawn_priority()` for more details, including a warning about
+    /// starvation.
+    #[cfg(feature = "unstable")]
+    pub fn spawn_priority<BODY>(&self, body: BODY)
+        where BODY: FnOnce(&Scope<'scope>) + 'scope
+    {
+        unsafe {
+            self.job_completed_latch.increment();
+            let job_ref = Box::new(HeapJob::new(move || self.execute_job(body)))
+                .as_job_ref();
+            let worker_thread = WorkerThread::current();
+
+            // the `Scope` is not send or sync, and we only give out
+            // pointers to it from within a worker thread
+            debug_assert!(!WorkerThread::current().is_null());
+
+            let worker_thread = &*worker_thread;
+            worker_thread.push_priority(job_ref);
+        }
+    }
+
     #[cfg(feature = "unstable")]
     pub fn spawn_future<F>(&self, future: F) -> RayonFuture<F::Item, F::Error>
         where F: Future + Send + 'scope
@@ -340,9 +645,59 @@ impl<'scope> Scope<'scope> {
     unsafe fn execute_job<FUNC>(&self, func: FUNC)
         where FUNC: FnOnce(&Scope<'scope>) + 'scope
     {
+        let panicked_and_aborting = self.abort_pending_on_panic &&
+                                    !self.panic.load(Ordering::Acquire).is_null();
+        if panicked_and_aborting || self.past_deadline() {
+            // Either some sibling job has already panicked and this
+            // scope aborts pending work, or the scope's deadline has
+            // passed: drop the closure (running the destructors of
+            // anything it captured) without executing its body.
+            if (*self.owner_thread).registry().offload_aborted_drops() {
+                self.drop_aborted_off_thread(func);
+            } else {
+                mem::drop(func);
+                self.job_completed_ok();
+            }
+            return;
+        }
         let _: Option<()> = self.execute_job_closure(func);
     }
 
+    /// True if this scope has a deadline (see `scope_with_deadline()`)
+    /// and it has already passed.
+    fn past_deadline(&self) -> bool {
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    /// Hands an aborted closure to the shared cleanup thread instead
+    /// of dropping it on this worker (see
+    /// `Configuration::offload_aborted_drops()`). The scope is only
+    /// marked complete from *inside* the handed-off closure, after
+    /// the drop; `scope()` therefore keeps waiting -- and everything
+    /// the closure borrows stays valid -- until the destructors have
+    /// actually finished, even though this worker moves on to its
+    /// next job immediately.
+    unsafe fn drop_aborted_off_thread<FUNC>(&self, func: FUNC)
+        where FUNC: FnOnce(&Scope<'scope>) + 'scope
+    {
+        let this: *const Scope<'scope> = self;
+        let job: Box<FnOnce() + 'scope> = Box::new(move || {
+            mem::drop(func);
+            unsafe {
+                (*this).job_completed_ok();
+            }
+        });
+        // Erase the lifetime and assert sendability: the completion
+        // protocol above keeps `'scope` alive until the closure has
+        // run, and scope jobs move between threads as a matter of
+        // course anyway (see `JobRef`).
+        let job: Box<FnOnce() + Send> = mem::transmute(job);
+        ::cleanup::run_off_thread(job);
+    }
+
     /// Executes `func` as a job in scope. Adjusts the "job completed"
     /// counters and also catches any panic and stores it into
     /// `scope`.
@@ -377,6 +732,12 @@ impl<'scope> Scope<'scope> {
         self.job_completed_latch.set();
     }
 
+    /// True once every job spawned into this scope has completed.
+    fn all_jobs_complete(&self) -> bool {
+        use latch::LatchProbe;
+        self.job_completed_latch.probe()
+    }
+
     unsafe fn steal_till_jobs_complete(&self) {
         // wait for job counter to reach 0:
         (*self.owner_thread).wait_until(&self.job_completed_latch);
@@ -394,3 +755,33 @@ impl<'scope> Scope<'scope> {
         }
     }
 }
+
+impl<'scope> Drop for Scope<'scope> {
+    /// The scope's soundness invariant is that no spawned job
+    /// outlives the stack data it borrows, which `scope()` upholds by
+    /// draining all jobs in `steal_till_jobs_complete()` -- including
+    /// when the scope body panics, since `execute_job_closure()`
+    /// catches the panic first. This drop impl is the last line of
+    /// defense: should any exit path ever unwind past that drain, we
+    /// block here, cooperatively executing scope jobs, until every
+    /// outstanding job has completed -- and only then let the unwind
+    /// continue and free the borrowed data. On the normal path the
+    /// latch is already set and this is a cheap probe.
+    fn drop(&mut self) {
+        if !self.all_jobs_complete() {
+            unsafe {
+                (*self.owner_thread).wait_until(&self.job_completed_latch);
+            }
+        }
+
+        // A panic recorded but never propagated means we are on the
+        // defensive path above; free the payload rather than leak it.
+        let panic = self.panic.swap(ptr::null_mut(), Ordering::Relaxed);
+        if !panic.is_null() {
+            unsafe {
+                let value: Box<Box<Any + Send + 'static>> = mem::transmute(panic);
+                mem::drop(value);
+            }
+        }
+    }
+}
@@ -1,6 +1,14 @@
 use Configuration;
 use {scope, Scope};
+#[cfg(feature = "unstable")]
+use scope_abort_on_panic;
+#[cfg(feature = "unstable")]
+use scope_collect;
+#[cfg(feature = "unstable")]
+use scope_with_deadline;
 use ThreadPool;
+use join::join;
+use registry;
 use rand::{Rng, SeedableRng, XorShiftRng};
 use std::cmp;
 use std::iter::once;
@@ -136,6 +144,32 @@ fn update_tree() {
     }
 }
 
+/// Nested `scope`/`join` calls made from within the pool must reuse
+/// the current worker thread directly (the fast `in_worker` path)
+/// rather than allocating a job and injecting it from the outside.
+#[test]
+fn nested_scopes_use_fast_path() {
+    // one thread, so that everything (including stolen-back jobs)
+    // runs on the same worker and we can read its thread-local counter
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    pool.install(|| {
+        let baseline = registry::in_worker_cold_calls();
+        join(|| {
+                 scope(|s| {
+                     s.spawn(|_| {
+                         scope(|t| {
+                             t.spawn(|_| join(|| (), || ()).0);
+                         });
+                     });
+                 });
+             },
+             || ());
+        assert_eq!(registry::in_worker_cold_calls(),
+                   baseline,
+                   "nested scope/join re-entered the pool through in_worker_cold");
+    });
+}
+
 /// Check that if you have a chain of scoped tasks where T0 spawns T1
 /// spawns T2 and so forth down to Tn, the stack space should not grow
 /// linearly with N. We test this by some unsafe hackery and
@@ -201,6 +235,106 @@ fn panic_propagate_nested_scope_spawn() {
     scope(|s| s.spawn(|_| scope(|s| s.spawn(|_| panic!("Hello, world!")))));
 }
 
+/// With a single worker, execution is serial and fully deterministic:
+/// the `b` of a `join` runs after `a`, spawned tasks run in a fixed
+/// (last-in, first-out) order at scope close, and no steal sweep is
+/// ever attempted since there is no one to steal from.
+#[test]
+fn single_thread_deterministic_order() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    pool.install(|| {
+        let baseline = registry::steal_sweeps();
+
+        let log = Mutex::new(vec![]);
+        join(|| log.lock().unwrap().push("a"),
+             || log.lock().unwrap().push("b"));
+        assert_eq!(*log.lock().unwrap(), vec!["a", "b"]);
+
+        let order = Mutex::new(vec![]);
+        scope(|s| for i in 0..10 {
+                  let order = &order;
+                  s.spawn(move |_| order.lock().unwrap().push(i));
+              });
+        let expected: Vec<usize> = (0..10).rev().collect();
+        assert_eq!(*order.lock().unwrap(), expected);
+
+        assert_eq!(registry::steal_sweeps(),
+                   baseline,
+                   "single-threaded pool attempted to steal");
+    });
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn scope_collect_ordered() {
+    let v = scope_collect(128, |_, i| i * 2);
+    let expected: Vec<usize> = (0..128).map(|i| i * 2).collect();
+    assert_eq!(v, expected);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn scope_collect_empty() {
+    let v: Vec<usize> = scope_collect(0, |_, i| i);
+    assert!(v.is_empty());
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+#[should_panic(expected = "Hello, world!")]
+fn scope_collect_panic_propagates() {
+    scope_collect(10, |_, i| if i == 5 {
+        panic!("Hello, world!");
+    } else {
+        i
+    });
+}
+
+/// On a single worker, a high-priority spawn must run before the
+/// regularly spawned jobs that were pushed ahead of it.
+#[test]
+#[cfg(feature = "unstable")]
+fn spawn_priority_runs_before_regular_spawns() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    pool.install(|| {
+        let order = Mutex::new(vec![]);
+        scope(|s| {
+            let order = &order;
+            for _ in 0..5 {
+                s.spawn(move |_| order.lock().unwrap().push("regular"));
+            }
+            s.spawn_priority(move |_| order.lock().unwrap().push("priority"));
+        });
+        assert_eq!(order.lock().unwrap()[0], "priority");
+    });
+}
+
+/// Once a panic from one spawned job has been propagated, the worker
+/// that unwound must be left in a consistent state and able to keep
+/// serving the pool.
+#[test]
+fn panic_then_pool_still_functions() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let result = unwind::halt_unwinding(|| {
+        pool.install(|| {
+            scope(|s| {
+                for _ in 0..5 {
+                    s.spawn(|_| ());
+                }
+                s.spawn(|_| panic!("Hello, world!"));
+                for _ in 0..5 {
+                    s.spawn(|_| ());
+                }
+            })
+        })
+    });
+    assert!(result.is_err(), "failed to propagate panic");
+
+    // the pool must still be able to run new work normally
+    let x = pool.install(|| 22);
+    assert_eq!(x, 22);
+}
+
 #[test]
 fn panic_propagate_still_execute_1() {
     let mut x = false;
@@ -256,3 +390,313 @@ fn panic_propagate_still_execute_4() {
         Err(_) => assert!(x, "panic in spawn tainted scope"),
     }
 }
+
+#[test]
+#[cfg(feature = "unstable")]
+fn abort_on_panic_drops_pending_jobs() {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    struct DropCounter(Arc<AtomicUsize>);
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let executed = Arc::new(AtomicUsize::new(0));
+    let dropped = Arc::new(AtomicUsize::new(0));
+
+    // On one thread the spawned jobs can only start after the scope
+    // body has returned, so the panic is already recorded when they
+    // come up, and all of them must be aborted.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let result = unwind::halt_unwinding(|| {
+        pool.install(|| {
+            scope_abort_on_panic(|s| {
+                for _ in 0..10 {
+                    let executed = executed.clone();
+                    let counter = DropCounter(dropped.clone());
+                    s.spawn(move |_| {
+                        let _ = &counter;
+                        executed.fetch_add(1, Ordering::SeqCst);
+                    });
+                }
+                panic!("Hello, world!");
+            });
+        })
+    });
+
+    assert!(result.is_err(), "panic should still propagate");
+    assert_eq!(executed.load(Ordering::SeqCst), 0);
+    assert_eq!(dropped.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn abort_on_panic_offloads_drops() {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    struct DropRecorder {
+        dropped: Arc<AtomicUsize>,
+        off_worker: Arc<AtomicUsize>,
+    }
+    impl Drop for DropRecorder {
+        fn drop(&mut self) {
+            // The destructors of aborted jobs must run on the shared
+            // cleanup thread, not on a pool worker.
+            if registry::WorkerThread::current().is_null() {
+                self.off_worker.fetch_add(1, Ordering::SeqCst);
+            }
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let off_worker = Arc::new(AtomicUsize::new(0));
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(1)
+            .offload_aborted_drops(true))
+        .unwrap();
+    let result = unwind::halt_unwinding(|| {
+        pool.install(|| {
+            scope_abort_on_panic(|s| {
+                for _ in 0..10 {
+                    let recorder = DropRecorder {
+                        dropped: dropped.clone(),
+                        off_worker: off_worker.clone(),
+                    };
+                    s.spawn(move |_| {
+                        let _ = &recorder;
+                    });
+                }
+                panic!("Hello, world!");
+            });
+        })
+    });
+
+    assert!(result.is_err(), "panic should still propagate");
+    // The scope does not complete before the off-loaded destructors
+    // have run, so by now every one of them has.
+    assert_eq!(dropped.load(Ordering::SeqCst), 10);
+    assert_eq!(off_worker.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn abort_on_panic_runs_all_without_panic() {
+    let counter = AtomicUsize::new(0);
+    scope_abort_on_panic(|s| for _ in 0..10 {
+                             s.spawn(|_| { counter.fetch_add(1, Ordering::SeqCst); });
+                         });
+    assert_eq!(counter.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn deadline_in_the_past_skips_spawned_jobs() {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{Duration, Instant};
+
+    struct DropCounter(Arc<AtomicUsize>);
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let executed = Arc::new(AtomicUsize::new(0));
+    let dropped = Arc::new(AtomicUsize::new(0));
+
+    // The deadline has already passed when the jobs come up, so none
+    // of them may run; their captures must still be dropped, and the
+    // scope must still return normally.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    pool.install(|| {
+        scope_with_deadline(Instant::now() - Duration::from_millis(1), |s| {
+            for _ in 0..10 {
+                let executed = executed.clone();
+                let counter = DropCounter(dropped.clone());
+                s.spawn(move |_| {
+                    let _ = &counter;
+                    executed.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+    });
+
+    assert_eq!(executed.load(Ordering::SeqCst), 0);
+    assert_eq!(dropped.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn generous_deadline_runs_all_jobs() {
+    use std::time::{Duration, Instant};
+
+    let counter = AtomicUsize::new(0);
+    scope_with_deadline(Instant::now() + Duration::from_secs(3600),
+                        |s| for _ in 0..10 {
+                            s.spawn(|_| { counter.fetch_add(1, Ordering::SeqCst); });
+                        });
+    assert_eq!(counter.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn panic_in_body_waits_for_stolen_task() {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+    use std::time::Duration;
+
+    // The core safety invariant of `scope()` under panic: the scope
+    // must not return (and hence free the stack data spawned jobs
+    // borrow) until every outstanding job -- here one that has been
+    // stolen and is running on another worker -- has completed.
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let log = Mutex::new(Vec::new());
+    let started = AtomicBool::new(false);
+    let result = pool.install(|| {
+        unwind::halt_unwinding(|| {
+            scope(|s| {
+                s.spawn(|_| {
+                    started.store(true, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    log.lock().unwrap().push("spawned job ran");
+                });
+                // Spinning here keeps the spawned job out of our own
+                // deque, so the other worker must steal it; panic
+                // only once it is genuinely outstanding.
+                while !started.load(Ordering::SeqCst) {
+                    thread::yield_now();
+                }
+                panic!("Hello, world!");
+            });
+        })
+    });
+    assert!(result.is_err(), "panic in scope body was not propagated");
+    // The borrowed `log` is still alive and the stolen task got to
+    // finish writing into it before the scope unwound.
+    assert_eq!(log.into_inner().unwrap(), vec!["spawned job ran"]);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn spawn_context_local_on_single_thread() {
+    // With one worker there is nobody to steal from, so every job is
+    // popped locally and must report not-migrated.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let migrated = &AtomicUsize::new(0);
+    let local = &AtomicUsize::new(0);
+    pool.install(|| {
+        scope(|s| for _ in 0..10 {
+            s.spawn_context(move |_, ctx| {
+                if ctx.migrated() {
+                    migrated.fetch_add(1, Ordering::SeqCst);
+                } else {
+                    local.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        });
+    });
+    assert_eq!(migrated.load(Ordering::SeqCst), 0);
+    assert_eq!(local.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn spawn_context_migrated_when_stolen() {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let migrated = &AtomicBool::new(false);
+    let started = &AtomicBool::new(false);
+    pool.install(|| {
+        scope(|s| {
+            s.spawn_context(move |_, ctx| {
+                migrated.store(ctx.migrated(), Ordering::SeqCst);
+                started.store(true, Ordering::SeqCst);
+            });
+            // Spinning here keeps the job out of our own deque, so
+            // the other worker must steal it.
+            while !started.load(Ordering::SeqCst) {
+                thread::yield_now();
+            }
+        });
+    });
+    assert!(migrated.load(Ordering::SeqCst));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn local_scope_hosts_non_send_tasks() {
+    use local_scope;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // The whole point of `local_scope()`: `!Send` scratch created
+    // inside the body is shared between the spawned jobs, which all
+    // run on the owning worker.
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let total = AtomicUsize::new(0);
+    pool.install(|| {
+        local_scope(|s| {
+            let scratch = Rc::new(Cell::new(0));
+            for _ in 0..10 {
+                let scratch = scratch.clone();
+                let total = &total;
+                s.spawn(move |_| {
+                    scratch.set(scratch.get() + 1);
+                    total.fetch_add(scratch.get(), Ordering::SeqCst);
+                });
+            }
+        });
+    });
+    // Each job adds the running count 1..10, in whatever order the
+    // jobs were popped: 1 + 2 + ... + 10.
+    assert_eq!(total.load(Ordering::SeqCst), 55);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn local_scope_tasks_stay_on_owner() {
+    use local_scope;
+    use std::collections::HashSet;
+    use std::thread;
+
+    // Even with other workers idle and hungry, local jobs (and the
+    // jobs they spawn in turn) must never be stolen.
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+    let threads = Mutex::new(HashSet::new());
+    pool.install(|| {
+        local_scope(|s| for _ in 0..10 {
+            let threads = &threads;
+            s.spawn(move |s| {
+                threads.lock().unwrap().insert(thread::current().id());
+                s.spawn(move |_| {
+                    threads.lock().unwrap().insert(thread::current().id());
+                });
+            });
+        });
+    });
+    assert_eq!(threads.into_inner().unwrap().len(), 1);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn local_scope_panic_propagates() {
+    use local_scope;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let result = pool.install(|| {
+        unwind::halt_unwinding(|| {
+            local_scope(|s| {
+                s.spawn(|_| panic!("Hello, world!"));
+            })
+        })
+    });
+    assert!(result.is_err(), "panic in local scope job was not propagated");
+}
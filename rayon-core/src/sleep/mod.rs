@@ -2,32 +2,210 @@
 //! for an overview.
 
 use log::Event::*;
+use std::cmp;
+#[cfg(feature = "unstable")]
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "unstable")]
+use std::sync::Arc;
 use std::sync::{Condvar, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 use std::usize;
+#[cfg(feature = "unstable")]
+use TransitionHandler;
 
 pub struct Sleep {
     state: AtomicUsize,
     data: Mutex<()>,
     tickle: Condvar,
+
+    /// Number of workers currently blocked in `sleep()`. This is not
+    /// part of the sleep protocol itself; it is a diagnostic counter
+    /// used by the deadlock watchdog (see `Registry`).
+    num_sleeping: AtomicUsize,
+
+    /// When true, each worker records how long it spends asleep into
+    /// `idle_micros`. Disabled by default so that the timing calls
+    /// cost nothing unless utilization tracking was requested (see
+    /// `Configuration::utilization_tracking()`).
+    track_idle: bool,
+
+    /// Accumulated time each worker has spent asleep, in
+    /// microseconds, since the counter was last taken.
+    idle_micros: Vec<AtomicUsize>,
+
+    /// For each worker: zero if the worker is not currently asleep;
+    /// otherwise, one plus the time (in microseconds since `epoch`)
+    /// at which its in-progress sleep started -- or was last sampled
+    /// by `take_idle_micros()`.
+    sleeping_since: Vec<AtomicUsize>,
+
+    /// Reference point for the timestamps in `sleeping_since`.
+    epoch: Instant,
+
+    /// End of the current warm-up window, as one plus a timestamp in
+    /// microseconds since `epoch`; zero when no window is active.
+    /// While the window lasts, workers that would fall asleep keep
+    /// spinning in `yield_now()` instead (see `warm_up()`).
+    warm_until: AtomicUsize,
+
+    /// Number of failed rounds after which a worker becomes sleepy,
+    /// resp. falls asleep. `ROUNDS_UNTIL_SLEEPY`/`ROUNDS_UNTIL_ASLEEP`
+    /// unless overridden via `Configuration::steal_retries()`.
+    rounds_until_sleepy: usize,
+    rounds_until_asleep: usize,
+
+    /// Callback fired when the last awake worker goes to sleep, resp.
+    /// when the first tickle thereafter arrives. See
+    /// `Configuration::on_idle()`/`on_busy()`.
+    #[cfg(feature = "unstable")]
+    idle_handler: Option<Arc<TransitionHandler>>,
+    #[cfg(feature = "unstable")]
+    busy_handler: Option<Arc<TransitionHandler>>,
+
+    /// True while the pool has reported itself idle (via
+    /// `idle_handler`) and not yet busy again. This flag is what makes
+    /// the transitions edge-triggered: whichever thread wins the swap
+    /// fires the callback, everyone else sees the edge already taken.
+    #[cfg(feature = "unstable")]
+    idle_reported: AtomicBool,
 }
 
 const AWAKE: usize = 0;
 const SLEEPING: usize = 1;
 
+/// Default number of failed rounds before a worker announces itself
+/// sleepy, and before it actually falls asleep; see
+/// `Configuration::steal_retries()` for the tunable override.
 const ROUNDS_UNTIL_SLEEPY: usize = 32;
 const ROUNDS_UNTIL_ASLEEP: usize = 64;
 
 impl Sleep {
-    pub fn new() -> Sleep {
+    pub fn new(num_threads: usize, track_idle: bool, steal_retries: Option<usize>) -> Sleep {
+        // A worker announces itself sleepy halfway towards actually
+        // going to sleep, mirroring the default 32/64 split.
+        let rounds_until_asleep = match steal_retries {
+            Some(n) => cmp::max(n, 1),
+            None => ROUNDS_UNTIL_ASLEEP,
+        };
         Sleep {
             state: AtomicUsize::new(AWAKE),
             data: Mutex::new(()),
             tickle: Condvar::new(),
+            num_sleeping: AtomicUsize::new(0),
+            track_idle: track_idle,
+            idle_micros: (0..num_threads).map(|_| AtomicUsize::new(0)).collect(),
+            sleeping_since: (0..num_threads).map(|_| AtomicUsize::new(0)).collect(),
+            epoch: Instant::now(),
+            warm_until: AtomicUsize::new(0),
+            rounds_until_sleepy: rounds_until_asleep / 2,
+            rounds_until_asleep: rounds_until_asleep,
+            #[cfg(feature = "unstable")]
+            idle_handler: None,
+            #[cfg(feature = "unstable")]
+            busy_handler: None,
+            #[cfg(feature = "unstable")]
+            idle_reported: AtomicBool::new(false),
+        }
+    }
+
+    /// Attaches the idle/busy transition callbacks, if any were
+    /// configured. Called once while the registry is being built.
+    #[cfg(feature = "unstable")]
+    pub fn with_transition_handlers(mut self,
+                                    on_idle: Option<Arc<TransitionHandler>>,
+                                    on_busy: Option<Arc<TransitionHandler>>)
+                                    -> Sleep {
+        self.idle_handler = on_idle;
+        self.busy_handler = on_busy;
+        self
+    }
+
+    /// Fires `on_idle` if this worker is about to become the last
+    /// sleeper. Called from `sleep()` *before* the data lock is
+    /// taken, so the callback runs with no scheduler lock held and
+    /// may safely call back into the pool. Best effort: in the narrow
+    /// window where the previous sleeper has blocked but not yet
+    /// bumped `num_sleeping`, the transition is missed until the next
+    /// sleep cycle.
+    #[cfg(feature = "unstable")]
+    fn note_nearly_asleep(&self) {
+        if let Some(ref handler) = self.idle_handler {
+            if self.num_sleeping.load(Ordering::SeqCst) + 1 >= self.sleeping_since.len() &&
+               !self.idle_reported.swap(true, Ordering::SeqCst) {
+                handler();
+            }
+        }
+    }
+
+    /// Fires `on_busy` if the pool had reported itself idle. The swap
+    /// on `idle_reported` picks a single winner, so a burst of
+    /// tickles fires the callback exactly once per idle period; a
+    /// sleepy worker that reported idle but got interrupted before
+    /// actually sleeping also comes through here to repair its
+    /// report. Runs with no scheduler lock held.
+    #[cfg(feature = "unstable")]
+    fn note_busy(&self) {
+        if self.busy_handler.is_some() || self.idle_handler.is_some() {
+            if self.idle_reported.swap(false, Ordering::SeqCst) {
+                if let Some(ref handler) = self.busy_handler {
+                    handler();
+                }
+            }
         }
     }
 
+    /// Takes (returns and resets) the number of microseconds the
+    /// given worker has spent asleep since the last call. If the
+    /// worker is asleep right now, its in-progress sleep is counted
+    /// up to the present moment. Always zero unless idle tracking was
+    /// enabled.
+    pub fn take_idle_micros(&self, worker_index: usize) -> usize {
+        let mut idle = self.idle_micros[worker_index].swap(0, Ordering::Relaxed);
+        let since = self.sleeping_since[worker_index].load(Ordering::Relaxed);
+        if since != 0 {
+            // The worker is asleep right now: count its in-progress
+            // sleep and restart that sleep's clock at the present
+            // moment. If the CAS fails, the worker woke up while we
+            // were looking and has accounted for the time itself.
+            let now = duration_micros(self.epoch.elapsed());
+            if self.sleeping_since[worker_index]
+                .compare_exchange(since, now + 1, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok() {
+                idle += now.saturating_sub(since - 1);
+            }
+        }
+        idle
+    }
+
+    /// Returns the number of workers that are currently blocked in
+    /// `sleep()`. This is approximate by nature and should only be
+    /// used for diagnostics, never for synchronization.
+    pub fn num_sleeping(&self) -> usize {
+        self.num_sleeping.load(Ordering::SeqCst)
+    }
+
+    /// Opens a warm-up window lasting `duration`: sleeping workers
+    /// are woken now, and until the window closes no worker enters
+    /// the deep condvar sleep -- idle workers spin in `yield_now()`
+    /// instead, so the next piece of work is picked up with minimal
+    /// wakeup latency. A new call simply moves the deadline; windows
+    /// never shrink each other since we store the later of the two.
+    pub fn warm_up(&self, duration: Duration) {
+        let deadline = duration_micros(self.epoch.elapsed() + duration) + 1;
+        self.warm_until.fetch_max(deadline, Ordering::SeqCst);
+        // Wake the current sleepers so the window applies to them
+        // too; they will find no work and settle into spinning.
+        self.tickle(usize::MAX);
+    }
+
+    /// Returns true while a warm-up window is open.
+    fn is_warm(&self) -> bool {
+        let warm_until = self.warm_until.load(Ordering::Relaxed);
+        warm_until != 0 && duration_micros(self.epoch.elapsed()) + 1 < warm_until
+    }
+
     fn anyone_sleeping(&self, state: usize) -> bool {
         state & SLEEPING != 0
     }
@@ -51,7 +229,7 @@ impl Sleep {
             worker: worker_index,
             yields: yields,
         });
-        if yields > ROUNDS_UNTIL_SLEEPY {
+        if yields > self.rounds_until_sleepy {
             // FIXME tickling here is a bit extreme; mostly we want to "release the lock"
             // from us being sleepy, we don't necessarily need to wake others
             // who are sleeping
@@ -66,16 +244,23 @@ impl Sleep {
             worker: worker_index,
             yields: yields,
         });
-        if yields < ROUNDS_UNTIL_SLEEPY {
+        if yields < self.rounds_until_sleepy {
             thread::yield_now();
             yields + 1
-        } else if yields == ROUNDS_UNTIL_SLEEPY {
+        } else if self.is_warm() {
+            // A warm-up window is open (see `warm_up()`): hold at the
+            // sleepy threshold, spinning, rather than escalate toward
+            // the condvar sleep. Checked only once the threshold is
+            // reached so the common path pays nothing for it.
+            thread::yield_now();
+            yields
+        } else if yields == self.rounds_until_sleepy {
             if self.get_sleepy(worker_index) {
                 yields + 1
             } else {
                 yields
             }
-        } else if yields < ROUNDS_UNTIL_ASLEEP {
+        } else if yields < self.rounds_until_asleep {
             thread::yield_now();
             if self.still_sleepy(worker_index) {
                 yields + 1
@@ -84,25 +269,46 @@ impl Sleep {
                 0
             }
         } else {
-            debug_assert_eq!(yields, ROUNDS_UNTIL_ASLEEP);
+            debug_assert_eq!(yields, self.rounds_until_asleep);
             self.sleep(worker_index);
             0
         }
     }
 
     pub fn tickle(&self, worker_index: usize) {
+        self.tickle_many(worker_index, usize::MAX);
+    }
+
+    /// Like `tickle()`, but wakes at most `count` sleeping workers. A
+    /// caller that knows it made exactly `count` new jobs available
+    /// can use this to avoid waking the whole pool: a single injected
+    /// job can only be taken by one worker, and on a large pool
+    /// having every sleeper wake up, sweep for work, and go back to
+    /// sleep is wasted churn.
+    ///
+    /// Note that this is only sound if *any* worker can take the new
+    /// work. If the work is reserved for one particular worker (see
+    /// `Registry::broadcast()`), use `tickle()`, since the workers we
+    /// wake here are picked arbitrarily.
+    pub fn tickle_many(&self, worker_index: usize, count: usize) {
         // As described in README.md, this load must be SeqCst so as to ensure that:
         // - if anyone is sleepy or asleep, we *definitely* see that now (and not eventually);
         // - if anyone after us becomes sleepy or asleep, they see memory events that
         //   precede the call to `tickle()`, even though we did not do a write.
         let old_state = self.state.load(Ordering::SeqCst);
         if old_state != AWAKE {
-            self.tickle_cold(worker_index);
+            self.tickle_cold(worker_index, count);
         }
     }
 
     #[cold]
-    fn tickle_cold(&self, worker_index: usize) {
+    fn tickle_cold(&self, worker_index: usize, count: usize) {
+        // The pool is leaving the idle state (if it was in it):
+        // report that before touching the data lock, so the callback
+        // cannot deadlock with the notification below.
+        #[cfg(feature = "unstable")]
+        self.note_busy();
+
         // The `Release` ordering here suffices. The reasoning is that
         // the atomic's own natural ordering ensure that any attempt
         // to become sleepy/asleep either will come before/after this
@@ -120,7 +326,22 @@ impl Sleep {
         });
         if self.anyone_sleeping(old_state) {
             let _data = self.data.lock().unwrap();
-            self.tickle.notify_all();
+            if count >= self.num_sleeping.load(Ordering::SeqCst) {
+                self.tickle.notify_all();
+            } else {
+                for _ in 0..count {
+                    self.tickle.notify_one();
+                }
+
+                // Some workers remain blocked on the condvar, but we
+                // have already swapped the state to `AWAKE`. Restore
+                // the SLEEPING bit so that the next tickle still
+                // takes this cold path and can wake them; otherwise
+                // the remaining sleepers would miss that wakeup
+                // entirely. (Setting the bit spuriously only costs an
+                // extra notification.)
+                self.state.fetch_or(SLEEPING, Ordering::SeqCst);
+            }
         }
     }
 
@@ -228,6 +449,8 @@ impl Sleep {
                 // reason for the `compare_exchange` to fail is if an
                 // awaken comes, in which case the next cycle around
                 // the loop will just return.
+                #[cfg(feature = "unstable")]
+                self.note_nearly_asleep();
                 let data = self.data.lock().unwrap();
 
                 // This must be SeqCst on success because we want to
@@ -253,14 +476,42 @@ impl Sleep {
                     // problem for us, we'll just loop around and maybe get
                     // sleepy again.
                     log!(FellAsleep { worker: worker_index });
+                    if self.track_idle {
+                        let now = duration_micros(self.epoch.elapsed());
+                        self.sleeping_since[worker_index].store(now + 1, Ordering::Relaxed);
+                    }
+                    self.num_sleeping.fetch_add(1, Ordering::SeqCst);
                     let _ = self.tickle.wait(data).unwrap();
+                    self.num_sleeping.fetch_sub(1, Ordering::SeqCst);
+                    if self.track_idle {
+                        // `take_idle_micros()` may have sampled part
+                        // of this sleep already; whatever timestamp
+                        // is left is the unaccounted remainder.
+                        let now = duration_micros(self.epoch.elapsed());
+                        let since = self.sleeping_since[worker_index].swap(0, Ordering::Relaxed);
+                        if since != 0 {
+                            self.idle_micros[worker_index]
+                                .fetch_add(now.saturating_sub(since - 1), Ordering::Relaxed);
+                        }
+                    }
                     log!(GotAwoken { worker: worker_index });
                     return;
                 }
             } else {
+                // If we were the one who reported the pool idle but
+                // got interrupted before actually sleeping, the pool
+                // never was idle: take the report back.
+                #[cfg(feature = "unstable")]
+                self.note_busy();
                 log!(GotInterrupted { worker: worker_index });
                 return;
             }
         }
     }
 }
+
+/// Converts a duration into (approximate) microseconds.
+pub fn duration_micros(duration: Duration) -> usize {
+    let micros = duration.as_secs() * 1_000_000 + (duration.subsec_nanos() / 1_000) as u64;
+    micros as usize
+}
@@ -1,8 +1,9 @@
 use future::{self, Future, RayonFuture};
 #[allow(unused_imports)]
 use latch::{Latch, SpinLatch};
+use latch::{LatchProbe, LockLatch};
 use job::*;
-use registry::Registry;
+use registry::{self, Registry, WorkerThread};
 use std::any::Any;
 use std::mem;
 use std::sync::Arc;
@@ -19,6 +20,18 @@ use unwind;
 /// by a mutex, or some such thing). If you want to compute a result,
 /// consider `spawn_future_async()`.
 ///
+/// # Allocation behavior
+///
+/// Unlike `join()`, whose tasks live on the caller's stack, a spawned
+/// task outlives its caller, so the closure (together with one
+/// pointer of pool bookkeeping) is normally moved to the heap -- one
+/// allocation per spawn, proportional to the closure's captures. Two
+/// escape hatches exist for spawn-heavy code: a closure small enough
+/// to fit in the job slot -- in practice, one that captures nothing
+/// -- is stored inline and allocates nothing at all, and
+/// `spawn_boxed()` takes an already-boxed closure so that a large
+/// closure's allocation is explicit at the call site.
+///
 /// # Panic handling
 ///
 /// If this closure should panic, the resulting panic will be
@@ -61,7 +74,7 @@ pub unsafe fn spawn_async_in<F>(func: F, registry: &Arc<Registry>)
     // executed. This ref is decremented at the (*) below.
     registry.increment_terminate_count();
 
-    let async_job = Box::new(HeapJob::new({
+    let async_task = {
         let registry = registry.clone();
         move || {
             match unwind::halt_unwinding(func) {
@@ -73,7 +86,7 @@ pub unsafe fn spawn_async_in<F>(func: F, registry: &Arc<Registry>)
             }
             registry.terminate(); // (*) permit registry to terminate now
         }
-    }));
+    };
 
     // We assert that this does not hold any references (we know
     // this because of the `'static` bound in the inferface);
@@ -81,9 +94,396 @@ pub unsafe fn spawn_async_in<F>(func: F, registry: &Arc<Registry>)
     // be able to panic, and hence the data won't leak but will be
     // enqueued into some deque for later execution.
     let abort_guard = unwind::AbortIfPanic; // just in case we are wrong, and code CAN panic
-    let job_ref = HeapJob::as_job_ref(async_job);
+    let job_ref = as_spawn_job_ref(async_task);
     registry.inject_or_push(job_ref);
     mem::forget(abort_guard);
+    registry::grow_if_saturated(registry);
+}
+
+/// Erases a spawn wrapper closure into a `JobRef`, storing it inline
+/// in the job slot when it fits (see `JobRef::new_inline()`) and
+/// falling back to the usual heap allocation otherwise. The fit test
+/// is a compile-time constant, so each closure type pays for exactly
+/// one of the two paths.
+///
+/// Unsafe for the `JobRef` reason: the result must be executed
+/// exactly once.
+unsafe fn as_spawn_job_ref<BODY>(body: BODY) -> JobRef
+    where BODY: FnOnce() + Send
+{
+    if fits_inline::<BODY>() {
+        JobRef::new_inline(body)
+    } else {
+        HeapJob::as_job_ref(Box::new(HeapJob::new(body)))
+    }
+}
+
+/// Fires off a task whose closure the caller has boxed already: like
+/// `spawn_async()`, but for closures with large captures, where the
+/// hidden move-to-the-heap of a plain spawn can be a surprising cost.
+/// Here the big allocation is explicit at the call site -- it can be
+/// done ahead of time, or amortized by a caller-side pool -- and what
+/// the spawn itself allocates is one constant-size job header,
+/// however large the closure is.
+///
+/// # Panic handling
+///
+/// As with `spawn_async()`, a panic in the task is propagated to the
+/// panic handler registered in the `Configuration`, if any.
+pub fn spawn_boxed(func: Box<FnBox + Send>) {
+    spawn_async(move || func.call_box())
+}
+
+/// Workaround trait for invoking a `Box<FnOnce()>`: a boxed closure
+/// is called through `&mut self`, which `FnOnce` does not allow, so
+/// the box is passed whole and consumed instead.
+pub trait FnBox {
+    fn call_box(self: Box<Self>);
+}
+
+impl<F> FnBox for F
+    where F: FnOnce()
+{
+    fn call_box(self: Box<F>) {
+        (*self)()
+    }
+}
+
+/// Fires off a "sticky" task: like `spawn_async()`, except that the
+/// task is pinned to the worker thread that created it and will never
+/// be stolen by other workers. This is useful for tasks that benefit
+/// from thread-local state (caches, RNGs, connection handles) which
+/// work-stealing would otherwise thrash.
+///
+/// If the calling thread is not a Rayon worker thread, there is no
+/// origin worker to stick to, and this behaves exactly like
+/// `spawn_async()`.
+///
+/// # Starvation warning
+///
+/// A sticky task only runs when its origin worker returns to the
+/// scheduler (e.g., when it goes idle, or while it is blocked in
+/// `join()` waiting for stolen work). If the origin worker stays busy
+/// with one very long job, its sticky tasks are starved indefinitely.
+pub fn spawn_sticky<F>(func: F)
+    where F: FnOnce() + Send + 'static
+{
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        if worker_thread.is_null() {
+            // Not on a worker thread: nowhere to stick to.
+            return spawn_async(func);
+        }
+
+        let registry = (*worker_thread).registry();
+
+        // Ensure that registry cannot terminate until this job has
+        // executed. This ref is decremented at the (*) below.
+        registry.increment_terminate_count();
+
+        let sticky_job = Box::new(HeapJob::new({
+            let registry = registry.clone();
+            move || {
+                match unwind::halt_unwinding(func) {
+                    Ok(()) => {
+                    }
+                    Err(err) => {
+                        registry.handle_panic(err);
+                    }
+                }
+                registry.terminate(); // (*) permit registry to terminate now
+            }
+        }));
+
+        // As in `spawn_async_in()`, the code between allocating the
+        // job and enqueuing it must not panic, or the job would leak.
+        let abort_guard = unwind::AbortIfPanic;
+        let job_ref = HeapJob::as_job_ref(sticky_job);
+        (*worker_thread).push_sticky(job_ref);
+        mem::forget(abort_guard);
+    }
+}
+
+/// Fires off a high-priority task: like `spawn_async()`, except that
+/// the task is pushed onto a second, high-priority deque on the
+/// current worker thread. Workers execute (and steal) high-priority
+/// jobs in preference to anything on their regular deques, which
+/// makes this suitable for latency-critical work on a mixed pool.
+///
+/// If the calling thread is not a Rayon worker thread, there is no
+/// local deque to push to, and this behaves exactly like
+/// `spawn_async()`.
+///
+/// # Starvation warning
+///
+/// This is a simple two-level priority scheme: as long as any
+/// high-priority job is available, regular jobs do not run. A steady
+/// stream of high-priority tasks can therefore starve the regular
+/// deques indefinitely. Users who do not opt in are unaffected; the
+/// normal scheduling path is unchanged.
+pub fn spawn_priority<F>(func: F)
+    where F: FnOnce() + Send + 'static
+{
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        if worker_thread.is_null() {
+            // Not on a worker thread: no local deque to prioritize in.
+            return spawn_async(func);
+        }
+
+        let registry = (*worker_thread).registry();
+
+        // Ensure that registry cannot terminate until this job has
+        // executed. This ref is decremented at the (*) below.
+        registry.increment_terminate_count();
+
+        let priority_job = Box::new(HeapJob::new({
+            let registry = registry.clone();
+            move || {
+                match unwind::halt_unwinding(func) {
+                    Ok(()) => {
+                    }
+                    Err(err) => {
+                        registry.handle_panic(err);
+                    }
+                }
+                registry.terminate(); // (*) permit registry to terminate now
+            }
+        }));
+
+        // As in `spawn_async_in()`, the code between allocating the
+        // job and enqueuing it must not panic, or the job would leak.
+        let abort_guard = unwind::AbortIfPanic;
+        let job_ref = HeapJob::as_job_ref(priority_job);
+        (*worker_thread).push_priority(job_ref);
+        mem::forget(abort_guard);
+    }
+}
+
+/// Fires off a task with a user-assigned integer priority: idle
+/// workers take injected work highest-priority first, with equal
+/// priorities running in submission order. Higher values are more
+/// urgent; the scale is entirely yours, so a job scheduler or build
+/// system can map its own cost model straight onto it. Unlike
+/// `spawn_priority()`, which is a two-level scheme local to the
+/// current worker, the priority here is a full ordering over the
+/// pool's shared injected queue, visible from any thread.
+///
+/// # Starvation warning
+///
+/// Nothing ages a waiting job: as long as higher-priority work keeps
+/// arriving, lower-priority tasks do not run. Submitters that must
+/// guarantee progress should bound how much high-priority work they
+/// keep in flight, or periodically re-submit starved work at a
+/// higher priority.
+///
+/// # Panic handling
+///
+/// As with `spawn_async()`, a panic in the task is propagated to the
+/// panic handler registered in the `Configuration`, if any.
+pub fn spawn_prioritized<F>(priority: i32, func: F)
+    where F: FnOnce() + Send + 'static
+{
+    // We assert that current registry has not terminated.
+    unsafe { spawn_prioritized_in(priority, func, &Registry::current()) }
+}
+
+/// Spawn a prioritized job in `registry`.
+///
+/// Unsafe because `registry` must not yet have terminated.
+///
+/// Not a public API, but used elsewhere in Rayon.
+pub unsafe fn spawn_prioritized_in<F>(priority: i32, func: F, registry: &Arc<Registry>)
+    where F: FnOnce() + Send + 'static
+{
+    // Ensure that registry cannot terminate until this job has
+    // executed. This ref is decremented at the (*) below.
+    registry.increment_terminate_count();
+
+    let prioritized_job = Box::new(HeapJob::new({
+        let registry = registry.clone();
+        move || {
+            match unwind::halt_unwinding(func) {
+                Ok(()) => {
+                }
+                Err(err) => {
+                    registry.handle_panic(err);
+                }
+            }
+            registry.terminate(); // (*) permit registry to terminate now
+        }
+    }));
+
+    // As in `spawn_async_in()`, the code between allocating the
+    // job and enqueuing it must not panic, or the job would leak.
+    let abort_guard = unwind::AbortIfPanic;
+    let job_ref = HeapJob::as_job_ref(prioritized_job);
+    registry.inject_prioritized(job_ref, priority);
+    mem::forget(abort_guard);
+    registry::grow_if_saturated(registry);
+}
+
+/// Fires off a task that will run on the worker thread with the
+/// given index of the current thread-pool (the global pool, if the
+/// caller is not on a worker thread). This is intended for placement
+/// experiments and NUMA-aware code, where a subtask should run on a
+/// particular core -- combine it with a `start_handler` that pins
+/// worker threads to cores.
+///
+/// The task goes into the target worker's "mailbox" rather than onto
+/// a deque, so unlike a regular spawn it can never be stolen:
+/// placement is exact, not a hint. The target executes mailbox jobs
+/// when it next returns to the scheduler, so a target that is busy
+/// with one very long job delays its mailbox accordingly. If the
+/// target worker is parked (in a lazy pool, or after `resize()`), it
+/// is started.
+///
+/// # Panics
+///
+/// Panics if `index` is not a valid worker index for the pool.
+///
+/// # Panic handling
+///
+/// As with `spawn_async()`, a panic in the task is propagated to the
+/// panic handler registered in the `Configuration`, if any.
+pub fn spawn_on<F>(index: usize, func: F)
+    where F: FnOnce() + Send + 'static
+{
+    // We assert that current registry has not terminated.
+    unsafe { spawn_on_in(index, func, &Registry::current()) }
+}
+
+/// Spawn a job in `registry` reserved for the worker with the given
+/// index.
+///
+/// Unsafe because `registry` must not yet have terminated.
+///
+/// Not a public API, but used elsewhere in Rayon.
+pub unsafe fn spawn_on_in<F>(index: usize, func: F, registry: &Arc<Registry>)
+    where F: FnOnce() + Send + 'static
+{
+    assert!(index < registry.num_threads(),
+            "spawn_on() targets worker {} but the pool only has {} threads",
+            index,
+            registry.num_threads());
+
+    // Ensure that registry cannot terminate until this job has
+    // executed. This ref is decremented at the (*) below.
+    registry.increment_terminate_count();
+
+    let targeted_job = Box::new(HeapJob::new({
+        let registry = registry.clone();
+        move || {
+            match unwind::halt_unwinding(func) {
+                Ok(()) => {
+                }
+                Err(err) => {
+                    registry.handle_panic(err);
+                }
+            }
+            registry.terminate(); // (*) permit registry to terminate now
+        }
+    }));
+
+    // As in `spawn_async_in()`, the code between allocating the
+    // job and enqueuing it must not panic, or the job would leak.
+    let abort_guard = unwind::AbortIfPanic;
+    let job_ref = HeapJob::as_job_ref(targeted_job);
+    registry.inject_targeted(index, job_ref);
+    mem::forget(abort_guard);
+    registry::ensure_worker_spawned(registry, index);
+}
+
+/// Fires off a detached task like `spawn_async()`, but keeps track
+/// of it: the returned `TaskHandle` can be probed or awaited, and
+/// the pool counts the task among its pending detached tasks (see
+/// `ThreadPool::pending_detached()`) until it finishes. This fills
+/// the gap between `scope()`, which waits for everything before
+/// returning, and a plain `spawn_async()`, which is fire-and-forget:
+/// background work that should be drained before shutdown can be
+/// spawned tracked, and a graceful shutdown waits on the handles (or
+/// on `ThreadPool::wait_detached()`) before dropping the pool.
+///
+/// # Panic handling
+///
+/// As with `spawn_async()`, a panic in the task is propagated to the
+/// panic handler registered in the `Configuration`, if any. The task
+/// still counts as finished: its handle is released and the pending
+/// count decremented.
+pub fn spawn_tracked<F>(func: F) -> TaskHandle
+    where F: FnOnce() + Send + 'static
+{
+    // We assert that current registry has not terminated.
+    let registry = Registry::current();
+    let latch = Arc::new(LockLatch::new());
+    unsafe {
+        // Ensure that registry cannot terminate until this job has
+        // executed. This ref is decremented at the (*) below.
+        registry.increment_terminate_count();
+        registry.note_detached_spawned();
+
+        let tracked_job = Box::new(HeapJob::new({
+            let registry = registry.clone();
+            let latch = latch.clone();
+            move || {
+                match unwind::halt_unwinding(func) {
+                    Ok(()) => {
+                    }
+                    Err(err) => {
+                        registry.handle_panic(err);
+                    }
+                }
+                // The pending count must drop before the latch is
+                // set, so that a handle that has been waited on is
+                // no longer counted.
+                registry.note_detached_completed();
+                latch.set();
+                registry.terminate(); // (*) permit registry to terminate now
+            }
+        }));
+
+        // As in `spawn_async_in()`, the code between allocating the
+        // job and enqueuing it must not panic, or the job would leak.
+        let abort_guard = unwind::AbortIfPanic;
+        let job_ref = HeapJob::as_job_ref(tracked_job);
+        registry.inject_or_push(job_ref);
+        mem::forget(abort_guard);
+        registry::grow_if_saturated(&registry);
+    }
+    TaskHandle {
+        latch: latch,
+        registry: registry,
+    }
+}
+
+/// Handle to a task started with `spawn_tracked()`.
+pub struct TaskHandle {
+    latch: Arc<LockLatch>,
+    registry: Arc<Registry>,
+}
+
+impl TaskHandle {
+    /// Returns true if the task has finished, whether normally or by
+    /// panic.
+    pub fn is_complete(&self) -> bool {
+        self.latch.probe()
+    }
+
+    /// Blocks until the task has finished. When called on a worker
+    /// thread of the task's own pool, the worker keeps executing
+    /// (and stealing) other jobs while it waits, so waiting on a
+    /// handle from inside the pool cannot deadlock it.
+    pub fn wait(&self) {
+        unsafe {
+            let worker_thread = WorkerThread::current();
+            if !worker_thread.is_null() &&
+               (*worker_thread).registry().id() == self.registry.id() {
+                (*worker_thread).wait_until(&*self.latch);
+            } else {
+                self.latch.wait();
+            }
+        }
+    }
 }
 
 /// Spawns a future, scheduling it to execute on Rayon's threadpool.
@@ -111,6 +511,32 @@ pub unsafe fn spawn_future_async_in<F>(future: F, registry: Arc<Registry>) -> Ra
     future::new_rayon_future(future, scope)
 }
 
+/// Spawns a future to be driven to completion on the Rayon
+/// threadpool purely for its side effects: unlike
+/// `spawn_future_async()`, no handle to the result is returned, so
+/// the item and error types must both be `()`. Each time the future
+/// is woken, a poll job is re-injected into the pool, letting async
+/// tasks and CPU-bound Rayon work share one set of worker threads.
+///
+/// # Panic handling
+///
+/// As with `spawn_async()`, a panic while polling the future is
+/// propagated to the panic handler registered in the
+/// `Configuration`, if any.
+pub fn spawn_future<F>(future: F)
+    where F: Future<Item = (), Error = ()> + Send + 'static
+{
+    let rayon_future = spawn_future_async(future);
+
+    // Dropping a `RayonFuture` cancels it, so hand the handle to a
+    // task that keeps it alive until the future has completed. On a
+    // worker thread, waiting here does not idle the worker: it keeps
+    // executing (and stealing) jobs, including our own poll jobs.
+    spawn_async(move || {
+        let _ = rayon_future.rayon_wait();
+    });
+}
+
 struct StaticFutureScope {
     registry: Arc<Registry>
 }
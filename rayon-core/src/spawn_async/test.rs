@@ -6,7 +6,8 @@ use std::sync::{Arc, Mutex};
 use std::sync::mpsc::channel;
 
 use {Configuration, ThreadPool};
-use super::{spawn_async, spawn_future_async};
+use super::{spawn_async, spawn_future, spawn_future_async, spawn_prioritized, spawn_priority,
+            spawn_sticky};
 
 #[test]
 fn spawn_then_join_in_worker() {
@@ -24,6 +25,56 @@ fn spawn_then_join_outside_worker() {
     assert_eq!(22, rx.recv().unwrap());
 }
 
+#[test]
+fn spawn_sticky_runs_on_origin_thread() {
+    let (tx, rx) = channel();
+    let pool = Arc::new(ThreadPool::new(Configuration::new().num_threads(2)).unwrap());
+    let origin = pool.install({
+        let pool = pool.clone();
+        move || {
+            let origin = pool.current_thread_index().unwrap();
+            let pool = pool.clone();
+            spawn_sticky(move || {
+                tx.send(pool.current_thread_index().unwrap()).unwrap();
+            });
+            origin
+        }
+    });
+    assert_eq!(origin, rx.recv().unwrap());
+}
+
+#[test]
+fn spawn_on_runs_on_target_worker() {
+    let (tx, rx) = channel();
+    let pool = Arc::new(ThreadPool::new(Configuration::new().num_threads(4)).unwrap());
+    for target in 0..4 {
+        let tx = tx.clone();
+        let pool_in_job = pool.clone();
+        pool.spawn_on(target, move || {
+            tx.send((target, pool_in_job.current_thread_index().unwrap())).unwrap();
+        });
+    }
+    for _ in 0..4 {
+        let (target, ran_on) = rx.recv().unwrap();
+        assert_eq!(target, ran_on, "targeted job ran on the wrong worker");
+    }
+}
+
+#[test]
+#[should_panic(expected = "targets worker 4")]
+fn spawn_on_invalid_index() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+    pool.spawn_on(4, || {});
+}
+
+#[test]
+fn spawn_sticky_outside_worker() {
+    // not on a worker thread, so this degenerates to `spawn_async()`
+    let (tx, rx) = channel();
+    spawn_sticky(move || tx.send(22).unwrap());
+    assert_eq!(22, rx.recv().unwrap());
+}
+
 #[test]
 fn panic_fwd() {
     let (tx, rx) = channel();
@@ -68,6 +119,27 @@ fn async_future_map() {
     assert_eq!(&data.lock().unwrap()[..], "Hello, world!");
 }
 
+#[test]
+fn spawn_priority_outside_worker() {
+    // not on a worker thread: must fall back to a plain spawn_async
+    let (tx, rx) = channel();
+    spawn_priority(move || tx.send(22).unwrap());
+    assert_eq!(22, rx.recv().unwrap());
+}
+
+#[test]
+fn spawn_future_runs_detached() {
+    let (tx, rx) = channel();
+
+    spawn_future(lazy(move || {
+        tx.send(22).unwrap();
+        Ok::<(), ()>(())
+    }));
+
+    // no handle to wait on; the future must complete on its own
+    assert_eq!(22, rx.recv().unwrap());
+}
+
 #[test]
 #[should_panic(expected = "Hello, world!")]
 fn async_future_panic_prop() {
@@ -187,3 +259,137 @@ fn custom_panic_handler_and_nested_spawn_async() {
         }
     }
 }
+
+#[test]
+fn spawn_tracked_handle_and_pending_count() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let gate = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+
+    let handle = {
+        let gate = gate.clone();
+        let done = done.clone();
+        pool.install(move || {
+            super::spawn_tracked(move || {
+                while gate.load(Ordering::SeqCst) == 0 {
+                    thread::yield_now();
+                }
+                done.store(1, Ordering::SeqCst);
+            })
+        })
+    };
+
+    // The task is gated, so it is pending and incomplete.
+    assert!(!handle.is_complete());
+    assert_eq!(pool.pending_detached(), 1);
+
+    gate.store(1, Ordering::SeqCst);
+    handle.wait();
+    assert!(handle.is_complete());
+    assert_eq!(done.load(Ordering::SeqCst), 1);
+    assert_eq!(pool.pending_detached(), 0);
+}
+
+#[test]
+fn wait_detached_drains_tracked_tasks() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let counter = Arc::new(AtomicUsize::new(0));
+    pool.install(|| for _ in 0..10 {
+                     let counter = counter.clone();
+                     super::spawn_tracked(move || {
+                         ::std::thread::sleep(Duration::from_millis(1));
+                         counter.fetch_add(1, Ordering::SeqCst);
+                     });
+                 });
+    pool.wait_detached();
+    assert_eq!(counter.load(Ordering::SeqCst), 10);
+    assert_eq!(pool.pending_detached(), 0);
+}
+
+#[test]
+fn panicked_tracked_task_still_completes() {
+    let (tx, rx) = channel();
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(1)
+            .panic_handler(move |_| tx.send(()).unwrap()))
+        .unwrap();
+    let handle = pool.install(|| super::spawn_tracked(|| panic!("Hello, world!")));
+    handle.wait();
+    assert!(handle.is_complete());
+    assert_eq!(pool.pending_detached(), 0);
+    rx.recv().unwrap();
+}
+
+#[test]
+fn spawn_prioritized_runs_highest_first() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let (ready_tx, ready_rx) = channel();
+    let (gate_tx, gate_rx) = channel();
+    let (done_tx, done_rx) = channel();
+
+    // Park the only worker, so that every job below is still queued
+    // when it next returns to the scheduler.
+    pool.spawn_async(move || {
+        ready_tx.send(()).unwrap();
+        gate_rx.recv().unwrap();
+    });
+    ready_rx.recv().unwrap();
+
+    for &(priority, tag) in &[(1, 'a'), (5, 'b'), (3, 'c'), (5, 'd'), (-2, 'e')] {
+        let order = order.clone();
+        pool.spawn_prioritized(priority, move || {
+            order.lock().unwrap().push(tag);
+        });
+    }
+    // Runs after everything above, whatever the timing.
+    pool.spawn_prioritized(i32::MIN, move || done_tx.send(()).unwrap());
+
+    gate_tx.send(()).unwrap();
+    done_rx.recv().unwrap();
+    // Highest priority first; submission order among equals.
+    assert_eq!(*order.lock().unwrap(), vec!['b', 'd', 'c', 'a', 'e']);
+}
+
+#[test]
+fn spawn_prioritized_outside_worker() {
+    // not on a worker thread: injected into the global pool's heap
+    let (tx, rx) = channel();
+    spawn_prioritized(7, move || tx.send(22).unwrap());
+    assert_eq!(22, rx.recv().unwrap());
+}
+
+#[test]
+fn spawn_inline_runs_zero_capture_closure() {
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+    use std::thread;
+
+    // A closure capturing nothing takes the inline job path: with the
+    // pool bookkeeping it is exactly one pointer wide.
+    assert!(::job::fits_inline::<()>());
+    assert!(!::job::fits_inline::<[usize; 2]>());
+
+    static HIT: AtomicUsize = ATOMIC_USIZE_INIT;
+    spawn_async(|| {
+        HIT.fetch_add(1, Ordering::SeqCst);
+    });
+    while HIT.load(Ordering::SeqCst) == 0 {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn spawn_boxed_runs_preboxed_closure() {
+    use super::spawn_boxed;
+
+    let (tx, rx) = channel();
+    let payload = vec![22; 1024];
+    spawn_boxed(Box::new(move || tx.send(payload.len()).unwrap()));
+    assert_eq!(1024, rx.recv().unwrap());
+}
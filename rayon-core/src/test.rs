@@ -4,6 +4,7 @@ use Configuration;
 use std::sync::{Arc, Barrier};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use thread_pool::*;
+use unwind;
 
 #[test]
 fn worker_thread_index() {
@@ -14,6 +15,44 @@ fn worker_thread_index() {
     assert!(index < 22);
 }
 
+#[test]
+fn configuration_clone_shares_handlers() {
+    let n_threads = 2;
+    let n_called = Arc::new(AtomicUsize::new(0));
+    // Wait for all the threads of both pools plus the one running tests.
+    let barrier = Arc::new(Barrier::new(2 * n_threads + 1));
+
+    let b = barrier.clone();
+    let nc = n_called.clone();
+    let config = Configuration::new()
+        .num_threads(n_threads)
+        .start_handler(move |_| {
+            nc.fetch_add(1, Ordering::SeqCst);
+            b.wait();
+        });
+
+    // Both pools share the same (cloned) configuration, including
+    // the start handler.
+    let _pool1 = ThreadPool::new(config.clone()).unwrap();
+    let _pool2 = ThreadPool::new(config).unwrap();
+
+    barrier.wait();
+    assert_eq!(n_called.load(Ordering::SeqCst), 2 * n_threads);
+}
+
+#[test]
+fn deadlock_detection_pool_works_normally() {
+    // The watchdog is only observable when something actually hangs,
+    // but we can at least check that enabling it does not disturb
+    // normal operation or termination.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .deadlock_detection(true))
+        .unwrap();
+    let v = pool.install(|| 22);
+    assert_eq!(v, 22);
+}
+
 #[test]
 fn start_callback_called() {
     let n_threads = 16;
@@ -118,3 +157,374 @@ fn handler_panics_handled_correctly() {
     // The panic handler must have been called twice on every thread.
     assert_eq!(n_called.load(Ordering::SeqCst), 2 * n_threads);
 }
+
+#[test]
+#[cfg(feature = "unstable")]
+fn build_error_reports_io_kind() {
+    use {ThreadPoolBuildError, ThreadPoolBuildErrorKind};
+    use std::io;
+
+    let result = ThreadPool::new(Configuration::new()
+        .num_threads(2)
+        .spawn_handler(|_| Err(io::Error::new(io::ErrorKind::Other, "no threads today"))));
+    let err = result.err().unwrap().downcast::<ThreadPoolBuildError>().unwrap();
+    match *err.kind() {
+        ThreadPoolBuildErrorKind::IOError(_) => {}
+        ref kind => panic!("unexpected kind: {:?}", kind),
+    }
+}
+
+#[test]
+fn global_pool_double_init_reports_kind() {
+    use ThreadPoolBuildErrorKind;
+
+    // The global pool may or may not have been initialized by other
+    // tests already; either way, by the second call it certainly has
+    // been, and the error must say so.
+    let _ = ::initialize(Configuration::new());
+    let err = ::initialize(Configuration::new()).err().unwrap();
+    match *err.kind() {
+        ThreadPoolBuildErrorKind::GlobalPoolAlreadyInitialized => {}
+        ref kind => panic!("unexpected kind: {:?}", kind),
+    }
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn max_consecutive_panics_resets_on_success() {
+    // Two panicking jobs, a successful one, then two more panicking:
+    // with a limit of three the pool must survive, since the success
+    // resets the worker's count. If the reset were broken, the fourth
+    // captured panic would abort the whole test process.
+    let n_handled = Arc::new(AtomicUsize::new(0));
+    let nh = n_handled.clone();
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(1)
+            .max_consecutive_panics(3)
+            .panic_handler(move |_| {
+                nh.fetch_add(1, Ordering::SeqCst);
+            }))
+        .unwrap();
+
+    for round in 0..2 {
+        pool.spawn_async(|| panic!("captured"));
+        pool.spawn_async(|| panic!("captured"));
+        while n_handled.load(Ordering::SeqCst) < 2 * (round + 1) {
+            ::std::thread::yield_now();
+        }
+        assert_eq!(pool.install(|| 22), 22);
+    }
+}
+
+#[test]
+fn abort_exit_code_pool_works_normally() {
+    // The configured code is only observable when Rayon actually
+    // aborts, but we can at least check that setting it does not
+    // disturb normal operation.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .abort_exit_code(101))
+        .unwrap();
+    assert_eq!(pool.install(|| 22), 22);
+}
+
+#[test]
+fn panic_abort_pool_runs_non_panicking_work() {
+    // The abort itself is not observable in-process; check that a
+    // pool with the option enabled runs panic-free work normally.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .panic_abort(true))
+        .unwrap();
+    assert_eq!(pool.install(|| 22), 22);
+}
+
+#[test]
+fn panic_abort_off_still_propagates_panics() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let result = pool.install(|| unwind::halt_unwinding(|| panic!("Hello, world!")));
+    assert!(result.is_err());
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn wait_all_blocks_until_every_latch_set() {
+    use latch::{Latch, LatchProbe, LockLatch};
+
+    let latches: Vec<Arc<LockLatch>> =
+        (0..3).map(|_| Arc::new(LockLatch::new())).collect();
+    latches[0].set();
+    latches[2].set();
+    let last = latches[1].clone();
+    let setter = ::std::thread::spawn(move || last.set());
+
+    let refs: Vec<&LockLatch> = latches.iter().map(|l| &**l).collect();
+    ::wait_all(&refs);
+    assert!(latches.iter().all(|l| l.probe()));
+    setter.join().unwrap();
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn wait_any_returns_lowest_set_index() {
+    use latch::{Latch, LockLatch};
+
+    let latches: Vec<LockLatch> = (0..3).map(|_| LockLatch::new()).collect();
+    latches[1].set();
+    let refs: Vec<&LockLatch> = latches.iter().collect();
+    assert_eq!(::wait_any(&refs), 1);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn wait_all_on_worker_runs_pool_work() {
+    use latch::{Latch, LatchProbe, LockLatch};
+
+    // With a single worker, the latch-setting job can only run if the
+    // waiting worker cooperatively picks it up while blocked in
+    // `wait_all()`.
+    let pool = Arc::new(ThreadPool::new(Configuration::new().num_threads(1)).unwrap());
+    let pool2 = pool.clone();
+    pool.install(move || {
+        let latch = Arc::new(LockLatch::new());
+        let l = latch.clone();
+        pool2.spawn_async(move || l.set());
+        ::wait_all(&[&*latch]);
+        assert!(latch.probe());
+    });
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn wake_batch_limit_pool_drains_bursts() {
+    // A tight wake bound changes only how many sleepers a burst
+    // wakes; every injected job must still run eventually.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(4)
+            .wake_batch_limit(1))
+        .unwrap();
+    let count = Arc::new(AtomicUsize::new(0));
+    for _ in 0..20 {
+        let c = count.clone();
+        pool.spawn_async(move || {
+            c.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    while count.load(Ordering::SeqCst) < 20 {
+        ::std::thread::yield_now();
+    }
+    pool.wait_until_idle();
+    assert_eq!(count.load(Ordering::SeqCst), 20);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn forbid_global_pool_rejects_initialize() {
+    use ThreadPoolBuildErrorKind;
+
+    // Explicit pools keep working while the global pool is forbidden;
+    // only the implicit one is off limits.
+    ::forbid_global_pool();
+    let err = ::initialize(Configuration::new()).err().unwrap();
+    match *err.kind() {
+        ThreadPoolBuildErrorKind::GlobalPoolForbidden => {}
+        ref kind => panic!("unexpected kind: {:?}", kind),
+    }
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    assert_eq!(pool.install(|| 22), 22);
+
+    // Undo the process-global flag so the rest of the suite can keep
+    // using the global pool.
+    ::registry::allow_global_pool();
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn with_worker_rng_off_pool_is_none() {
+    assert!(::with_worker_rng(|_| ()).is_none());
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn with_worker_rng_draws_on_a_worker() {
+    use rand::Rng;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let draws: Vec<u32> = pool.install(|| {
+        (0..64)
+            .map(|_| ::with_worker_rng(|rng| rng.next_u32()).unwrap())
+            .collect()
+    });
+    // The stream must advance between calls: 64 identical draws from
+    // a xorshift generator would mean the saved-back state is stuck.
+    assert!(draws.windows(2).any(|w| w[0] != w[1]));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn inject_or_run_inline_uses_a_worker_when_idle() {
+    use std::thread;
+
+    // An idle pool has sleeping workers, so saturation never
+    // triggers and the job is injected as usual.
+    let caller = thread::current().id();
+    let ran_on = ::inject_or_run_inline(true, || thread::current().id());
+    assert!(ran_on != caller);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn inject_or_run_inline_runs_inline_when_saturated() {
+    use std::sync::Barrier;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    // Saturate the global pool: park every worker on a barrier, then
+    // leave one more job waiting in the injector.
+    let n_threads = ::current_num_threads();
+    let release = Arc::new(Barrier::new(n_threads + 1));
+    let parked = Arc::new(AtomicUsize::new(0));
+    for _ in 0..n_threads {
+        let release = release.clone();
+        let parked = parked.clone();
+        ::spawn_async(move || {
+            parked.fetch_add(1, Ordering::SeqCst);
+            release.wait();
+        });
+    }
+    while parked.load(Ordering::SeqCst) < n_threads {
+        thread::yield_now();
+    }
+    let (tx, rx) = channel();
+    ::spawn_async(move || tx.send(()).unwrap());
+
+    // Every worker is awake but busy and a job is pending, so a
+    // willing caller gets the inline path.
+    let caller = thread::current().id();
+    let ran_on = ::inject_or_run_inline(true, || thread::current().id());
+    assert_eq!(ran_on, caller);
+
+    release.wait();
+    rx.recv().unwrap();
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn job_profiler_observes_executed_jobs() {
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    let timings = Arc::new(Mutex::new(Vec::new()));
+    let recorder = timings.clone();
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .job_profiler(move |timing: ::JobTiming| {
+                recorder.lock().unwrap().push(timing);
+            }))
+        .unwrap();
+    assert_eq!(pool.install(|| {
+                   thread::sleep(Duration::from_millis(5));
+                   22
+               }),
+               22);
+
+    // The profiler runs after the job has set its latch, so `install`
+    // can return a moment before the record lands: wait for it. The
+    // installed job slept for 5ms, so its recorded duration must
+    // reflect that.
+    while !timings.lock()
+                  .unwrap()
+                  .iter()
+                  .any(|timing| timing.duration >= Duration::from_millis(5)) {
+        thread::yield_now();
+    }
+    assert!(timings.lock().unwrap().iter().all(|timing| timing.worker < 2));
+}
+
+#[test]
+fn inject_checked_returns_jobs_after_terminate() {
+    use job::StackJob;
+    use latch::LockLatch;
+    use registry::Registry;
+
+    let registry = Registry::new(Configuration::new().num_threads(1)).unwrap();
+    unsafe {
+        // Accepted while the pool is alive.
+        let job = StackJob::new(|| 22, LockLatch::new());
+        assert!(registry.inject_checked(&[job.as_job_ref()]).is_ok());
+        job.latch.wait();
+        assert_eq!(job.into_result(), 22);
+
+        // Handed back, not queued (and not panicking), once
+        // termination has begun -- the race an external producer can
+        // lose against a pool shutdown.
+        let late = StackJob::new(|| (), LockLatch::new());
+        let late_ref = late.as_job_ref();
+        registry.terminate();
+        match registry.inject_checked(&[late_ref]) {
+            Err(jobs) => assert_eq!(jobs, vec![late_ref]),
+            Ok(()) => panic!("inject_checked() accepted a job after terminate"),
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn busy_workers_count_nested_pools() {
+    // A job on pool A installing into pool B keeps a worker of each
+    // pool awake at once -- exactly the oversubscription the counter
+    // is meant to surface. Other pools (the global one, pools of
+    // other tests) may contribute as well, so only a lower bound is
+    // asserted.
+    let pool_a = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let pool_b = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let (busy, factor) = pool_a.install(|| {
+        pool_b.install(|| (::total_busy_workers(), ::oversubscription_factor()))
+    });
+    assert!(busy >= 2, "expected both nested workers counted, got {}", busy);
+    assert!(factor >= 2.0 / ::num_cpus::get() as f64);
+}
+
+#[test]
+fn automatic_thread_count_is_at_least_one() {
+    // The automatic thread count must never come out as zero -- a
+    // zero-worker pool would accept injected jobs that no thread can
+    // ever run, hanging the first caller. `num_cpus` can report 0 in
+    // rare constrained (e.g. containerized) environments, so the
+    // resolution clamps.
+    assert!(Configuration::new().get_num_threads() >= 1);
+
+    // And a pool built from the automatic count is actually usable.
+    let pool = ThreadPool::new(Configuration::new()).unwrap();
+    assert!(pool.current_num_threads() >= 1);
+    assert_eq!(pool.install(|| 22), 22);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn drain_local_spawned_acts_as_local_barrier() {
+    use scope::scope;
+
+    // With a single worker nothing can be stolen, so all ten spawned
+    // jobs sit in the local deque when the drain is called -- and a
+    // job spawned by a drained job is drained in the same call.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let counter = AtomicUsize::new(0);
+    pool.install(|| {
+        scope(|s| {
+            for _ in 0..10 {
+                s.spawn(|s| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    s.spawn(|_| { counter.fetch_add(1, Ordering::SeqCst); });
+                });
+            }
+            ::drain_local_spawned();
+            assert_eq!(counter.load(Ordering::SeqCst), 20);
+        });
+    });
+
+    // Off-pool there is nothing local to drain; this must simply
+    // return.
+    ::drain_local_spawned();
+}
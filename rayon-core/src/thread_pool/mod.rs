@@ -9,7 +9,11 @@ use job::StackJob;
 use spawn_async;
 use std::sync::Arc;
 use std::error::Error;
-use registry::{Registry, WorkerThread};
+#[cfg(feature = "unstable")]
+use std::time::Duration;
+use registry::{self, Registry, WorkerThread};
+#[cfg(feature = "unstable")]
+use registry::RegistrySnapshot;
 
 mod test;
 
@@ -20,16 +24,54 @@ pub struct ThreadPool {
 impl ThreadPool {
     /// Constructs a new thread pool with the given configuration. If
     /// the configuration is not valid, returns a suitable `Err`
-    /// result.  See `InitError` for more details.
+    /// result. The boxed error can be downcast to
+    /// `ThreadPoolBuildError` to inspect the cause programmatically.
     pub fn new(configuration: Configuration) -> Result<ThreadPool, Box<Error>> {
         let registry = try!(Registry::new(configuration));
         Ok(ThreadPool { registry: registry })
     }
 
+    /// Constructs two thread pools at once. This is a convenience
+    /// for multi-pool architectures -- typically a small "blocking"
+    /// pool paired with a larger "compute" pool -- where jobs are
+    /// handed from one pool to the other with `inject_into()` rather
+    /// than going through the global registry. The two pools are
+    /// otherwise fully independent: dropping one does not affect the
+    /// other.
+    #[cfg(feature = "unstable")]
+    pub fn new_pair(config_a: Configuration,
+                    config_b: Configuration)
+                    -> Result<(ThreadPool, ThreadPool), Box<Error>> {
+        let registry_a = try!(Registry::new(config_a));
+        let registry_b = try!(Registry::new(config_b));
+        Ok((ThreadPool { registry: registry_a }, ThreadPool { registry: registry_b }))
+    }
+
+    /// Enqueues `op` to execute asynchronously in `other`. This is
+    /// the bridge half of `new_pair()`: a job running in this pool
+    /// (say, a blocking pool) can hand CPU-bound work to its partner
+    /// compute pool without blocking on the result. Equivalent to
+    /// `other.spawn_async(op)`.
+    #[cfg(feature = "unstable")]
+    pub fn inject_into<OP>(&self, other: &ThreadPool, op: OP)
+        where OP: FnOnce() + Send + 'static
+    {
+        // We assert that `other.registry` has not terminated.
+        unsafe { spawn_async::spawn_async_in(op, &other.registry) }
+    }
+
     /// Executes `op` within the threadpool. Any attempts to use
     /// `join`, `scope`, or parallel iterators will then operate
     /// within that threadpool.
     ///
+    /// If the calling thread is already a worker thread of this same
+    /// pool (e.g. because of a nested `install` on the same pool),
+    /// `op` simply runs inline on the current worker. Injecting a job
+    /// and blocking on it from within the pool itself could otherwise
+    /// deadlock: on a pool with one thread, the only worker that
+    /// could run the injected job would be the one blocked waiting
+    /// for it.
+    ///
     /// # Warning: thread-local data
     ///
     /// Because `op` is executing within the Rayon thread-pool,
@@ -43,9 +85,16 @@ impl ThreadPool {
         where OP: FnOnce() -> R + Send
     {
         unsafe {
+            let worker_thread = WorkerThread::current();
+            if !worker_thread.is_null() && (*worker_thread).registry().id() == self.registry.id() {
+                return op();
+            }
             let job_a = StackJob::new(op, LockLatch::new());
             self.registry.inject(&[job_a.as_job_ref()]);
-            job_a.latch.wait();
+            registry::grow_if_saturated(&self.registry);
+            self.registry.mark_blocked_waiter();
+            self.registry.wait_for_injected_latch(&job_a.latch);
+            self.registry.unmark_blocked_waiter();
             job_a.into_result()
         }
     }
@@ -97,6 +146,185 @@ impl ThreadPool {
         }
     }
 
+    /// Returns, for each worker thread, the fraction of time it
+    /// spent busy (not asleep waiting for work) since the previous
+    /// call to this method (or since the pool was created). All
+    /// workers report as fully busy unless the pool was created with
+    /// `Configuration::utilization_tracking()` enabled.
+    ///
+    /// This is a tuning aid: consistently low fractions suggest the
+    /// pool has more threads than the workload can use, while large
+    /// differences between workers point at load imbalance.
+    #[cfg(feature = "unstable")]
+    pub fn utilization(&self) -> Vec<f64> {
+        self.registry.utilization()
+    }
+
+    /// Blocks until all work in this thread-pool has drained: every
+    /// worker thread is idle and no job is waiting to be executed.
+    /// This is mainly useful for tests and batch pipelines that need
+    /// a quiescence point and would otherwise resort to sleeping.
+    ///
+    /// Note that this is inherently racy: it is only meaningful if
+    /// the caller knows that nothing will submit more work to the
+    /// pool. Must not be called from within the pool itself.
+    #[cfg(feature = "unstable")]
+    pub fn wait_until_idle(&self) {
+        self.registry.wait_until_idle();
+    }
+
+    /// Returns this pool's minimum split length: the piece size below
+    /// which the fork-join helpers run serially when given a
+    /// `min_chunk` of zero (see `Configuration::min_split_len()`).
+    /// Exposed so that user-written recursive helpers can agree with
+    /// the built-in ones on the threshold.
+    #[cfg(feature = "unstable")]
+    pub fn min_split_len(&self) -> usize {
+        self.registry.min_split_len()
+    }
+
+    /// Returns true if this pool currently appears saturated: every
+    /// worker is awake (presumably busy), no further worker could be
+    /// started, and at least one injected job is still waiting to be
+    /// picked up. Intended for admission control: a server ingesting
+    /// external requests can consult this to decide whether to accept
+    /// more work or shed load instead.
+    ///
+    /// This is a snapshot hint, not a guarantee -- the answer may be
+    /// stale by the time the caller acts on it, so use it to bias a
+    /// decision, not to enforce an invariant.
+    #[cfg(feature = "unstable")]
+    pub fn is_saturated(&self) -> bool {
+        self.registry.is_saturated()
+    }
+
+    /// Keeps this pool's workers awake and spinning for the given
+    /// window, so that work injected during it starts with minimal
+    /// wakeup latency. A latency-sensitive request handler can call
+    /// this right before an anticipated burst to "prime the pump";
+    /// after the window, normal sleeping resumes. The cost is real
+    /// CPU time -- idle workers spin for the whole window -- so keep
+    /// it short.
+    #[cfg(feature = "unstable")]
+    pub fn warm_up(&self, duration: Duration) {
+        self.registry.warm_up(duration);
+    }
+
+    /// Returns, for each worker, the deepest its deque has been
+    /// (approximately) since the pool was created or since the last
+    /// `reset_max_deque_depths()`. Deep deques point at
+    /// over-splitting, shallow ones at under-parallelization; see
+    /// `Configuration::min_split_len()` for the matching tuning knob.
+    #[cfg(feature = "unstable")]
+    pub fn max_deque_depths(&self) -> Vec<usize> {
+        self.registry.max_deque_depths()
+    }
+
+    /// Resets the `max_deque_depths()` high-water marks, scoping the
+    /// measurement to the program phase that follows.
+    #[cfg(feature = "unstable")]
+    pub fn reset_max_deque_depths(&self) {
+        self.registry.reset_max_deque_depths();
+    }
+
+    /// Returns the stack size the pool's worker threads were created
+    /// with, or `None` if they use the platform default. Together
+    /// with `current_num_threads()` and the mode getters below, this
+    /// lets diagnostic code log the realized settings of a pool --
+    /// the values captured at construction, after any defaults or
+    /// environment overrides were applied -- rather than the
+    /// `Configuration` it was asked for.
+    #[cfg(feature = "unstable")]
+    pub fn stack_size(&self) -> Option<usize> {
+        self.registry.stack_size()
+    }
+
+    /// Returns true if this pool steals in batches (see
+    /// `Configuration::steal_batching()`).
+    #[cfg(feature = "unstable")]
+    pub fn steal_batching(&self) -> bool {
+        self.registry.steal_batching()
+    }
+
+    /// Returns true if threads blocked in `install()` help drain the
+    /// injected queue (see `Configuration::cooperative_install()`).
+    #[cfg(feature = "unstable")]
+    pub fn cooperative_install(&self) -> bool {
+        self.registry.cooperative_install()
+    }
+
+    /// Returns this pool's scheduling policy for injected jobs (see
+    /// `Configuration::inject_priority()`).
+    #[cfg(feature = "unstable")]
+    pub fn inject_priority(&self) -> ::InjectPriority {
+        self.registry.inject_priority()
+    }
+
+    /// Returns a snapshot of the scheduling decisions this pool has
+    /// recorded so far -- empty unless it was built with
+    /// `Configuration::record_steal_trace()`. The trace can be fed to
+    /// `Configuration::replay_steal_trace()` to steer a later run
+    /// toward the same schedule.
+    #[cfg(feature = "unstable")]
+    pub fn scheduler_trace(&self) -> ::SchedulerTrace {
+        self.registry.scheduler_trace()
+    }
+
+    /// Returns true if `self` and `other` are handles to the very
+    /// same pool, i.e. share the same worker threads. When libraries
+    /// hand pools across crate boundaries, this lets code detect such
+    /// aliasing and avoid, say, nested `install()`s that would
+    /// oversubscribe the workers; see also `current_pool()`.
+    #[cfg(feature = "unstable")]
+    pub fn same_pool(&self, other: &ThreadPool) -> bool {
+        self.registry.id() == other.registry.id()
+    }
+
+    /// Returns true if every started worker thread of this pool is up
+    /// and running, i.e. a subsequent piece of work will not pay any
+    /// thread startup cost. Benchmarks can use this to decide whether
+    /// a warmup round is still needed, without blocking for one.
+    #[cfg(feature = "unstable")]
+    pub fn is_primed(&self) -> bool {
+        self.registry.is_primed()
+    }
+
+    /// Sets the number of active worker threads at runtime, clamped
+    /// to between one and the count this pool was created with (a
+    /// pool can never grow past that count, since worker slots are
+    /// allocated up front -- create the pool with the largest count
+    /// you may ever want, possibly with `lazy_threads()`, to leave
+    /// headroom). Growing starts workers immediately; shrinking is
+    /// asynchronous: the excess workers retire the next time they run
+    /// out of work, so a worker busy with a long job keeps running
+    /// until that job completes.
+    #[cfg(feature = "unstable")]
+    pub fn resize(&self, num_threads: usize) {
+        registry::resize_pool(&self.registry, num_threads);
+    }
+
+    /// Runs `op` with at most `num_threads` of this pool's workers
+    /// actively acquiring work. For the duration of `op`, the
+    /// remaining workers are marked dormant: they finish work they
+    /// already have but no longer steal or take injected jobs, so the
+    /// pool's effective parallelism drops -- useful for memory-bound
+    /// phases where extra threads only add bandwidth contention --
+    /// without tearing down and rebuilding the pool. The flags are
+    /// restored when `op` returns (or panics), unlike `resize()`,
+    /// which persists and actually parks threads.
+    ///
+    /// The limit is best-effort: workers already executing a job run
+    /// it to completion, and work spawned by `op` from outside the
+    /// dormant set proceeds normally. Values below one are treated as
+    /// one. When calls nest, each restores only the workers it
+    /// parked.
+    #[cfg(feature = "unstable")]
+    pub fn with_max_threads<OP, R>(&self, num_threads: usize, op: OP) -> R
+        where OP: FnOnce() -> R
+    {
+        self.registry.with_max_threads(num_threads, op)
+    }
+
     /// Spawns an asynchronous task in this thread-pool. See
     /// `spawn_async()` for more details.
     #[cfg(feature = "unstable")]
@@ -107,6 +335,126 @@ impl ThreadPool {
         unsafe { spawn_async::spawn_async_in(op, &self.registry) }
     }
 
+    /// Spawns a task with a user-assigned priority in this
+    /// thread-pool. See `spawn_prioritized()` for more details.
+    #[cfg(feature = "unstable")]
+    pub fn spawn_prioritized<OP>(&self, priority: i32, op: OP)
+        where OP: FnOnce() + Send + 'static
+    {
+        // We assert that `self.registry` has not terminated.
+        unsafe { spawn_async::spawn_prioritized_in(priority, op, &self.registry) }
+    }
+
+    /// Returns how many tracked detached tasks (see
+    /// `spawn_tracked()`) have been spawned into this pool but have
+    /// not yet finished.
+    #[cfg(feature = "unstable")]
+    pub fn pending_detached(&self) -> usize {
+        self.registry.pending_detached()
+    }
+
+    /// Blocks until every tracked detached task of this pool has
+    /// finished -- the graceful-shutdown companion of
+    /// `spawn_tracked()`. Stop submitting tracked tasks before
+    /// calling this, or the wait may never end.
+    #[cfg(feature = "unstable")]
+    pub fn wait_detached(&self) {
+        self.registry.wait_detached()
+    }
+
+    /// Attempts to execute one job that was injected into this pool
+    /// and has not yet been picked up by a worker, returning whether
+    /// any work was done. Callable from any thread: this is how an
+    /// otherwise-idle external thread (say, a main thread waiting on
+    /// something else) can donate cycles to the pool without becoming
+    /// a permanent worker, e.g.:
+    ///
+    /// ```rust,ignore
+    /// while !done() {
+    ///     if !pool.try_run_one() {
+    ///         std::thread::yield_now();
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Only the shared injected queue is drained; jobs already on a
+    /// worker's deque are not stolen, since the calling thread has no
+    /// deque of its own to take part in the stealing protocol with.
+    #[cfg(feature = "unstable")]
+    pub fn try_run_one(&self) -> bool {
+        self.registry.try_run_one()
+    }
+
+    /// Executes injected jobs on the calling thread until none are
+    /// pending, returning how many jobs ran. Jobs that the executed
+    /// jobs themselves inject (say, a `spawn_async()` fired from
+    /// inside another) are picked up by the same call.
+    ///
+    /// This is the driving end of a caller-driven pool (see
+    /// `Configuration::caller_driven()`): a host event loop calls
+    /// `run_pending()` whenever it is ready to donate time, and the
+    /// pool's work runs inline, with no OS threads involved. On an
+    /// ordinary pool it simply drains whatever the workers have not
+    /// picked up yet, like `try_run_one()` in a loop.
+    #[cfg(feature = "unstable")]
+    pub fn run_pending(&self) -> usize {
+        let mut count = 0;
+        while self.registry.try_run_one() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Takes a point-in-time diagnostic snapshot of this pool's state:
+    /// thread counts, queue lengths, the terminate flag and a
+    /// per-worker breakdown. Each field is read atomically, but the
+    /// fields are sampled one after another while the pool keeps
+    /// running, so they need not be mutually consistent; treat the
+    /// result as a diagnostic reading, not an invariant. Intended for
+    /// logging and debugging dashboards, e.g.:
+    ///
+    /// ```rust,ignore
+    /// let snap = pool.snapshot();
+    /// info!("pool: {} of {} workers busy, {} jobs queued",
+    ///       snap.threads_at_work, snap.num_threads, snap.pending_jobs);
+    /// ```
+    #[cfg(feature = "unstable")]
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        self.registry.snapshot()
+    }
+
+    /// Returns the range of valid worker indices for this pool,
+    /// `0..current_num_threads()`. This is the index space used by
+    /// `spawn_on()`, `current_thread_index()` and the start/exit
+    /// handlers, and is the supported way to enumerate workers when
+    /// placing work deliberately, e.g.:
+    ///
+    /// ```rust,ignore
+    /// for index in pool.worker_indices() {
+    ///     pool.spawn_on(index, move || prefetch_shard(index));
+    /// }
+    /// ```
+    ///
+    /// Every index in the range stays valid for the life of the pool;
+    /// this holds even for workers that are currently parked (lazy
+    /// pools, or after `resize()`), since targeting such a worker
+    /// starts it.
+    #[cfg(feature = "unstable")]
+    pub fn worker_indices(&self) -> ::std::ops::Range<usize> {
+        self.registry.worker_indices()
+    }
+
+    /// Spawns an asynchronous task reserved for the worker thread
+    /// with the given index of this thread-pool. See `spawn_on()`
+    /// for more details.
+    #[cfg(feature = "unstable")]
+    pub fn spawn_on<OP>(&self, index: usize, op: OP)
+        where OP: FnOnce() + Send + 'static
+    {
+        // We assert that `self.registry` has not terminated.
+        unsafe { spawn_async::spawn_on_in(index, op, &self.registry) }
+    }
+
     /// Spawns an asynchronous task in this thread-pool. See
     /// `spawn_future_async()` for more details.
     #[cfg(feature = "unstable")]
@@ -123,3 +471,87 @@ impl Drop for ThreadPool {
         self.registry.terminate();
     }
 }
+
+/// A non-owning reference to a pool, as returned by `current_pool()`.
+/// Unlike `ThreadPool` it neither keeps the pool alive nor terminates
+/// it on drop; it only supports identity checks against pool handles.
+#[cfg(feature = "unstable")]
+pub struct ThreadPoolRef {
+    registry: Arc<Registry>,
+}
+
+#[cfg(feature = "unstable")]
+impl ThreadPoolRef {
+    /// Returns true if this reference points at the same pool as
+    /// `pool`.
+    pub fn same_pool(&self, pool: &ThreadPool) -> bool {
+        self.registry.id() == pool.registry.id()
+    }
+
+    /// Returns the number of worker threads of the referenced pool.
+    pub fn current_num_threads(&self) -> usize {
+        self.registry.num_threads()
+    }
+}
+
+/// Returns a reference to the pool whose worker thread we are
+/// currently on, if any. Library code that receives a `ThreadPool`
+/// from its caller can combine this with `same_pool()` to detect "am
+/// I already inside this pool?" and run work directly instead of
+/// redundantly injecting it.
+#[cfg(feature = "unstable")]
+pub fn current_pool() -> Option<ThreadPoolRef> {
+    unsafe {
+        let worker_thread = WorkerThread::current();
+        if worker_thread.is_null() {
+            None
+        } else {
+            Some(ThreadPoolRef { registry: (*worker_thread).registry().clone() })
+        }
+    }
+}
+
+/// Runs `oper_a` on `pool_a` and `oper_b` on `pool_b`, in parallel,
+/// and returns both results. This is the multi-pool counterpart of
+/// `join()`, for asymmetric systems where the two pools are pinned
+/// to different hardware (say, performance versus efficiency cores):
+/// each closure is injected into its own pool, and the caller blocks
+/// until both have completed.
+///
+/// The two pools may be the same pool, in which case this degrades
+/// to injecting two jobs into it. If the caller is itself a worker
+/// thread of one of the pools, it executes that pool's work --
+/// including, possibly, one of the two closures -- while it waits,
+/// so calling this from inside a pool does not idle its worker.
+/// Unlike `join()`, both closures are always injected rather than
+/// pushed on a local deque, so this is *not* a cheap primitive to
+/// build recursive splitting on; use it at the coarse top level and
+/// plain `join()` below.
+///
+/// # Panics
+///
+/// If either closure panics, that panic is propagated once both
+/// sides have been waited for, like `join()`. If both panic, which
+/// of the two panics is propagated is unspecified.
+#[cfg(feature = "unstable")]
+pub fn join_on<A, B, RA, RB>(pool_a: &ThreadPool,
+                             oper_a: A,
+                             pool_b: &ThreadPool,
+                             oper_b: B)
+                             -> (RA, RB)
+    where A: FnOnce() -> RA + Send,
+          B: FnOnce() -> RB + Send,
+          RA: Send,
+          RB: Send
+{
+    unsafe {
+        let job_a = StackJob::new(oper_a, LockLatch::new());
+        let job_b = StackJob::new(oper_b, LockLatch::new());
+        pool_a.registry.inject(&[job_a.as_job_ref()]);
+        pool_b.registry.inject(&[job_b.as_job_ref()]);
+        registry::grow_if_saturated(&pool_a.registry);
+        registry::grow_if_saturated(&pool_b.registry);
+        registry::wait_all(&[&job_a.latch, &job_b.latch]);
+        (job_a.into_result(), job_b.into_result())
+    }
+}
@@ -8,6 +8,297 @@ use join;
 use super::ThreadPool;
 use unwind;
 
+#[test]
+#[cfg(feature = "unstable")]
+fn utilization_mostly_idle_pool() {
+    use std::thread;
+    use std::time::Duration;
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .utilization_tracking(true))
+        .unwrap();
+
+    // Give the workers ample time to fall asleep.
+    thread::sleep(Duration::from_millis(200));
+
+    let utilization = pool.utilization();
+    assert_eq!(utilization.len(), 2);
+    for &fraction in &utilization {
+        assert!(0.0 <= fraction && fraction < 0.5,
+                "idle worker reported busy-fraction {}",
+                fraction);
+    }
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn wait_until_idle_drains_spawns() {
+    use std::thread;
+    use std::time::Duration;
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    for _ in 0 .. 10 {
+        let counter = counter.clone();
+        pool.spawn_async(move || {
+            thread::sleep(Duration::from_millis(1));
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    pool.wait_until_idle();
+    assert_eq!(counter.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn max_injected_queue_backpressure() {
+    use std::thread;
+    use std::time::Duration;
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .max_injected_queue(4))
+        .unwrap();
+
+    // Inject far more jobs than the queue can hold; the injecting
+    // thread blocks whenever the bound is reached, but every job
+    // must still run eventually.
+    for _ in 0 .. 100 {
+        let counter = counter.clone();
+        pool.spawn_async(move || {
+            thread::sleep(Duration::from_millis(1));
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    pool.wait_until_idle();
+    assert_eq!(counter.load(Ordering::SeqCst), 100);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn new_pair_inject_into() {
+    use std::sync::mpsc::channel;
+
+    let (blocking, compute) = ThreadPool::new_pair(Configuration::new().num_threads(1),
+                                                   Configuration::new().num_threads(2))
+        .unwrap();
+    let compute = Arc::new(compute);
+
+    let (tx, rx) = channel();
+    blocking.install(|| {
+        // hand work from the blocking pool over to the compute pool
+        let compute_in_job = compute.clone();
+        let tx = tx.clone();
+        blocking.inject_into(&compute, move || {
+            tx.send(compute_in_job.current_thread_index()).unwrap();
+        });
+    });
+
+    let index = rx.recv().unwrap();
+    assert!(index.is_some(), "bridged job did not run in the compute pool");
+}
+
+#[test]
+fn single_inject_wakes_a_sleeping_pool() {
+    use std::{thread, time};
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(16)).unwrap();
+
+    // Give the whole pool time to fall asleep, then check that a
+    // lone injected job still gets picked up promptly even though
+    // only one sleeper is woken for it.
+    thread::sleep(time::Duration::from_millis(200));
+    for _ in 0..3 {
+        assert_eq!(pool.install(|| 22), 22);
+    }
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn lazy_pool_grows_on_demand() {
+    use broadcast::broadcast;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(4).lazy_threads(true)).unwrap();
+    assert_eq!(pool.registry.num_spawned_threads(), 1);
+    assert_eq!(pool.install(|| 22), 22);
+
+    // a broadcast needs every worker, so it brings a lazy pool up to
+    // full strength
+    let v = pool.install(|| broadcast(|i| i));
+    assert_eq!(v, vec![0, 1, 2, 3]);
+    assert_eq!(pool.registry.num_spawned_threads(), 4);
+}
+
+#[test]
+fn lazy_pool_workers_stop() {
+    let registry;
+
+    {
+        // once we exit this block, thread-pool will be dropped, and
+        // the workers that were never spawned must not hold up
+        // termination
+        let thread_pool =
+            ThreadPool::new(Configuration::new().num_threads(4).lazy_threads(true)).unwrap();
+        registry = thread_pool.registry.clone();
+        assert_eq!(thread_pool.install(|| 22), 22);
+    }
+
+    registry.wait_until_stopped();
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn resize_shrinks_and_regrows() {
+    use std::{thread, time};
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+    assert_eq!(pool.registry.num_spawned_threads(), 4);
+
+    // Shrinking is asynchronous: the excess workers retire once they
+    // next look for work.
+    pool.resize(2);
+    for _ in 0..100 {
+        if pool.registry.num_spawned_threads() == 2 {
+            break;
+        }
+        thread::sleep(time::Duration::from_millis(10));
+    }
+    assert_eq!(pool.registry.num_spawned_threads(), 2);
+    assert_eq!(pool.install(|| join(|| 1, || 2)), (1, 2));
+
+    // Growing restarts the retired workers right away; the count is
+    // clamped to the capacity the pool was created with.
+    pool.resize(100);
+    assert_eq!(pool.registry.num_spawned_threads(), 4);
+    assert_eq!(pool.install(|| join(|| 1, || 2)), (1, 2));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn resize_shrunken_pool_workers_stop() {
+    use std::{thread, time};
+
+    let registry;
+
+    {
+        // workers retired by a shrink must not hold up termination
+        // once the thread-pool is dropped
+        let thread_pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+        registry = thread_pool.registry.clone();
+        thread_pool.resize(1);
+        for _ in 0..100 {
+            if registry.num_spawned_threads() == 1 {
+                break;
+            }
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        assert_eq!(thread_pool.install(|| 22), 22);
+    }
+
+    registry.wait_until_stopped();
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn is_primed_after_warmup() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    pool.registry.wait_until_primed();
+    assert!(pool.is_primed());
+
+    // both calls are idempotent: waiting again returns right away,
+    // and the non-blocking check keeps reporting true
+    pool.registry.wait_until_primed();
+    assert!(pool.is_primed());
+}
+
+#[test]
+fn nested_install_same_pool_runs_inline() {
+    // With a single worker, a nested `install` that injected and
+    // blocked could never be served; it must run inline instead.
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let result = pool.install(|| {
+        let outer = pool.current_thread_index().unwrap();
+        pool.install(|| {
+            // still on the very same worker thread
+            assert_eq!(pool.current_thread_index(), Some(outer));
+            22
+        })
+    });
+    assert_eq!(result, 22);
+}
+
+#[test]
+fn len_hint_saturates_at_the_boundary() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+
+    // Drive the hint right up to the boundary; one more recorded
+    // push must pin it there, not wrap it back to zero.
+    pool.registry.set_len_hint(0, ::std::usize::MAX - 1);
+    pool.registry.bump_len_hint(0);
+    assert_eq!(pool.registry.len_hint(0), ::std::usize::MAX);
+    pool.registry.bump_len_hint(0);
+    assert_eq!(pool.registry.len_hint(0), ::std::usize::MAX);
+
+    // Even with the hint pinned at the ceiling, the scheduler must
+    // keep draining work normally.
+    assert_eq!(pool.install(|| join(|| 1, || 2)), (1, 2));
+
+    pool.registry.set_len_hint(0, 0);
+}
+
+#[test]
+fn try_inject_accepts_when_unbounded() {
+    use job::StackJob;
+    use latch::LockLatch;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    unsafe {
+        let job = StackJob::new(|| 22, LockLatch::new());
+        assert!(pool.registry.try_inject(&[job.as_job_ref()]),
+                "unbounded queue refused a job");
+        job.latch.wait();
+        assert_eq!(job.into_result(), 22);
+    }
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn event_sink_receives_events() {
+    let events = Arc::new(AtomicUsize::new(0));
+    let events_in_sink = events.clone();
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .event_sink(move |_| {
+                            events_in_sink.fetch_add(1, Ordering::SeqCst);
+                            // a panicking sink must not disturb the scheduler
+                            panic!()
+                        }))
+        .unwrap();
+
+    assert_eq!(pool.install(|| join(|| 1, || 2)), (1, 2));
+    assert!(events.load(Ordering::SeqCst) > 0,
+            "no scheduling events reached the sink");
+}
+
+#[test]
+fn leave_cores_free_clamps_to_one() {
+    // Reserving more cores than the machine has must still leave us
+    // one worker.
+    let pool = ThreadPool::new(Configuration::new().leave_cores_free(::std::usize::MAX)).unwrap();
+    assert_eq!(pool.current_num_threads(), 1);
+}
+
+#[test]
+fn leave_cores_free_yields_to_explicit_count() {
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(3)
+            .leave_cores_free(::std::usize::MAX))
+        .unwrap();
+    assert_eq!(pool.current_num_threads(), 3);
+}
+
 #[test]
 #[should_panic(expected = "Hello, world!")]
 fn panic_propagate() {
@@ -137,3 +428,998 @@ fn panic_thread_name() {
     assert_eq!(5, wait_for_counter(start_count));
     assert_eq!(5, wait_for_counter(exit_count));
 }
+
+#[test]
+fn steal_retries_pool_still_computes() {
+    // A minimal steal retry limit means workers go to sleep almost
+    // immediately when idle; work must still wake them and complete.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .steal_retries(1))
+        .unwrap();
+    for _ in 0..100 {
+        let (a, b) = pool.install(|| join(|| 10, || 22));
+        assert_eq!(a + b, 32);
+    }
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn custom_spawn_handler() {
+    use std::thread;
+    let spawn_count = Arc::new(AtomicUsize::new(0));
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .spawn_handler({
+                let spawn_count = spawn_count.clone();
+                move |thread| {
+                    spawn_count.fetch_add(1, Ordering::SeqCst);
+                    try!(thread::Builder::new().spawn(move || thread.run()));
+                    Ok(())
+                }
+            }))
+        .unwrap();
+
+    // Both workers went through our handler, and the pool works.
+    assert_eq!(spawn_count.load(Ordering::SeqCst), 2);
+    assert_eq!(pool.install(|| join(|| 1, || 2)), (1, 2));
+}
+
+#[test]
+fn pending_job_counter_balances_out() {
+    fn fib(n: usize) -> usize {
+        if n < 2 {
+            return n;
+        }
+        let (a, b) = join(|| fib(n - 1), || fib(n - 2));
+        a + b
+    }
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+    assert_eq!(pool.install(|| fib(16)), 987);
+
+    // Every push/inject was matched by a pop/steal/uninject, so the
+    // advisory counter must be back at zero; were it stuck above
+    // zero, every idle worker would spin instead of sleeping. (The
+    // registry method, unlike the `ThreadPool` wrapper, is available
+    // without the `unstable` feature.)
+    pool.registry.wait_until_idle();
+    assert_eq!(pool.registry.pending_jobs(), 0);
+}
+
+#[test]
+fn steal_batching_pool_computes() {
+    fn fib(n: usize) -> usize {
+        if n < 2 {
+            return n;
+        }
+        let (a, b) = join(|| fib(n - 1), || fib(n - 2));
+        a + b
+    }
+
+    // Batch stealing only changes which worker runs what, never what
+    // runs; the result (and the advisory counter balance) must come
+    // out as in the single-steal baseline.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(4)
+            .steal_batching(true))
+        .unwrap();
+    assert_eq!(pool.install(|| fib(16)), 987);
+    pool.registry.wait_until_idle();
+    assert_eq!(pool.registry.pending_jobs(), 0);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn scheduler_fuzz_pool_computes() {
+    fn fib(n: usize) -> usize {
+        if n < 2 {
+            return n;
+        }
+        let (a, b) = join(|| fib(n - 1), || fib(n - 2));
+        a + b
+    }
+
+    // The fuzzer only injects yields; it must never change what runs
+    // or lose a job, just shuffle the interleavings.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(4)
+            .scheduler_fuzz(0x5eed_5eed_5eed_5eed))
+        .unwrap();
+    assert_eq!(pool.install(|| fib(16)), 987);
+    pool.wait_until_idle();
+    assert_eq!(pool.registry.pending_jobs(), 0);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn job_accounting_balances_at_quiescence() {
+    fn fib(n: usize) -> usize {
+        if n < 2 {
+            return n;
+        }
+        let (a, b) = join(|| fib(n - 1), || fib(n - 2));
+        a + b
+    }
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+    assert_eq!(pool.install(|| fib(16)), 987);
+
+    // The exact debug-build accounting must balance once the pool is
+    // idle: a deficit would mean a job was lost, and `taken` running
+    // ahead of `recorded` would already have tripped the assertion in
+    // the scheduler itself. The registry-level `wait_until_idle()`
+    // keeps this runnable in a default-features debug build, where
+    // the `ThreadPool` wrapper is absent.
+    pool.registry.wait_until_idle();
+    let (recorded, taken) = pool.registry.job_accounting();
+    assert_eq!(recorded, taken);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn with_max_threads_restores_the_pool() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(4)).unwrap();
+
+    // Work still completes (just with less parallelism), and exactly
+    // the excess workers are dormant while the closure runs.
+    let result = pool.with_max_threads(2, || {
+        assert_eq!(pool.registry.num_dormant(), 2);
+        pool.install(|| join(|| 1, || 2))
+    });
+    assert_eq!(result, (1, 2));
+    assert_eq!(pool.registry.num_dormant(), 0);
+
+    // The flags are restored even when the closure panics.
+    let panic = unwind::halt_unwinding(|| pool.with_max_threads(1, || panic!("boom")));
+    assert!(panic.is_err());
+    assert_eq!(pool.registry.num_dormant(), 0);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn worker_indices_cover_the_pool() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(3)).unwrap();
+    assert_eq!(pool.worker_indices(), 0..3);
+
+    // Every index in the range is a valid spawn_on() target.
+    let hits = Arc::new(AtomicUsize::new(0));
+    for index in pool.worker_indices() {
+        let hits = hits.clone();
+        pool.spawn_on(index, move || {
+            hits.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    while hits.load(Ordering::SeqCst) < 3 {
+        ::std::thread::yield_now();
+    }
+}
+
+/// Common setup for the `InjectPriority` ordering tests: on a
+/// one-thread pool, arrange for the worker to come off a job with one
+/// job on its local deque and one in the injected queue, and report
+/// the order in which the two ran as `(local_rank, injected_rank)`.
+#[cfg(feature = "unstable")]
+fn inject_priority_ranks(priority: ::InjectPriority) -> (usize, usize) {
+    use std::usize;
+
+    let pool = Arc::new(ThreadPool::new(Configuration::new()
+            .num_threads(1)
+            .inject_priority(priority))
+        .unwrap());
+    let seq = Arc::new(AtomicUsize::new(0));
+    let local_rank = Arc::new(AtomicUsize::new(usize::MAX));
+    let injected_rank = Arc::new(AtomicUsize::new(usize::MAX));
+    let ready = Arc::new(AtomicUsize::new(0));
+    let go = Arc::new(AtomicUsize::new(0));
+
+    {
+        let pool = pool.clone();
+        let seq = seq.clone();
+        let local_rank = local_rank.clone();
+        let ready = ready.clone();
+        let go = go.clone();
+        pool.clone().spawn_async(move || {
+            // Runs on the worker, so this inner spawn lands on its
+            // local deque rather than in the injected queue.
+            pool.spawn_async(move || {
+                local_rank.store(seq.fetch_add(1, Ordering::SeqCst), Ordering::SeqCst);
+            });
+            ready.store(1, Ordering::SeqCst);
+            // Hold the worker here until the test has injected the
+            // competing job from the outside.
+            while go.load(Ordering::SeqCst) == 0 {
+                ::std::thread::yield_now();
+            }
+        });
+    }
+    while ready.load(Ordering::SeqCst) == 0 {
+        ::std::thread::yield_now();
+    }
+    {
+        let seq = seq.clone();
+        let injected_rank = injected_rank.clone();
+        pool.spawn_async(move || {
+            injected_rank.store(seq.fetch_add(1, Ordering::SeqCst), Ordering::SeqCst);
+        });
+    }
+    go.store(1, Ordering::SeqCst);
+
+    while local_rank.load(Ordering::SeqCst) == usize::MAX ||
+          injected_rank.load(Ordering::SeqCst) == usize::MAX {
+        ::std::thread::yield_now();
+    }
+    (local_rank.load(Ordering::SeqCst), injected_rank.load(Ordering::SeqCst))
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn inject_priority_prefer_local_runs_local_first() {
+    let (local, injected) = inject_priority_ranks(::InjectPriority::PreferLocal);
+    assert!(local < injected,
+            "local job ran at {} but injected at {}",
+            local,
+            injected);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn inject_priority_prefer_injected_runs_injected_first() {
+    let (local, injected) = inject_priority_ranks(::InjectPriority::PreferInjected);
+    assert!(injected < local,
+            "injected job ran at {} but local at {}",
+            injected,
+            local);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn inject_priority_round_robin_runs_both() {
+    // Which source gets the first turn depends on how many idle
+    // rounds the worker has already been through, so only the
+    // completion of both jobs is deterministic here.
+    let (local, injected) = inject_priority_ranks(::InjectPriority::RoundRobin);
+    assert!((local == 0 && injected == 1) || (local == 1 && injected == 0));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn try_run_one_drains_injected_jobs() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+
+    // Tie up the only worker in user code so that further injected
+    // jobs cannot be picked up by the pool itself. We must wait until
+    // the worker has actually taken the blocking job: otherwise the
+    // `try_run_one()` loop below could pop it and deadlock on the
+    // barrier itself.
+    let barrier = Arc::new(::std::sync::Barrier::new(2));
+    let blocked = Arc::new(AtomicUsize::new(0));
+    {
+        let barrier = barrier.clone();
+        let blocked = blocked.clone();
+        pool.spawn_async(move || {
+            blocked.store(1, Ordering::SeqCst);
+            barrier.wait();
+        });
+    }
+    while blocked.load(Ordering::SeqCst) == 0 {
+        ::std::thread::yield_now();
+    }
+
+    let ran = Arc::new(AtomicUsize::new(0));
+    {
+        let ran = ran.clone();
+        pool.spawn_async(move || {
+            ran.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+
+    // This thread is not a worker, but it can still drain the
+    // injected queue.
+    while !pool.try_run_one() {
+        ::std::thread::yield_now();
+    }
+    assert_eq!(ran.load(Ordering::SeqCst), 1);
+    assert!(!pool.try_run_one());
+
+    barrier.wait();
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn min_split_len_is_used_by_helpers() {
+    use reduce::reduce_range;
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .min_split_len(64))
+        .unwrap();
+    assert_eq!(pool.min_split_len(), 64);
+
+    // A `min_chunk` of zero picks up the pool's configured threshold;
+    // the result is the same either way, only the task granularity
+    // changes.
+    let n = 1000;
+    let sum = pool.install(|| reduce_range(0..n, 0, || 0, |i| i, |a, b| a + b));
+    assert_eq!(sum, n * (n - 1) / 2);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn with_min_split_len_overrides_and_nests() {
+    use reduce::reduce_range;
+    use registry::{current_min_split_len, with_min_split_len};
+    use unwind;
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .min_split_len(64))
+        .unwrap();
+
+    pool.install(|| {
+        assert_eq!(current_min_split_len(), 64);
+        with_min_split_len(7, || {
+            assert_eq!(current_min_split_len(), 7);
+
+            // Nested overrides shadow, and restore on return.
+            with_min_split_len(3, || assert_eq!(current_min_split_len(), 3));
+            assert_eq!(current_min_split_len(), 7);
+
+            // Values below one are clamped, like the config setting.
+            with_min_split_len(0, || assert_eq!(current_min_split_len(), 1));
+
+            // Helpers called in the scope pick up the override via
+            // their `min_chunk == 0` path; the result is unchanged,
+            // only the granularity is.
+            let n = 1000;
+            let sum = reduce_range(0..n, 0, || 0, |i| i, |a, b| a + b);
+            assert_eq!(sum, n * (n - 1) / 2);
+        });
+
+        // The pool-wide default is back, even when a scope is left by
+        // panic rather than by return.
+        assert_eq!(current_min_split_len(), 64);
+        let result = unwind::halt_unwinding(|| with_min_split_len(7, || panic!("escape")));
+        assert!(result.is_err());
+        assert_eq!(current_min_split_len(), 64);
+    });
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn is_saturated_reflects_injected_backlog() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+
+    // Tie up the only worker, then inject a job it cannot get to:
+    // every worker is awake and work is queued, i.e. saturated.
+    let barrier = Arc::new(::std::sync::Barrier::new(2));
+    let blocked = Arc::new(AtomicUsize::new(0));
+    {
+        let barrier = barrier.clone();
+        let blocked = blocked.clone();
+        pool.spawn_async(move || {
+            blocked.store(1, Ordering::SeqCst);
+            barrier.wait();
+        });
+    }
+    while blocked.load(Ordering::SeqCst) == 0 {
+        ::std::thread::yield_now();
+    }
+    pool.spawn_async(|| ());
+    assert!(pool.is_saturated());
+
+    // Once the backlog has drained, the pool is saturated no more.
+    barrier.wait();
+    pool.wait_until_idle();
+    assert!(!pool.is_saturated());
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn cooperative_install_runs_on_busy_pool() {
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(1)
+            .cooperative_install(true))
+        .unwrap();
+
+    // Tie up the only worker (making sure it has actually taken the
+    // blocking job, as in `try_run_one_drains_injected_jobs`). A
+    // plain `install()` would now block forever; a cooperative one
+    // runs the injected closure on the calling thread instead.
+    let barrier = Arc::new(::std::sync::Barrier::new(2));
+    let blocked = Arc::new(AtomicUsize::new(0));
+    {
+        let barrier = barrier.clone();
+        let blocked = blocked.clone();
+        pool.spawn_async(move || {
+            blocked.store(1, Ordering::SeqCst);
+            barrier.wait();
+        });
+    }
+    while blocked.load(Ordering::SeqCst) == 0 {
+        ::std::thread::yield_now();
+    }
+
+    assert_eq!(pool.install(|| 22), 22);
+
+    barrier.wait();
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn warm_up_keeps_workers_spinning() {
+    use std::thread;
+    use std::time::Duration;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    // `wait_until_idle()` returns once both workers are in the deep
+    // sleep.
+    pool.wait_until_idle();
+    assert_eq!(pool.registry.num_sleeping_workers(), 2);
+
+    pool.warm_up(Duration::from_secs(2));
+    // The sleepers wake promptly...
+    while pool.registry.num_sleeping_workers() > 0 {
+        thread::yield_now();
+    }
+    // ...and refuse to go back to sleep inside the window.
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(pool.registry.num_sleeping_workers(), 0);
+    assert_eq!(pool.install(|| 22), 22);
+}
+
+#[test]
+fn install_returns_typed_result_through_latch() {
+    // `install()` from outside the pool exercises the full
+    // `StackJob` result protocol: the worker writes a typed,
+    // heap-owning result into the job's slot before setting the
+    // latch, and the blocked caller reads it only after the wait
+    // returns.
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let result: Vec<String> = pool.install(|| (0..10).map(|i| i.to_string()).collect());
+    assert_eq!(result.len(), 10);
+    assert_eq!(result[7], "7");
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn max_deque_depths_records_and_resets() {
+    fn fib(n: usize) -> usize {
+        if n < 2 {
+            return n;
+        }
+        let (a, b) = join(|| fib(n - 1), || fib(n - 2));
+        a + b
+    }
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    assert_eq!(pool.max_deque_depths(), vec![0, 0]);
+
+    // The recursion pushes one job per split, so the calling worker's
+    // deque must have gotten several jobs deep at some point.
+    assert_eq!(pool.install(|| fib(12)), 144);
+    let depths = pool.max_deque_depths();
+    assert_eq!(depths.len(), 2);
+    assert!(depths.iter().any(|&depth| depth >= 3),
+            "no worker saw a deep deque: {:?}",
+            depths);
+
+    // Resetting scopes the next measurement to a quiet phase.
+    pool.wait_until_idle();
+    pool.reset_max_deque_depths();
+    assert_eq!(pool.max_deque_depths(), vec![0, 0]);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn same_pool_compares_identity() {
+    let pool_a = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let pool_b = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    assert!(pool_a.same_pool(&pool_a));
+    assert!(!pool_a.same_pool(&pool_b));
+    assert!(!pool_b.same_pool(&pool_a));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn current_pool_identifies_the_enclosing_pool() {
+    use super::current_pool;
+
+    let pool_a = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let pool_b = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+
+    assert!(current_pool().is_none());
+    pool_a.install(|| {
+        let here = current_pool().unwrap();
+        assert!(here.same_pool(&pool_a));
+        assert!(!here.same_pool(&pool_b));
+        assert_eq!(here.current_num_threads(), 2);
+    });
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn shrink_idle_deques_releases_sticky_storage() {
+    use broadcast::broadcast;
+    use registry;
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(1)
+            .shrink_idle_deques(true))
+        .unwrap();
+
+    // Grow the worker's sticky queue with a burst, then drain it.
+    let n_done = Arc::new(AtomicUsize::new(0));
+    {
+        let n_done = n_done.clone();
+        pool.spawn_async(move || {
+            for _ in 0..64 {
+                let n_done = n_done.clone();
+                ::spawn_sticky(move || {
+                    n_done.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+    }
+    while n_done.load(Ordering::SeqCst) < 64 {
+        ::std::thread::yield_now();
+    }
+
+    // Once the worker has gone idle (it is asleep by the time
+    // `wait_until_idle()` returns), the emptied queue's buffer must
+    // have been released.
+    pool.wait_until_idle();
+    let capacities = pool.install(|| {
+        broadcast(|_| unsafe { (*registry::WorkerThread::current()).sticky_capacity() })
+    });
+    assert_eq!(capacities, vec![0]);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn settings_getters_report_realized_values() {
+    use InjectPriority;
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(3)
+            .stack_size(2 * 1024 * 1024)
+            .steal_batching(true)
+            .inject_priority(InjectPriority::PreferInjected))
+        .unwrap();
+    assert_eq!(pool.current_num_threads(), 3);
+    assert_eq!(pool.stack_size(), Some(2 * 1024 * 1024));
+    assert!(pool.steal_batching());
+    assert!(!pool.cooperative_install());
+    assert_eq!(pool.inject_priority(), InjectPriority::PreferInjected);
+
+    // Defaults report back as such.
+    let plain = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    assert_eq!(plain.stack_size(), None);
+    assert!(!plain.steal_batching());
+    assert_eq!(plain.inject_priority(), InjectPriority::PreferLocal);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn steal_trace_records_thief_and_victim() {
+    use scope::scope;
+    use std::thread;
+
+    // The worker running the scope body spins until the spawned job
+    // has run, and since a spinning worker never pops its own deque,
+    // the only way the job can run is for the other worker to steal
+    // it: at least one steal is guaranteed.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .record_steal_trace(true))
+        .unwrap();
+    let flag = AtomicUsize::new(0);
+    pool.install(|| {
+        scope(|s| {
+            s.spawn(|_| { flag.store(1, Ordering::SeqCst); });
+            while flag.load(Ordering::SeqCst) == 0 {
+                thread::yield_now();
+            }
+        })
+    });
+
+    let trace = pool.scheduler_trace();
+    assert!(!trace.records().is_empty());
+    for record in trace.records() {
+        assert!(record.thief < 2);
+        assert!(record.victim < 2);
+        assert!(record.thief != record.victim);
+    }
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn scheduler_trace_empty_without_recording() {
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    pool.install(|| join(|| (), || ()));
+    assert!(pool.scheduler_trace().records().is_empty());
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn replayed_trace_completes_the_workload() {
+    use scope::scope;
+    use std::thread;
+
+    fn workload(pool: &ThreadPool) -> usize {
+        let count = AtomicUsize::new(0);
+        pool.install(|| for _ in 0..10 {
+                         scope(|s| {
+                             s.spawn(|_| { count.fetch_add(1, Ordering::SeqCst); });
+                             while count.load(Ordering::SeqCst) % 2 == 0 {
+                                 thread::yield_now();
+                             }
+                             s.spawn(|_| { count.fetch_add(1, Ordering::SeqCst); });
+                         });
+                     });
+        count.load(Ordering::SeqCst)
+    }
+
+    // Record the steal schedule of one run...
+    let recorder = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .record_steal_trace(true))
+        .unwrap();
+    assert_eq!(workload(&recorder), 20);
+    let trace = recorder.scheduler_trace();
+    assert!(!trace.records().is_empty());
+
+    // ...and drive a second pool through the same workload with it.
+    // Replay is best-effort, so the assertion is about liveness and
+    // the result, not about reproducing the schedule bit for bit.
+    let replayer = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .replay_steal_trace(trace))
+        .unwrap();
+    assert_eq!(workload(&replayer), 20);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn steal_order_pool_computes() {
+    fn fib(n: usize) -> usize {
+        if n < 2 {
+            return n;
+        }
+        let (a, b) = join(|| fib(n - 1), || fib(n - 2));
+        a + b
+    }
+
+    // A steal order only changes which victim a thief probes first,
+    // never what runs; the result must match the random baseline.
+    // Rank victims in descending index order, unlike the default
+    // ascending rotation.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(4)
+            .steal_order(|index| (0..4).rev().filter(|&v| v != index).collect()))
+        .unwrap();
+    assert_eq!(pool.install(|| fib(16)), 987);
+    pool.wait_until_idle();
+    assert_eq!(pool.registry.pending_jobs(), 0);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn steal_order_tolerates_bogus_orders() {
+    use scope::scope;
+    use std::thread;
+
+    // Out-of-range entries, duplicates, the worker's own index, and
+    // omitted victims are all sanitized at pool construction; in a
+    // two-worker pool every worker must still be able to steal. The
+    // scope body spins until the spawned job has run, so the job
+    // *must* be stolen for the test to complete.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .steal_order(|index| vec![99, index, index]))
+        .unwrap();
+    let flag = AtomicUsize::new(0);
+    pool.install(|| {
+        scope(|s| {
+            s.spawn(|_| { flag.store(1, Ordering::SeqCst); });
+            while flag.load(Ordering::SeqCst) == 0 {
+                thread::yield_now();
+            }
+        })
+    });
+    assert_eq!(flag.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn caller_driven_run_pending_drains_follow_ups() {
+    let pool = Arc::new(ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .caller_driven(true))
+        .unwrap());
+    assert_eq!(pool.registry.num_spawned_threads(), 0);
+
+    let count = Arc::new(AtomicUsize::new(0));
+    {
+        let pool = pool.clone();
+        let count = count.clone();
+        pool.clone().spawn_async(move || {
+            count.fetch_add(1, Ordering::SeqCst);
+            // A follow-up injected while draining must be run by the
+            // same `run_pending()` call.
+            let count = count.clone();
+            pool.spawn_async(move || { count.fetch_add(1, Ordering::SeqCst); });
+        });
+    }
+
+    // With no workers, nothing can have run yet -- deterministically.
+    assert_eq!(count.load(Ordering::SeqCst), 0);
+    assert_eq!(pool.run_pending(), 2);
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+    assert_eq!(pool.registry.num_spawned_threads(), 0);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn caller_driven_install_runs_on_caller() {
+    use std::thread;
+
+    // With no workers to hand the job to, the blocked `install()`
+    // must execute it itself, on the calling thread.
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(4)
+            .caller_driven(true))
+        .unwrap();
+    let caller = thread::current().id();
+    let (value, ran_on) = pool.install(|| (22, thread::current().id()));
+    assert_eq!(value, 22);
+    assert_eq!(ran_on, caller);
+    assert_eq!(pool.registry.num_spawned_threads(), 0);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn strict_inject_order_runs_jobs_in_submission_order() {
+    use std::sync::Mutex;
+    use std::thread;
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(1)
+            .strict_inject_order(true))
+        .unwrap();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let gate = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+
+    // Hold the only worker so that the whole sequence is queued
+    // before any of it runs.
+    {
+        let gate = gate.clone();
+        pool.spawn_async(move || while gate.load(Ordering::SeqCst) == 0 {
+                             thread::yield_now();
+                         });
+    }
+    for i in 0..100 {
+        let order = order.clone();
+        pool.spawn_async(move || { order.lock().unwrap().push(i); });
+    }
+    // Strict FIFO means the job submitted last runs last.
+    {
+        let done = done.clone();
+        pool.spawn_async(move || { done.store(1, Ordering::SeqCst); });
+    }
+
+    gate.store(1, Ordering::SeqCst);
+    while done.load(Ordering::SeqCst) == 0 {
+        thread::yield_now();
+    }
+    let order = order.lock().unwrap();
+    assert_eq!(*order, (0..100).collect::<Vec<usize>>());
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_on_runs_each_side_on_its_pool() {
+    use thread_pool::{current_pool, join_on};
+
+    let pool_a = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let pool_b = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let (a, b) = join_on(&pool_a,
+                         || {
+                             assert!(current_pool().unwrap().same_pool(&pool_a));
+                             1
+                         },
+                         &pool_b,
+                         || {
+                             assert!(current_pool().unwrap().same_pool(&pool_b));
+                             2
+                         });
+    assert_eq!((a, b), (1, 2));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn join_on_from_inside_one_of_the_pools() {
+    use thread_pool::join_on;
+
+    // The caller occupies the only worker of pool A, so its own side
+    // can only run if the blocked caller cooperatively executes pool
+    // A's injected work while waiting.
+    let pool_a = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let pool_b = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let (a, b) = pool_a.install(|| join_on(&pool_a, || 1, &pool_b, || 2));
+    assert_eq!((a, b), (1, 2));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+#[should_panic(expected = "Hello, world!")]
+fn join_on_propagates_panics() {
+    use thread_pool::join_on;
+
+    let pool_a = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    let pool_b = ThreadPool::new(Configuration::new().num_threads(1)).unwrap();
+    join_on(&pool_a, || 1, &pool_b, || panic!("Hello, world!"));
+}
+
+#[test]
+fn local_queue_cap_bounds_deque_depth() {
+    use scope;
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(1)
+            .local_queue_cap(4))
+        .unwrap();
+    let counter = &AtomicUsize::new(0);
+    pool.install(|| {
+        scope(|s| for _ in 0..100 {
+            s.spawn(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        });
+    });
+    assert_eq!(counter.load(Ordering::SeqCst), 100);
+    // Spawning 100 jobs in a burst would normally drive the worker's
+    // deque about that deep; with the cap, everything past it spilled
+    // to the injected queue and the high-water mark stays bounded.
+    // The cap itself is ungated, so go through the registry method
+    // to keep this assertion runnable without the `unstable` feature.
+    assert!(pool.registry.max_deque_depths()[0] <= 4,
+            "deque depth {} exceeds the configured cap",
+            pool.registry.max_deque_depths()[0]);
+}
+
+#[test]
+fn local_queue_cap_pool_computes() {
+    use scope;
+
+    let pool = ThreadPool::new(Configuration::new()
+            .num_threads(2)
+            .local_queue_cap(1))
+        .unwrap();
+    let counter = &AtomicUsize::new(0);
+    pool.install(|| {
+        scope(|s| for _ in 0..200 {
+            s.spawn(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        });
+    });
+    assert_eq!(counter.load(Ordering::SeqCst), 200);
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn snapshot_reports_pool_state() {
+    use std::thread;
+
+    let pool = ThreadPool::new(Configuration::new().num_threads(3)).unwrap();
+    // Make sure the workers have started before sampling, so that the
+    // per-worker `started` flags are deterministic.
+    while !pool.is_primed() {
+        thread::yield_now();
+    }
+
+    let snap = pool.snapshot();
+    assert_eq!(snap.num_threads, 3);
+    assert_eq!(snap.num_spawned, 3);
+    assert!(snap.threads_at_work <= snap.num_spawned);
+    assert!(!snap.terminating);
+    assert_eq!(snap.workers.len(), 3);
+    for (index, worker) in snap.workers.iter().enumerate() {
+        assert_eq!(worker.index, index);
+        assert_eq!(worker.consecutive_panics, 0);
+        assert!(worker.started);
+        assert!(worker.queued <= worker.max_queued || worker.max_queued == 0);
+    }
+
+    // The snapshot is a plain data type: cloning and debug-formatting
+    // it must work away from the pool.
+    let copy = snap.clone();
+    let formatted = format!("{:?}", copy);
+    assert!(formatted.contains("num_threads: 3"));
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn woken_worker_sweeps_for_fanned_out_work() {
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    // A single injected root job fans out a subtask and then blocks
+    // until it completes. The root occupies the worker that won the
+    // race for it, so the subtask can only run if another worker,
+    // woken from sleep along the way, sweeps for work instead of
+    // going straight back to sleep.
+    let pool = Arc::new(ThreadPool::new(Configuration::new().num_threads(2)).unwrap());
+
+    // Let both workers fall asleep first, so the injection below
+    // actually exercises the wake path.
+    while pool.snapshot().num_sleeping < 2 {
+        thread::yield_now();
+    }
+
+    let (sub_tx, sub_rx) = channel();
+    let (done_tx, done_rx) = channel();
+    {
+        let pool_in_job = pool.clone();
+        pool.spawn_async(move || {
+            pool_in_job.spawn_async(move || sub_tx.send(()).unwrap());
+            // Block the winning worker until the other one has
+            // picked the subtask up.
+            sub_rx.recv().unwrap();
+            done_tx.send(()).unwrap();
+        });
+    }
+    done_rx.recv().unwrap();
+}
+
+#[test]
+#[cfg(feature = "unstable")]
+fn idle_and_busy_callbacks_alternate() {
+    use std::thread;
+
+    let idle_count = Arc::new(AtomicUsize::new(0));
+    let busy_count = Arc::new(AtomicUsize::new(0));
+    let pool = {
+        let idle_count = idle_count.clone();
+        let busy_count = busy_count.clone();
+        ThreadPool::new(Configuration::new()
+                .num_threads(2)
+                .on_idle(move || {
+                    idle_count.fetch_add(1, Ordering::SeqCst);
+                })
+                .on_busy(move || {
+                    busy_count.fetch_add(1, Ordering::SeqCst);
+                }))
+            .unwrap()
+    };
+
+    // A freshly built pool has no work: both workers fall asleep and
+    // `on_idle` fires.
+    while idle_count.load(Ordering::SeqCst) == 0 {
+        thread::yield_now();
+    }
+    let idle_before = idle_count.load(Ordering::SeqCst);
+
+    // Injecting work wakes the pool, firing `on_busy`; once the job
+    // is done and the workers settle again, `on_idle` fires anew.
+    pool.install(|| ());
+    while idle_count.load(Ordering::SeqCst) == idle_before {
+        thread::yield_now();
+    }
+
+    // The callbacks are edge-triggered and strictly alternate: every
+    // busy period is bracketed by idle reports, so the busy count
+    // never exceeds the idle count and never lags it by more than
+    // one. In particular the wake/sleep churn inside `install()` --
+    // many tickles, two workers settling -- must not multiply either
+    // callback.
+    let busy = busy_count.load(Ordering::SeqCst);
+    let idle = idle_count.load(Ordering::SeqCst);
+    assert!(busy >= 1, "injecting work never fired on_busy");
+    assert!(busy <= idle && idle <= busy + 1,
+            "transitions out of step: {} idle vs {} busy",
+            idle,
+            busy);
+}
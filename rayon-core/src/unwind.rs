@@ -4,18 +4,73 @@
 
 use libc;
 use std::any::Any;
+use std::cell::Cell;
+use std::mem;
+use std::isize;
 use std::panic::{self, AssertUnwindSafe};
+use std::process;
 use std::io::stderr;
 use std::io::prelude::*;
+use std::sync::atomic::{AtomicIsize, Ordering};
 use std::thread;
 
+/// Exit code used when Rayon aborts the process, stored process-wide
+/// (see `Configuration::abort_exit_code()`). The sentinel
+/// `isize::MIN` means "not configured": we call `libc::abort()` as
+/// before, so supervisors keep seeing SIGABRT unless an exit code was
+/// explicitly chosen.
+static ABORT_EXIT_CODE: AtomicIsize = AtomicIsize::new(isize::MIN);
+
+/// Installs `code` as the process exit code for Rayon-internal
+/// aborts. Process-wide: the last configured pool wins.
+pub fn set_abort_exit_code(code: i32) {
+    ABORT_EXIT_CODE.store(code as isize, Ordering::SeqCst);
+}
+
+/// Terminates the process after an internal failure, honoring the
+/// configured exit code if there is one. Callers are expected to have
+/// written an explanation to stderr first.
+pub fn abort() -> ! {
+    match ABORT_EXIT_CODE.load(Ordering::SeqCst) {
+        isize::MIN => unsafe { libc::abort() },
+        code => process::exit(code as i32),
+    }
+}
+
+thread_local! {
+    /// Set on the worker threads of pools built with
+    /// `Configuration::panic_abort()`: user panics on such a thread
+    /// abort the process at the panic site instead of being captured.
+    static ABORT_USER_PANICS: Cell<bool> = Cell::new(false)
+}
+
+/// Marks the current thread so that `halt_unwinding()` aborts on a
+/// panic rather than capturing it (see
+/// `Configuration::panic_abort()`). Worker threads call this at
+/// startup; the flag being thread-local is what scopes the behavior
+/// to the configured pool.
+pub fn set_abort_on_user_panic(enabled: bool) {
+    ABORT_USER_PANICS.with(|flag| flag.set(enabled));
+}
+
 /// Executes `f` and captures any panic, translating that panic into a
 /// `Err` result. The assumption is that any panic will be propagated
 /// later with `resume_unwinding`, and hence `f` can be treated as
 /// exception safe.
+///
+/// On a thread marked by `set_abort_on_user_panic()`, nothing is
+/// captured: a panic trips the abort guard while the panicking
+/// frames are still on the stack, so the resulting backtrace (and a
+/// debugger) sees the panic site rather than some later join point.
 pub fn halt_unwinding<F, R>(func: F) -> thread::Result<R>
     where F: FnOnce() -> R
 {
+    if ABORT_USER_PANICS.with(|flag| flag.get()) {
+        let guard = AbortOnUserPanic;
+        let result = func();
+        mem::forget(guard);
+        return Ok(result);
+    }
     panic::catch_unwind(AssertUnwindSafe(func))
 }
 
@@ -27,9 +82,35 @@ pub struct AbortIfPanic;
 
 impl Drop for AbortIfPanic {
     fn drop(&mut self) {
-        unsafe {
-            let _ = writeln!(&mut stderr(), "Rayon: detected unexpected panic; aborting");
-            libc::abort();
-        }
+        let _ = writeln!(&mut stderr(), "Rayon: detected unexpected panic; aborting");
+        abort();
+    }
+}
+
+/// The guard behind `Configuration::panic_abort()`: distinguishes a
+/// deliberate abort-on-user-panic from the "unexpected panic" of
+/// `AbortIfPanic`, which signals a bug in Rayon itself.
+struct AbortOnUserPanic;
+
+impl Drop for AbortOnUserPanic {
+    fn drop(&mut self) {
+        let _ = writeln!(&mut stderr(),
+                         "Rayon: user panic with panic_abort configured; aborting");
+        abort();
+    }
+}
+
+/// Like `AbortIfPanic`, but guarding against a worker thread leaving
+/// the scheduler's main loop through a path we did not anticipate
+/// (`mem::forget` it on each legitimate exit). Without this, the rest
+/// of the pool would silently wait forever for latches the departed
+/// worker was responsible for; better to fail loudly.
+pub struct AbortOnUnexpectedExit;
+
+impl Drop for AbortOnUnexpectedExit {
+    fn drop(&mut self) {
+        let _ = writeln!(&mut stderr(),
+                         "Rayon: worker thread exited the main loop unexpectedly; aborting");
+        abort();
     }
 }
@@ -38,6 +38,8 @@ pub use rayon_core::ThreadPool;
 pub use rayon_core::join;
 pub use rayon_core::{scope, Scope};
 #[cfg(feature = "unstable")]
+pub use rayon_core::{local_scope, LocalScope};
+#[cfg(feature = "unstable")]
 pub use rayon_core::spawn_async;
 #[cfg(feature = "unstable")]
 pub use rayon_core::spawn_future_async;
@@ -0,0 +1,19 @@
+extern crate rayon;
+
+use rayon::local_scope;
+use std::rc::Rc;
+
+fn main() {
+    // `local_scope()` relaxes the `Send` bound on *spawned* closures,
+    // because they never leave the worker that owns the scope. The
+    // scope body itself may still be shipped to a worker thread, so
+    // it must not capture `!Send` data from outside the scope: the
+    // scratch has to be created inside the body instead.
+    let rc = Rc::new(22);
+    local_scope(|s| {
+        //~^ ERROR cannot be sent between threads safely
+        s.spawn(move |_| {
+            let _ = Rc::clone(&rc);
+        });
+    });
+}
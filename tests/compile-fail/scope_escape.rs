@@ -0,0 +1,13 @@
+extern crate rayon;
+
+fn main() {
+    // The `Scope` reference must not escape the scope closure: spawning
+    // through it after `scope()` has returned would break the
+    // wait-for-completion guarantee that scoped borrows rely on.
+    let mut escaped = None;
+    rayon::scope(|s| {
+        //~^ ERROR cannot be shared between threads safely
+        //~| ERROR cannot be shared between threads safely
+        escaped = Some(s);
+    });
+}